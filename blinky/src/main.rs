@@ -26,7 +26,7 @@ actuator_set!(
     LedSetError
 );
 
-fn get_run_commands<const TIMER_HZ: u32>() -> [Command<TIMER_HZ, LedId, (), ()>; 6] {
+fn get_run_commands<const TIMER_HZ: u32>() -> [Command<TIMER_HZ, LedId, (), (), ()>; 6] {
     [
         Command::Led(
             LedId::Green,
@@ -100,7 +100,8 @@ fn main() -> ! {
     let red_led_timer = super_timer.sub();
     let red_led = LedDevice::new(red_led_pin, red_led_timer);
 
-    let mut robot = RobotBuilder::new()
+    let robot_timer = super_timer.sub();
+    let mut robot = RobotBuilder::new(robot_timer)
         .with_leds(LedSet::new(green_led, blue_led, red_led))
         .build()
         .with_run_commands(&get_run_commands())
@@ -116,10 +117,9 @@ fn main() -> ! {
             }
         }
 
+        // the robot halts and latches until reset
         if let Poll::Ready(Err(err)) = robot.poll() {
-            defmt::println!("Unexpected error: {}", Debug2Format(&err));
-
-            robot.stop();
+            defmt::println!("Unexpected error: {}", Debug2Format(err));
         }
     }
 }
@@ -3,10 +3,12 @@ use robokit::{AxisAction, Command, LedAction};
 
 use crate::actuators::{AxisId, LedId, SpindleId};
 
-type BotCommand<const TIMER_HZ: u32> = Command<TIMER_HZ, LedId, AxisId, SpindleId>;
+type BotCommand<const TIMER_HZ: u32> = Command<TIMER_HZ, LedId, AxisId, SpindleId, ()>;
 
-pub fn get_run_commands<const TIMER_HZ: u32>() -> [BotCommand<TIMER_HZ>; 9] {
+pub fn get_run_commands<const TIMER_HZ: u32>() -> [BotCommand<TIMER_HZ>; 15] {
     [
+        // one pass, raise the count for more
+        Command::RepeatStart { count: 1 },
         Command::Led(
             LedId::Green,
             LedAction::Blink {
@@ -46,6 +48,11 @@ pub fn get_run_commands<const TIMER_HZ: u32>() -> [BotCommand<TIMER_HZ>; 9] {
                 position_in_millimeters: 120_f64,
             },
         ),
+        Command::Call { label: "blinks" },
+        Command::RepeatEnd,
+        // the end of the program, subroutines follow
+        Command::Return,
+        Command::Label { label: "blinks" },
         Command::Led(
             LedId::Red,
             LedAction::Blink {
@@ -64,6 +71,7 @@ pub fn get_run_commands<const TIMER_HZ: u32>() -> [BotCommand<TIMER_HZ>; 9] {
                 duration: 166.millis(),
             },
         ),
+        Command::Return,
     ]
 }
 
@@ -100,3 +108,13 @@ pub fn get_stop_commands<const TIMER_HZ: u32>() -> [BotCommand<TIMER_HZ>; 1] {
         */
     ]
 }
+
+// after an error the x axis may have lost its home, so only signal it
+pub fn get_error_commands<const TIMER_HZ: u32>() -> [BotCommand<TIMER_HZ>; 1] {
+    [Command::Led(
+        LedId::Red,
+        LedAction::Blink {
+            duration: 1000.millis(),
+        },
+    )]
+}
@@ -6,7 +6,7 @@ use gridbot_tahi as _;
 use core::task::Poll;
 use cortex_m_rt::entry;
 use defmt::Debug2Format;
-use fugit::ExtU32;
+use fugit::{ExtU32, TimerDurationU64};
 use stm32f7xx_hal::{
     gpio::{self, Alternate, Floating, Input, Output, Pin, PullUp, PushPull},
     pac,
@@ -18,14 +18,15 @@ use stm32f7xx_hal::{
 };
 
 use robokit::{
-    AxisDevice, AxisLimitSide, LedDevice, RobotBuilder, Sensor, SpindleDevice,
-    SpindleDriverJmcHsv57, SubTimer, SuperTimer, SwitchActiveHigh, SwitchActiveLow, SwitchDevice,
-    SwitchStatus,
+    AxisDevice, AxisLimitSide, AxisLimits, ErrorPolicy, LedDevice, RobotBuilder, Sensor,
+    SimulatedAxis, SimulatedPin, SimulatedSpindleDriver, Simulation, SpindleDevice,
+    SpindleDriverJmcHsv57, SpindleLimits, SubTimer, SuperTimer, SwitchActiveHigh, SwitchActiveLow,
+    SwitchDevice, SwitchStatus, ValidationSettings,
 };
 
 use gridbot_tahi::{
-    actuators::{AxisSet, LedSet, SpindleSet},
-    commands::{get_run_commands, get_start_commands, get_stop_commands},
+    actuators::{AxisId, AxisSet, LedSet, SpindleId, SpindleSet},
+    commands::{get_error_commands, get_run_commands, get_start_commands, get_stop_commands},
     init_heap,
 };
 
@@ -35,6 +36,7 @@ const TICK_TIMER_MAX: u32 = u32::MAX;
 const TICK_TIMER_HZ: u32 = 1_000_000;
 type TickTimerDevice = Counter<pac::TIM5, TICK_TIMER_HZ>;
 type TickTimer = SuperTimer<TickTimerDevice, TICK_TIMER_HZ>;
+type RobotTimer = SubTimer<TICK_TIMER_HZ>;
 
 type UserButtonPin = Pin<'C', 13, Input<Floating>>;
 type UserButtonTimer = SubTimer<TICK_TIMER_HZ>;
@@ -108,6 +110,8 @@ fn main() -> ! {
     let red_led = LedDevice::new(red_led_pin, red_led_timer);
 
     let max_acceleration_in_millimeters_per_sec_per_sec = 20_f64;
+    // ramp the acceleration, so the gantry doesn't shake as it starts and stops
+    let max_jerk_in_millimeters_per_sec_per_sec_per_sec = 200_f64;
 
     // https://www.makerstore.com.au/product/gear-m1/
     let steps_per_revolution = 6400_f64;
@@ -116,6 +120,49 @@ fn main() -> ! {
 
     defmt::println!("Steps per mm: {}", steps_per_millimeter);
 
+    // the travel between the x axis limit switches
+    let x_axis_limit_min_in_millimeters = 0_f64;
+    let x_axis_limit_max_in_millimeters = 1000_f64;
+
+    // dry run the job against simulated actuators, to check it before running it on real stock
+    let simulation = Simulation::<TICK_TIMER_HZ>::new(1.millis(), TimerDurationU64::minutes(60));
+    let mut simulated_robot = RobotBuilder::new(simulation.sub())
+        .with_leds(LedSet::new(
+            LedDevice::new(SimulatedPin, simulation.sub()),
+            LedDevice::new(SimulatedPin, simulation.sub()),
+            LedDevice::new(SimulatedPin, simulation.sub()),
+        ))
+        .with_axes(AxisSet::new(
+            SimulatedAxis::new(
+                simulation.sub(),
+                max_acceleration_in_millimeters_per_sec_per_sec,
+                steps_per_millimeter,
+                x_axis_limit_min_in_millimeters,
+                x_axis_limit_max_in_millimeters,
+                AxisLimitSide::Min,
+            )
+            .with_max_jerk(max_jerk_in_millimeters_per_sec_per_sec_per_sec)
+            .expect("Failed to set the simulated x axis max jerk")
+            .with_soft_limits(
+                x_axis_limit_min_in_millimeters,
+                x_axis_limit_max_in_millimeters,
+            ),
+        ))
+        .with_spindles(SpindleSet::new(SpindleDevice::new(
+            SimulatedSpindleDriver::new(),
+        )))
+        .build()
+        .with_run_commands(&get_run_commands())
+        .with_start_commands(&get_start_commands())
+        .with_stop_commands(&get_stop_commands())
+        .build::<ACTIVE_COMMANDS_COUNT>();
+    let report = simulated_robot.simulate(&simulation);
+    defmt::println!("Dry run: {}", Debug2Format(&report));
+    let is_dry_run_valid = report.is_complete && report.limit_violations_count() == 0;
+    if !is_dry_run_valid {
+        defmt::println!("Dry run failed, check the job before running it");
+    }
+
     let x_axis_dir_pin: XAxisDirPin = gpiog.pg9.into_push_pull_output();
     let x_axis_step_pin: XAxisStepPin = gpiog.pg14.into_push_pull_output();
     let x_axis_timer: XAxisTimer = p.TIM3.counter(&clocks);
@@ -136,6 +183,13 @@ fn main() -> ! {
         x_axis_limit_min,
         x_axis_limit_max,
         AxisLimitSide::Min,
+    )
+    .expect("Failed to setup x axis")
+    .with_max_jerk(max_jerk_in_millimeters_per_sec_per_sec_per_sec)
+    .expect("Failed to set x axis max jerk")
+    .with_soft_limits(
+        x_axis_limit_min_in_millimeters,
+        x_axis_limit_max_in_millimeters,
     );
 
     let main_spindle_serial_tx = gpiod.pd5.into_alternate();
@@ -154,7 +208,8 @@ fn main() -> ! {
     let main_spindle_driver: MainSpindleDriver = SpindleDriverJmcHsv57::new(main_spindle_serial);
     let main_spindle = SpindleDevice::new(main_spindle_driver);
 
-    let mut robot = RobotBuilder::new()
+    let robot_timer: RobotTimer = super_timer.sub();
+    let mut robot = RobotBuilder::new(robot_timer)
         .with_leds(LedSet::new(green_led, blue_led, red_led))
         .with_axes(AxisSet::new(x_axis))
         .with_spindles(SpindleSet::new(main_spindle))
@@ -162,8 +217,35 @@ fn main() -> ! {
         .with_run_commands(&get_run_commands())
         .with_start_commands(&get_start_commands())
         .with_stop_commands(&get_stop_commands())
+        .with_error_commands(&get_error_commands())
+        .with_error_policy(ErrorPolicy::RunErrorCommands)
         .build::<ACTIVE_COMMANDS_COUNT>();
 
+    // check the job before it can be started
+    let validation_settings = ValidationSettings {
+        axes: &[(
+            AxisId::X,
+            AxisLimits {
+                min_position_in_millimeters: x_axis_limit_min_in_millimeters,
+                max_position_in_millimeters: x_axis_limit_max_in_millimeters,
+                max_velocity_in_millimeters_per_sec: 50_f64,
+            },
+        )],
+        spindles: &[(
+            SpindleId::Main,
+            SpindleLimits {
+                max_rpm: MainSpindleDriver::MAX_RPM,
+            },
+        )],
+    };
+    let is_valid = match robot.validate(&validation_settings) {
+        Ok(()) => is_dry_run_valid,
+        Err(err) => {
+            defmt::println!("Invalid job: {}", Debug2Format(&err));
+            false
+        }
+    };
+
     let mut iwdg = watchdog::IndependentWatchdog::new(p.IWDG);
 
     iwdg.start(2.millis());
@@ -173,20 +255,15 @@ fn main() -> ! {
         super_timer.tick().expect("Failed to tick super timer");
 
         if let Some(user_button_update) = user_button.sense().expect("Error reading user button") {
-            if let SwitchStatus::On = user_button_update.status {
+            // an invalid job can't be started
+            if let (SwitchStatus::On, true) = (user_button_update.status, is_valid) {
                 robot.toggle();
             }
         }
 
+        // the robot runs the error commands and latches until reset
         if let Poll::Ready(Err(err)) = robot.poll() {
-            defmt::println!("Unexpected error: {}", Debug2Format(&err));
-
-            robot.stop();
-            loop {
-                match robot.poll() {
-                    _ => {}
-                }
-            }
+            defmt::println!("Unexpected error: {}", Debug2Format(err));
         }
 
         iwdg.feed();
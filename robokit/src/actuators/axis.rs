@@ -8,12 +8,14 @@ use fugit_timer::Timer as FugitTimer;
 use stepper::{
     compat, drivers,
     motion_control::{self, SoftwareMotionControl},
-    ramp_maker,
     traits::{MotionControl, SetDirection, Step},
     Direction, Stepper,
 };
 
-use super::Actuator;
+use super::{
+    profile::{AxisProfile, AxisRamp},
+    Actuator, ActuatorStatus,
+};
 use crate::sensors::{
     switch::{SwitchStatus, SwitchUpdate},
     Sensor,
@@ -29,6 +31,16 @@ pub enum AxisAction {
         max_velocity_in_millimeters_per_sec: AxisVelocity,
         position_in_millimeters: f64,
     },
+    // an axis's share of a coordinated motion, see `MotionAction`
+    MoveCoordinated {
+        max_velocity_in_millimeters_per_sec: AxisVelocity,
+        max_acceleration_in_millimeters_per_sec_per_sec: f64,
+        // none keeps the axis's own max jerk
+        max_jerk_in_millimeters_per_sec_per_sec_per_sec: Option<f64>,
+        position_in_millimeters: f64,
+        // part of a relative motion, so it doesn't need the axis to be homed
+        is_relative: bool,
+    },
     Home {
         max_velocity_in_millimeters_per_sec: AxisVelocity,
         back_off_distance_in_millimeters: f64,
@@ -36,24 +48,32 @@ pub enum AxisAction {
 }
 
 type AxisVelocity = f64;
-type AxisMotionProfile = ramp_maker::Trapezoidal<AxisVelocity>;
-type AxisMotionControl<Driver, Timer, const TIMER_HZ: u32> = SoftwareMotionControl<
+type AxisMotionControl<Driver, Timer, Profile, const TIMER_HZ: u32> = SoftwareMotionControl<
     Driver,
     StepperTimer<Timer, TIMER_HZ>,
-    AxisMotionProfile,
+    Profile,
     DelayToTicks<TimerDuration<TIMER_HZ>, TIMER_HZ>,
     TIMER_HZ,
 >;
-type AxisDriverDQ542MA<PinDir, PinStep, Timer, const TIMER_HZ: u32> = AxisMotionControl<
+type AxisDriverDQ542MA<PinDir, PinStep, Timer, Profile, const TIMER_HZ: u32> = AxisMotionControl<
     drivers::dq542ma::DQ542MA<(), compat::Pin<PinStep>, compat::Pin<PinDir>>,
     Timer,
+    Profile,
     TIMER_HZ,
 >;
+type AxisErrorDQ542MA<PinDir, PinStep, Timer, Profile, const TIMER_HZ: u32, LimitMin, LimitMax> =
+    AxisError<
+        <AxisDriverDQ542MA<PinDir, PinStep, Timer, Profile, TIMER_HZ> as MotionControl>::Error,
+        <LimitMin as Sensor>::Error,
+        <LimitMax as Sensor>::Error,
+    >;
 
 // https://docs.rs/stepper/latest/src/stepper/stepper/move_to.rs.html
 #[derive(Clone, Copy, Debug, Format)]
 struct AxisMoveState {
     max_velocity_in_steps_per_sec: AxisVelocity,
+    max_acceleration_in_steps_per_sec_per_sec: f64,
+    max_jerk_in_steps_per_sec_per_sec_per_sec: Option<f64>,
     target_step: i32,
     #[defmt(Debug2Format)]
     direction: Direction,
@@ -70,9 +90,10 @@ struct AxisHomeState {
     max_velocity_in_steps_per_sec: AxisVelocity,
     #[defmt(Debug2Format)]
     towards_home_direction: Direction,
+    towards_home_target_step: i32,
     #[defmt(Debug2Format)]
     back_off_home_direction: Direction,
-    back_off_distance_in_steps: i32,
+    back_off_home_target_step: i32,
 }
 
 #[derive(Clone, Copy, Debug, Format)]
@@ -84,11 +105,43 @@ enum AxisHomeStatus {
     Done,
 }
 
+#[derive(Clone, Copy, Debug, Format)]
+enum AxisStopStatus {
+    Start,
+    Motion,
+}
+
 #[derive(Clone, Copy, Debug, Format)]
 enum AxisState {
     Idle,
     Moving(AxisMoveState, AxisMoveStatus),
     Homing(AxisHomeState, AxisHomeStatus),
+    Stopping(Option<AxisMoveState>, AxisStopStatus),
+    // a move past the soft limits, rejected on the next poll
+    SoftLimit { requested: f64, min: f64, max: f64 },
+    // an absolute move before homing, rejected on the next poll
+    NotHomed,
+}
+
+// the motion to resume (if any) is kept while paused
+#[derive(Clone, Copy, Debug, Format)]
+enum AxisPauseStatus {
+    Start,
+    Stopping(Option<AxisMoveState>),
+    Stopped(Option<AxisMoveState>),
+    Resume(Option<AxisMoveState>),
+}
+
+#[derive(Clone, Copy, Debug, Default, Format)]
+pub struct AxisStatus {
+    // where the axis was last told to go
+    pub logical_position_in_millimeters: f64,
+    // where the axis has stepped to
+    pub real_position_in_millimeters: f64,
+    pub is_homed: bool,
+    pub max_acceleration_in_millimeters_per_sec_per_sec: f64,
+    // none without a jerk limit
+    pub max_jerk_in_millimeters_per_sec_per_sec_per_sec: Option<f64>,
 }
 
 #[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
@@ -111,8 +164,15 @@ where
 {
     stepper: Stepper<Driver>,
     steps_per_millimeter: f64,
+    max_acceleration_in_steps_per_sec_per_sec: f64,
+    max_jerk_in_steps_per_sec_per_sec_per_sec: Option<f64>,
     state: AxisState,
+    pause_status: Option<AxisPauseStatus>,
     logical_position: f64,
+    is_homed: bool,
+    allow_unhomed_absolute_moves: bool,
+    // min and max, relative to home
+    soft_limits_in_millimeters: Option<(f64, f64)>,
     limit_min: LimitMin,
     limit_max: LimitMax,
     limit_min_status: Option<AxisLimitStatus>,
@@ -121,17 +181,20 @@ where
 }
 
 impl<PinDir, PinStep, Timer, const TIMER_HZ: u32, LimitMin, LimitMax>
-    AxisDevice<AxisDriverDQ542MA<PinDir, PinStep, Timer, TIMER_HZ>, LimitMin, LimitMax>
+    AxisDevice<AxisDriverDQ542MA<PinDir, PinStep, Timer, AxisRamp, TIMER_HZ>, LimitMin, LimitMax>
 where
     PinDir: OutputPin,
     <PinDir as OutputPin>::Error: Debug,
     PinStep: OutputPin,
     <PinStep as OutputPin>::Error: Debug,
     Timer: FugitTimer<TIMER_HZ>,
-    <AxisDriverDQ542MA<PinDir, PinStep, Timer, TIMER_HZ> as MotionControl>::Error: Debug,
+    <AxisDriverDQ542MA<PinDir, PinStep, Timer, AxisRamp, TIMER_HZ> as MotionControl>::Error: Debug,
     LimitMin: Sensor<Message = SwitchUpdate>,
+    LimitMin::Error: Debug,
     LimitMax: Sensor<Message = SwitchUpdate>,
+    LimitMax::Error: Debug,
 {
+    // the acceleration jumps like a trapezoid until the axis has a max jerk, see `with_max_jerk`
     pub fn new_dq542ma(
         dir: PinDir,
         step: PinStep,
@@ -141,10 +204,13 @@ where
         limit_min: LimitMin,
         limit_max: LimitMax,
         home_side: AxisLimitSide,
-    ) -> Self {
+    ) -> Result<
+        Self,
+        AxisErrorDQ542MA<PinDir, PinStep, Timer, AxisRamp, TIMER_HZ, LimitMin, LimitMax>,
+    > {
         let max_acceleration_in_steps_per_sec_per_sec =
             max_acceleration_in_millimeters_per_sec_per_sec * steps_per_millimeter;
-        let profile = ramp_maker::Trapezoidal::new(max_acceleration_in_steps_per_sec_per_sec);
+        let profile = AxisRamp::from_limits(max_acceleration_in_steps_per_sec_per_sec, None);
 
         let compat_dir = compat::Pin(dir);
         let compat_step = compat::Pin(step);
@@ -152,32 +218,69 @@ where
 
         let stepper = Stepper::from_driver(drivers::dq542ma::DQ542MA::new())
             .enable_direction_control(compat_dir, Direction::Forward, &mut stepper_timer)
-            .unwrap()
+            .map_err(|err| AxisError::DriverSetup(motion_control::Error::SetDirection(err)))?
             .enable_step_control(compat_step)
             .enable_motion_control((stepper_timer, profile, DelayToTicks::new()));
 
-        Self {
+        Ok(Self::from_stepper(
             stepper,
             steps_per_millimeter,
-            state: AxisState::Idle,
-            logical_position: 0_f64,
+            max_acceleration_in_steps_per_sec_per_sec,
             limit_min,
-            limit_min_status: None,
             limit_max,
-            limit_max_status: None,
             home_side,
-        }
+        ))
     }
 }
 
-impl<Driver, Timer, const TIMER_HZ: u32, LimitMin, LimitMax>
-    AxisDevice<AxisMotionControl<Driver, Timer, TIMER_HZ>, LimitMin, LimitMax>
+impl<Driver, Timer, Profile, const TIMER_HZ: u32, LimitMin, LimitMax>
+    AxisDevice<AxisMotionControl<Driver, Timer, Profile, TIMER_HZ>, LimitMin, LimitMax>
 where
     Driver: SetDirection + Step,
     Timer: FugitTimer<TIMER_HZ>,
+    Profile: AxisProfile,
     LimitMin: Sensor<Message = SwitchUpdate>,
     LimitMax: Sensor<Message = SwitchUpdate>,
 {
+    fn from_stepper(
+        stepper: Stepper<AxisMotionControl<Driver, Timer, Profile, TIMER_HZ>>,
+        steps_per_millimeter: f64,
+        max_acceleration_in_steps_per_sec_per_sec: f64,
+        limit_min: LimitMin,
+        limit_max: LimitMax,
+        home_side: AxisLimitSide,
+    ) -> Self {
+        Self {
+            stepper,
+            steps_per_millimeter,
+            max_acceleration_in_steps_per_sec_per_sec,
+            max_jerk_in_steps_per_sec_per_sec_per_sec: None,
+            state: AxisState::Idle,
+            pause_status: None,
+            logical_position: 0_f64,
+            is_homed: false,
+            allow_unhomed_absolute_moves: false,
+            soft_limits_in_millimeters: None,
+            limit_min,
+            limit_min_status: None,
+            limit_max,
+            limit_max_status: None,
+            home_side,
+        }
+    }
+
+    // moves past the soft limits are rejected before they start, once the axis is homed
+    pub fn with_soft_limits(mut self, min_in_millimeters: f64, max_in_millimeters: f64) -> Self {
+        self.soft_limits_in_millimeters = Some((min_in_millimeters, max_in_millimeters));
+        self
+    }
+
+    // absolute moves before homing are relative to wherever the axis was at power on
+    pub fn with_unhomed_absolute_moves(mut self) -> Self {
+        self.allow_unhomed_absolute_moves = true;
+        self
+    }
+
     pub fn get_current_step(&mut self) -> i32 {
         self.stepper.driver_mut().current_step()
     }
@@ -185,6 +288,121 @@ where
     pub fn get_real_position(&mut self) -> f64 {
         (self.get_current_step() as f64) / self.steps_per_millimeter
     }
+
+    // the motion the driver is currently stepping through, if any
+    fn motion(&self) -> Option<AxisMoveState> {
+        match self.state {
+            AxisState::Moving(move_state, AxisMoveStatus::Motion) => Some(move_state),
+            AxisState::Homing(home_state, AxisHomeStatus::MotionTowardsHome) => {
+                Some(AxisMoveState {
+                    max_velocity_in_steps_per_sec: home_state.max_velocity_in_steps_per_sec,
+                    max_acceleration_in_steps_per_sec_per_sec: self
+                        .max_acceleration_in_steps_per_sec_per_sec,
+                    max_jerk_in_steps_per_sec_per_sec_per_sec: self
+                        .max_jerk_in_steps_per_sec_per_sec_per_sec,
+                    target_step: home_state.towards_home_target_step,
+                    direction: home_state.towards_home_direction,
+                })
+            }
+            AxisState::Homing(home_state, AxisHomeStatus::MotionBackOffHome) => {
+                Some(AxisMoveState {
+                    max_velocity_in_steps_per_sec: home_state.max_velocity_in_steps_per_sec,
+                    max_acceleration_in_steps_per_sec_per_sec: self
+                        .max_acceleration_in_steps_per_sec_per_sec,
+                    max_jerk_in_steps_per_sec_per_sec_per_sec: self
+                        .max_jerk_in_steps_per_sec_per_sec_per_sec,
+                    target_step: home_state.back_off_home_target_step,
+                    direction: home_state.back_off_home_direction,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn limit_hit(&self, direction: Direction) -> Option<AxisLimitSide> {
+        match direction {
+            // limit: max
+            Direction::Forward => match self.limit_max_status {
+                Some(AxisLimitStatus::Over) => Some(AxisLimitSide::Max),
+                _ => None,
+            },
+            // limit: min
+            Direction::Backward => match self.limit_min_status {
+                Some(AxisLimitStatus::Over) => Some(AxisLimitSide::Min),
+                _ => None,
+            },
+        }
+    }
+
+    // stop stepping now, with a fresh motion profile so the next motion starts from rest
+    fn halt(&mut self) {
+        *self.stepper.driver_mut().profile_mut() = Profile::from_limits(
+            self.max_acceleration_in_steps_per_sec_per_sec,
+            self.max_jerk_in_steps_per_sec_per_sec_per_sec,
+        );
+
+        self.state = AxisState::Idle;
+        self.pause_status = None;
+        self.logical_position = self.get_real_position();
+    }
+
+    fn move_to(
+        &mut self,
+        max_velocity_in_millimeters_per_sec: f64,
+        max_acceleration_in_millimeters_per_sec_per_sec: f64,
+        max_jerk_in_millimeters_per_sec_per_sec_per_sec: Option<f64>,
+        position_in_millimeters: f64,
+    ) {
+        let max_velocity_in_steps_per_sec =
+            max_velocity_in_millimeters_per_sec * self.steps_per_millimeter;
+        let max_acceleration_in_steps_per_sec_per_sec =
+            max_acceleration_in_millimeters_per_sec_per_sec * self.steps_per_millimeter;
+        let max_jerk_in_steps_per_sec_per_sec_per_sec =
+            match max_jerk_in_millimeters_per_sec_per_sec_per_sec {
+                Some(max_jerk) => Some(max_jerk * self.steps_per_millimeter),
+                None => self.max_jerk_in_steps_per_sec_per_sec_per_sec,
+            };
+
+        // the limits are relative to home, so they mean nothing until then
+        if let (true, Some((min, max))) = (self.is_homed, self.soft_limits_in_millimeters) {
+            let requested = position_in_millimeters;
+            if requested < min || requested > max {
+                self.state = AxisState::SoftLimit {
+                    requested,
+                    min,
+                    max,
+                };
+                return;
+            }
+        }
+
+        let next_logical_position = position_in_millimeters;
+        let real_position_difference = next_logical_position - self.get_real_position();
+        let step_difference: i32 = (real_position_difference * self.steps_per_millimeter) as i32;
+        let target_step = self.get_current_step() + step_difference;
+
+        // NOTE(mw) hmm... is this the best way to do this?
+        self.logical_position = next_logical_position;
+
+        // NOTE(mw): We do this because stepper doesn't immediately set direction after
+        //   .move_to_position(), we need the direction right away.
+        let direction = if step_difference < 0 {
+            Direction::Backward
+        } else {
+            Direction::Forward
+        };
+
+        self.state = AxisState::Moving(
+            AxisMoveState {
+                max_velocity_in_steps_per_sec,
+                max_acceleration_in_steps_per_sec_per_sec,
+                max_jerk_in_steps_per_sec_per_sec_per_sec,
+                target_step,
+                direction,
+            },
+            AxisMoveStatus::Start,
+        );
+    }
 }
 
 pub struct DelayToTicks<Time, const TIMER_HZ: u32>(PhantomData<Time>);
@@ -209,20 +427,60 @@ impl<Time, const TIMER_HZ: u32> motion_control::DelayToTicks<f64, TIMER_HZ>
 
 #[derive(Clone, Copy, Debug)]
 pub enum AxisError<DriverError: Debug, LimitMinSenseError: Debug, LimitMaxSenseError: Debug> {
+    DriverSetup(DriverError),
     DriverUpdate(DriverError),
     DriverResetPosition(DriverError),
     DriverMoveToPosition(DriverError),
     Limit(AxisLimitSide),
+    SoftLimit { requested: f64, min: f64, max: f64 },
+    NotHomed,
     LimitSensor(LimitSensorError<LimitMinSenseError, LimitMaxSenseError>),
     Unexpected,
+    MaxJerkOutOfRange(f64),
+}
+
+impl<Driver, Timer, Profile, const TIMER_HZ: u32, LimitMin, LimitMax>
+    AxisDevice<AxisMotionControl<Driver, Timer, Profile, TIMER_HZ>, LimitMin, LimitMax>
+where
+    Driver: SetDirection + Step,
+    Timer: FugitTimer<TIMER_HZ>,
+    Profile: AxisProfile,
+    <AxisMotionControl<Driver, Timer, Profile, TIMER_HZ> as MotionControl>::Error: Debug,
+    LimitMin: Sensor<Message = SwitchUpdate>,
+    LimitMin::Error: Debug,
+    LimitMax: Sensor<Message = SwitchUpdate>,
+    LimitMax::Error: Debug,
+{
+    // the acceleration ramps up and down within the max jerk, for a smoother start and stop
+    // (a profile without a jerk limit, like `Trapezoidal`, ignores it)
+    pub fn with_max_jerk(
+        mut self,
+        max_jerk_in_millimeters_per_sec_per_sec_per_sec: f64,
+    ) -> Result<Self, <Self as Actuator>::Error> {
+        if !max_jerk_in_millimeters_per_sec_per_sec_per_sec.is_finite()
+            || max_jerk_in_millimeters_per_sec_per_sec_per_sec <= 0_f64
+        {
+            return Err(AxisError::MaxJerkOutOfRange(
+                max_jerk_in_millimeters_per_sec_per_sec_per_sec,
+            ));
+        }
+        self.max_jerk_in_steps_per_sec_per_sec_per_sec =
+            Some(max_jerk_in_millimeters_per_sec_per_sec_per_sec * self.steps_per_millimeter);
+        *self.stepper.driver_mut().profile_mut() = Profile::from_limits(
+            self.max_acceleration_in_steps_per_sec_per_sec,
+            self.max_jerk_in_steps_per_sec_per_sec_per_sec,
+        );
+        Ok(self)
+    }
 }
 
-impl<Driver, Timer, const TIMER_HZ: u32, LimitMin, LimitMax> Actuator
-    for AxisDevice<AxisMotionControl<Driver, Timer, TIMER_HZ>, LimitMin, LimitMax>
+impl<Driver, Timer, Profile, const TIMER_HZ: u32, LimitMin, LimitMax> Actuator
+    for AxisDevice<AxisMotionControl<Driver, Timer, Profile, TIMER_HZ>, LimitMin, LimitMax>
 where
     Driver: SetDirection + Step,
     Timer: FugitTimer<TIMER_HZ>,
-    <AxisMotionControl<Driver, Timer, TIMER_HZ> as MotionControl>::Error: Debug,
+    Profile: AxisProfile,
+    <AxisMotionControl<Driver, Timer, Profile, TIMER_HZ> as MotionControl>::Error: Debug,
     LimitMin: Sensor<Message = SwitchUpdate>,
     LimitMin::Error: Debug,
     LimitMax: Sensor<Message = SwitchUpdate>,
@@ -230,56 +488,62 @@ where
 {
     type Action = AxisAction;
     type Error = AxisError<
-        <AxisMotionControl<Driver, Timer, TIMER_HZ> as MotionControl>::Error,
+        <AxisMotionControl<Driver, Timer, Profile, TIMER_HZ> as MotionControl>::Error,
         <LimitMin as Sensor>::Error,
         <LimitMax as Sensor>::Error,
     >;
 
     fn run(&mut self, action: &Self::Action) {
+        self.pause_status = None;
+
         match action {
             AxisAction::MoveRelative {
                 max_velocity_in_millimeters_per_sec,
                 distance_in_millimeters,
             } => {
-                let position_in_millimeters = self.logical_position + distance_in_millimeters;
+                let max_acceleration_in_millimeters_per_sec_per_sec =
+                    self.max_acceleration_in_steps_per_sec_per_sec / self.steps_per_millimeter;
+
+                self.move_to(
+                    *max_velocity_in_millimeters_per_sec,
+                    max_acceleration_in_millimeters_per_sec_per_sec,
+                    None,
+                    self.logical_position + distance_in_millimeters,
+                )
+            }
+            AxisAction::MoveAbsolute {
+                max_velocity_in_millimeters_per_sec,
+                position_in_millimeters,
+            } => {
+                let max_acceleration_in_millimeters_per_sec_per_sec =
+                    self.max_acceleration_in_steps_per_sec_per_sec / self.steps_per_millimeter;
 
-                self.run(&AxisAction::MoveAbsolute {
+                self.run(&AxisAction::MoveCoordinated {
                     max_velocity_in_millimeters_per_sec: *max_velocity_in_millimeters_per_sec,
-                    position_in_millimeters,
+                    max_acceleration_in_millimeters_per_sec_per_sec,
+                    max_jerk_in_millimeters_per_sec_per_sec_per_sec: None,
+                    position_in_millimeters: *position_in_millimeters,
+                    is_relative: false,
                 })
             }
-            AxisAction::MoveAbsolute {
+            AxisAction::MoveCoordinated {
                 max_velocity_in_millimeters_per_sec,
+                max_acceleration_in_millimeters_per_sec_per_sec,
+                max_jerk_in_millimeters_per_sec_per_sec_per_sec,
                 position_in_millimeters,
+                is_relative,
             } => {
-                let max_velocity_in_steps_per_sec =
-                    max_velocity_in_millimeters_per_sec * self.steps_per_millimeter;
-
-                let next_logical_position = position_in_millimeters;
-                let real_position_difference = next_logical_position - self.get_real_position();
-                let step_difference: i32 =
-                    (real_position_difference * self.steps_per_millimeter) as i32;
-                let target_step = self.get_current_step() + step_difference;
-
-                // NOTE(mw) hmm... is this the best way to do this?
-                self.logical_position = *next_logical_position;
-
-                // NOTE(mw): We do this because stepper doesn't immediately set direction after
-                //   .move_to_position(), we need the direction right away.
-                let direction = if step_difference < 0 {
-                    Direction::Backward
-                } else {
-                    Direction::Forward
-                };
+                if !is_relative && !self.is_homed && !self.allow_unhomed_absolute_moves {
+                    self.state = AxisState::NotHomed;
+                    return;
+                }
 
-                self.state = AxisState::Moving(
-                    AxisMoveState {
-                        max_velocity_in_steps_per_sec,
-                        target_step,
-                        direction,
-                    },
-                    AxisMoveStatus::Start,
-                );
+                self.move_to(
+                    *max_velocity_in_millimeters_per_sec,
+                    *max_acceleration_in_millimeters_per_sec_per_sec,
+                    *max_jerk_in_millimeters_per_sec_per_sec_per_sec,
+                    *position_in_millimeters,
+                )
             }
             AxisAction::Home {
                 max_velocity_in_millimeters_per_sec,
@@ -290,17 +554,25 @@ where
                 let back_off_distance_in_steps =
                     (back_off_distance_in_millimeters * self.steps_per_millimeter) as i32;
 
+                // homing resets the position, so until it's done the axis isn't homed
+                self.is_homed = false;
+
                 let (towards_home_direction, back_off_home_direction) = match self.home_side {
                     AxisLimitSide::Min => (Direction::Backward, Direction::Forward),
                     AxisLimitSide::Max => (Direction::Forward, Direction::Backward),
                 };
+                let (towards_home_target_step, back_off_home_target_step) = match self.home_side {
+                    AxisLimitSide::Min => (i32::MIN + 1, back_off_distance_in_steps),
+                    AxisLimitSide::Max => (i32::MAX - 1, -back_off_distance_in_steps),
+                };
 
                 self.state = AxisState::Homing(
                     AxisHomeState {
                         max_velocity_in_steps_per_sec,
                         towards_home_direction,
+                        towards_home_target_step,
                         back_off_home_direction,
-                        back_off_distance_in_steps,
+                        back_off_home_target_step,
                     },
                     AxisHomeStatus::Start,
                 );
@@ -308,8 +580,87 @@ where
         }
     }
 
-    // https://docs.rs/stepper/latest/src/stepper/stepper/move_to.rs.html#
     fn poll(&mut self) -> Poll<Result<(), Self::Error>> {
+        let poll = self.poll_state();
+
+        // steps may have been lost, so where the axis is can't be trusted until homed again
+        if let Poll::Ready(Err(
+            AxisError::Limit(_)
+            | AxisError::DriverUpdate(_)
+            | AxisError::DriverResetPosition(_)
+            | AxisError::DriverMoveToPosition(_),
+        )) = poll
+        {
+            self.is_homed = false;
+        }
+
+        poll
+    }
+
+    fn pause(&mut self) {
+        if self.pause_status.is_none() {
+            self.pause_status = Some(AxisPauseStatus::Start);
+        }
+    }
+
+    fn resume(&mut self) {
+        self.pause_status = match self.pause_status {
+            Some(AxisPauseStatus::Stopping(motion)) | Some(AxisPauseStatus::Stopped(motion)) => {
+                Some(AxisPauseStatus::Resume(motion))
+            }
+            Some(AxisPauseStatus::Start) => None,
+            pause_status => pause_status,
+        };
+    }
+
+    fn cancel(&mut self) {
+        if let AxisState::Stopping(..) = self.state {
+            return;
+        }
+
+        // if paused, we've already stopped (or are stopping)
+        let motion = match self.pause_status {
+            None | Some(AxisPauseStatus::Start) => self.motion(),
+            Some(AxisPauseStatus::Stopping(motion)) => motion,
+            Some(AxisPauseStatus::Stopped(_)) | Some(AxisPauseStatus::Resume(_)) => None,
+        };
+
+        // already at a limit switch, so there's no room to slow down
+        if let Some(AxisMoveState { direction, .. }) = motion {
+            if self.limit_hit(direction).is_some() {
+                self.halt();
+                self.is_homed = false;
+                return;
+            }
+        }
+
+        // slowing down doesn't lose steps, so the axis stays homed (unless it was homing)
+        self.pause_status = None;
+        self.state = AxisState::Stopping(motion, AxisStopStatus::Start);
+    }
+
+    fn emergency_stop(&mut self) {
+        self.halt();
+
+        // stopping from full speed can lose steps
+        self.is_homed = false;
+    }
+}
+
+impl<Driver, Timer, Profile, const TIMER_HZ: u32, LimitMin, LimitMax>
+    AxisDevice<AxisMotionControl<Driver, Timer, Profile, TIMER_HZ>, LimitMin, LimitMax>
+where
+    Driver: SetDirection + Step,
+    Timer: FugitTimer<TIMER_HZ>,
+    Profile: AxisProfile,
+    <AxisMotionControl<Driver, Timer, Profile, TIMER_HZ> as MotionControl>::Error: Debug,
+    LimitMin: Sensor<Message = SwitchUpdate>,
+    LimitMin::Error: Debug,
+    LimitMax: Sensor<Message = SwitchUpdate>,
+    LimitMax::Error: Debug,
+{
+    // https://docs.rs/stepper/latest/src/stepper/stepper/move_to.rs.html#
+    fn poll_state(&mut self) -> Poll<Result<(), <Self as Actuator>::Error>> {
         self.update_limit_switches()
             .map_err(AxisError::LimitSensor)?;
 
@@ -321,11 +672,94 @@ where
             return Poll::Ready(Err(AxisError::Unexpected));
         }
 
+        if let Some(pause_status) = self.pause_status {
+            match pause_status {
+                AxisPauseStatus::Start => {
+                    let motion = self.motion();
+
+                    if let Some(AxisMoveState {
+                        max_velocity_in_steps_per_sec,
+                        direction,
+                        ..
+                    }) = motion
+                    {
+                        // target the next step, so the motion profile ramps down to a stop
+                        let stop_step = self.get_current_step() + direction as i32;
+
+                        self.stepper
+                            .driver_mut()
+                            .move_to_position(max_velocity_in_steps_per_sec, stop_step)
+                            .map_err(AxisError::DriverMoveToPosition)?;
+                    }
+
+                    self.pause_status = Some(AxisPauseStatus::Stopping(motion));
+                }
+                AxisPauseStatus::Stopping(motion) => {
+                    if let Some(AxisMoveState { direction, .. }) = motion {
+                        if let Some(limit_side) = self.limit_hit(direction) {
+                            if let AxisState::Homing(_, AxisHomeStatus::MotionTowardsHome) =
+                                self.state
+                            {
+                                // found home while stopping, homing carries on after resume
+                                self.pause_status = Some(AxisPauseStatus::Stopped(motion));
+                                return Poll::Pending;
+                            }
+
+                            self.halt();
+                            return Poll::Ready(Err(AxisError::Limit(limit_side)));
+                        }
+                    }
+
+                    let driver = self.stepper.driver_mut();
+                    let still_moving = driver.update().map_err(AxisError::DriverUpdate)?;
+                    if !still_moving {
+                        self.pause_status = Some(AxisPauseStatus::Stopped(motion));
+                    }
+                }
+                AxisPauseStatus::Stopped(_) => {}
+                AxisPauseStatus::Resume(motion) => {
+                    if let Some(AxisMoveState {
+                        max_velocity_in_steps_per_sec,
+                        target_step,
+                        ..
+                    }) = motion
+                    {
+                        self.stepper
+                            .driver_mut()
+                            .move_to_position(max_velocity_in_steps_per_sec, target_step)
+                            .map_err(AxisError::DriverMoveToPosition)?;
+                    }
+
+                    self.pause_status = None;
+                }
+            }
+
+            return Poll::Pending;
+        }
+
         match self.state {
             AxisState::Idle => Poll::Ready(Ok(())),
+            AxisState::SoftLimit {
+                requested,
+                min,
+                max,
+            } => {
+                self.state = AxisState::Idle;
+                Poll::Ready(Err(AxisError::SoftLimit {
+                    requested,
+                    min,
+                    max,
+                }))
+            }
+            AxisState::NotHomed => {
+                self.state = AxisState::Idle;
+                Poll::Ready(Err(AxisError::NotHomed))
+            }
             AxisState::Moving(move_state, move_status) => {
                 let AxisMoveState {
                     max_velocity_in_steps_per_sec,
+                    max_acceleration_in_steps_per_sec_per_sec,
+                    max_jerk_in_steps_per_sec_per_sec_per_sec,
                     target_step,
                     direction,
                 } = move_state;
@@ -334,6 +768,11 @@ where
 
                 match move_status {
                     AxisMoveStatus::Start => {
+                        // starting from rest, so the profile is free to change
+                        *driver.profile_mut() = Profile::from_limits(
+                            max_acceleration_in_steps_per_sec_per_sec,
+                            max_jerk_in_steps_per_sec_per_sec_per_sec,
+                        );
                         driver
                             .move_to_position(max_velocity_in_steps_per_sec, target_step)
                             .map_err(AxisError::DriverMoveToPosition)?;
@@ -346,12 +785,14 @@ where
                             // limit: max
                             Direction::Forward => {
                                 if let Some(AxisLimitStatus::Over) = self.limit_max_status {
+                                    self.halt();
                                     return Poll::Ready(Err(AxisError::Limit(AxisLimitSide::Max)));
                                 }
                             }
                             // limit: min
                             Direction::Backward => {
                                 if let Some(AxisLimitStatus::Over) = self.limit_min_status {
+                                    self.halt();
                                     return Poll::Ready(Err(AxisError::Limit(AxisLimitSide::Min)));
                                 }
                             }
@@ -371,24 +812,27 @@ where
                 let AxisHomeState {
                     max_velocity_in_steps_per_sec,
                     towards_home_direction,
+                    towards_home_target_step,
                     back_off_home_direction,
-                    back_off_distance_in_steps,
+                    back_off_home_target_step,
                 } = home_state;
 
                 let driver = self.stepper.driver_mut();
 
                 match home_status {
                     AxisHomeStatus::Start => {
-                        let target_step = match self.home_side {
-                            AxisLimitSide::Min => i32::MIN + 1,
-                            AxisLimitSide::Max => i32::MAX - 1,
-                        };
-
+                        *driver.profile_mut() = Profile::from_limits(
+                            self.max_acceleration_in_steps_per_sec_per_sec,
+                            self.max_jerk_in_steps_per_sec_per_sec_per_sec,
+                        );
                         driver
                             .reset_position(0)
                             .map_err(AxisError::DriverResetPosition)?;
                         driver
-                            .move_to_position(max_velocity_in_steps_per_sec, target_step)
+                            .move_to_position(
+                                max_velocity_in_steps_per_sec,
+                                towards_home_target_step,
+                            )
                             .map_err(AxisError::DriverMoveToPosition)?;
 
                         self.state =
@@ -420,16 +864,14 @@ where
                         }
                     }
                     AxisHomeStatus::Interlude => {
-                        let target_step = match self.home_side {
-                            AxisLimitSide::Min => back_off_distance_in_steps,
-                            AxisLimitSide::Max => -back_off_distance_in_steps,
-                        };
-
                         driver
                             .reset_position(0)
                             .map_err(AxisError::DriverResetPosition)?;
                         driver
-                            .move_to_position(max_velocity_in_steps_per_sec, target_step)
+                            .move_to_position(
+                                max_velocity_in_steps_per_sec,
+                                back_off_home_target_step,
+                            )
                             .map_err(AxisError::DriverMoveToPosition)?;
 
                         self.state =
@@ -442,12 +884,14 @@ where
                             // limit: max
                             Direction::Forward => {
                                 if let Some(AxisLimitStatus::Over) = self.limit_max_status {
+                                    self.halt();
                                     return Poll::Ready(Err(AxisError::Limit(AxisLimitSide::Max)));
                                 }
                             }
                             // limit: min
                             Direction::Backward => {
                                 if let Some(AxisLimitStatus::Over) = self.limit_min_status {
+                                    self.halt();
                                     return Poll::Ready(Err(AxisError::Limit(AxisLimitSide::Min)));
                                 }
                             }
@@ -468,11 +912,79 @@ where
                         self.state = AxisState::Idle;
 
                         self.logical_position = 0_f64;
+                        self.is_homed = true;
 
                         Poll::Ready(Ok(()))
                     }
                 }
             }
+            AxisState::Stopping(motion, stop_status) => match stop_status {
+                AxisStopStatus::Start => {
+                    if let Some(AxisMoveState {
+                        max_velocity_in_steps_per_sec,
+                        direction,
+                        ..
+                    }) = motion
+                    {
+                        // target the next step, so the motion profile ramps down to a stop
+                        let stop_step = self.get_current_step() + direction as i32;
+
+                        self.stepper
+                            .driver_mut()
+                            .move_to_position(max_velocity_in_steps_per_sec, stop_step)
+                            .map_err(AxisError::DriverMoveToPosition)?;
+                    }
+
+                    self.state = AxisState::Stopping(motion, AxisStopStatus::Motion);
+                    Poll::Pending
+                }
+                AxisStopStatus::Motion => {
+                    if let Some(AxisMoveState { direction, .. }) = motion {
+                        if let Some(limit_side) = self.limit_hit(direction) {
+                            self.halt();
+                            return Poll::Ready(Err(AxisError::Limit(limit_side)));
+                        }
+                    }
+
+                    let driver = self.stepper.driver_mut();
+                    let still_moving = driver.update().map_err(AxisError::DriverUpdate)?;
+                    if still_moving {
+                        Poll::Pending
+                    } else {
+                        self.state = AxisState::Idle;
+                        self.logical_position = self.get_real_position();
+                        Poll::Ready(Ok(()))
+                    }
+                }
+            },
+        }
+    }
+}
+
+impl<Driver, Timer, Profile, const TIMER_HZ: u32, LimitMin, LimitMax> ActuatorStatus
+    for AxisDevice<AxisMotionControl<Driver, Timer, Profile, TIMER_HZ>, LimitMin, LimitMax>
+where
+    Driver: SetDirection + Step,
+    Timer: FugitTimer<TIMER_HZ>,
+    Profile: AxisProfile,
+    LimitMin: Sensor<Message = SwitchUpdate>,
+    LimitMax: Sensor<Message = SwitchUpdate>,
+{
+    type Status = AxisStatus;
+
+    fn status(&self) -> Self::Status {
+        let current_step = self.stepper.driver().current_step();
+
+        AxisStatus {
+            logical_position_in_millimeters: self.logical_position,
+            real_position_in_millimeters: (current_step as f64) / self.steps_per_millimeter,
+            is_homed: self.is_homed,
+            max_acceleration_in_millimeters_per_sec_per_sec: self
+                .max_acceleration_in_steps_per_sec_per_sec
+                / self.steps_per_millimeter,
+            max_jerk_in_millimeters_per_sec_per_sec_per_sec: self
+                .max_jerk_in_steps_per_sec_per_sec_per_sec
+                .map(|max_jerk| max_jerk / self.steps_per_millimeter),
         }
     }
 }
@@ -483,11 +995,12 @@ pub enum LimitSensorError<LimitMinSenseError: Debug, LimitMaxSenseError: Debug>
     Max(LimitMaxSenseError),
 }
 
-impl<Driver, Timer, const TIMER_HZ: u32, LimitMin, LimitMax>
-    AxisDevice<AxisMotionControl<Driver, Timer, TIMER_HZ>, LimitMin, LimitMax>
+impl<Driver, Timer, Profile, const TIMER_HZ: u32, LimitMin, LimitMax>
+    AxisDevice<AxisMotionControl<Driver, Timer, Profile, TIMER_HZ>, LimitMin, LimitMax>
 where
     Driver: SetDirection + Step,
     Timer: FugitTimer<TIMER_HZ>,
+    Profile: AxisProfile,
     LimitMin: Sensor<Message = SwitchUpdate>,
     LimitMin::Error: Debug,
     LimitMax: Sensor<Message = SwitchUpdate>,
@@ -141,4 +141,12 @@ where
             None => Poll::Ready(Ok(())),
         }
     }
+
+    fn cancel(&mut self) {
+        self.state = Some(LedState::Set { is_on: false });
+    }
+
+    fn emergency_stop(&mut self) {
+        self.state = Some(LedState::Set { is_on: false });
+    }
 }
@@ -1,5 +1,6 @@
 pub mod axis;
 pub mod led;
+pub mod profile;
 pub mod spindle;
 
 use core::fmt::Debug;
@@ -17,15 +18,43 @@ pub trait Actuator {
 
     fn run(&mut self, action: &Self::Action);
     fn poll(&mut self) -> Poll<Result<(), Self::Error>>;
+
+    // hold the current action, by default actuators finish their action
+    fn pause(&mut self) {}
+    fn resume(&mut self) {}
+
+    // give up the current action in a controlled way, polling finishes the cancel
+    fn cancel(&mut self);
+
+    // abort the current action and halt immediately, polling finishes the halt
+    fn emergency_stop(&mut self);
 }
 
 pub trait ActuatorSet {
     type Action: Debug + Format;
-    type Id: Copy + Debug + Format;
+    type Id: Copy + Debug + Format + 'static;
     type Error: Error;
 
+    const IDS: &'static [Self::Id];
+
     fn run(&mut self, id: &Self::Id, action: &Self::Action);
     fn poll(&mut self, id: &Self::Id) -> Poll<Result<(), Self::Error>>;
+    fn pause(&mut self, id: &Self::Id);
+    fn resume(&mut self, id: &Self::Id);
+    fn cancel(&mut self, id: &Self::Id);
+    fn emergency_stop(&mut self, id: &Self::Id);
+}
+
+// report what an actuator is up to, e.g. the position of an axis
+pub trait ActuatorStatus {
+    type Status: Copy + Debug + Format;
+
+    fn status(&self) -> Self::Status;
+}
+
+// any status the actuators' statuses convert into, e.g. a simulated axis status into an axis status
+pub trait ActuatorSetStatus<Status>: ActuatorSet {
+    fn status(&self, id: &Self::Id) -> Status;
 }
 
 pub struct EmptyActuatorSet<Action> {
@@ -51,10 +80,256 @@ where
     type Id = ();
     type Error = ();
 
+    const IDS: &'static [Self::Id] = &[];
+
     fn run(&mut self, _id: &Self::Id, _action: &Self::Action) {}
     fn poll(&mut self, _id: &Self::Id) -> Poll<Result<(), Self::Error>> {
-        unreachable!("EmptyActuatorSet::poll is unreachable.")
+        // there's no such actuator
+        Poll::Ready(Err(()))
+    }
+    fn pause(&mut self, _id: &Self::Id) {}
+    fn resume(&mut self, _id: &Self::Id) {}
+    fn cancel(&mut self, _id: &Self::Id) {}
+    fn emergency_stop(&mut self, _id: &Self::Id) {}
+}
+
+impl<Action, Status> ActuatorSetStatus<Status> for EmptyActuatorSet<Action>
+where
+    Action: Debug + Format,
+    Status: Default,
+{
+    // there's no such actuator
+    fn status(&self, _id: &Self::Id) -> Status {
+        Status::default()
+    }
+}
+
+// actuator sets of kinds beyond leds, axes and spindles, e.g. relays, see `actuator_set_list!`
+pub trait ActuatorSetList {
+    // which actuator of which set, and what to do
+    type Command: Copy + Debug + Format;
+    type Error: Error;
+
+    fn run(&mut self, command: &Self::Command);
+    fn poll(&mut self, command: &Self::Command) -> Poll<Result<(), Self::Error>>;
+    fn pause(&mut self, command: &Self::Command);
+    fn resume(&mut self, command: &Self::Command);
+    fn cancel(&mut self, command: &Self::Command);
+    // every actuator of every set
+    fn poll_all(&mut self) -> Poll<Result<(), Self::Error>>;
+    fn emergency_stop_all(&mut self);
+}
+
+pub struct EmptyActuatorSetList;
+
+impl EmptyActuatorSetList {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for EmptyActuatorSetList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ActuatorSetList for EmptyActuatorSetList {
+    type Command = ();
+    type Error = ();
+
+    fn run(&mut self, _command: &Self::Command) {}
+    fn poll(&mut self, _command: &Self::Command) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+    fn pause(&mut self, _command: &Self::Command) {}
+    fn resume(&mut self, _command: &Self::Command) {}
+    fn cancel(&mut self, _command: &Self::Command) {}
+    fn poll_all(&mut self) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
     }
+    fn emergency_stop_all(&mut self) {}
+}
+
+#[macro_export]
+macro_rules! actuator_set_list {
+    (
+        { $($kind:ident: $action:ty),* },
+        $command:ident,
+        $list:ident,
+        $error:ident
+    ) => {
+        $crate::paste! {
+            #[derive(Copy, Clone, Debug, defmt::Format)]
+            pub enum $command<
+                $(
+                    [<$kind:camel Id>],
+                )*
+            > {
+                $(
+                    [<$kind:camel>]([<$kind:camel Id>], $action),
+                )*
+            }
+
+            #[derive(Copy, Clone, Debug)]
+            pub enum $error<
+                $(
+                    [<$kind:camel Id>]: core::fmt::Debug,
+                    [<$kind:camel Error>]: core::fmt::Debug,
+                )*
+            > {
+                $(
+                    [<$kind:camel>]([<$kind:camel Id>], [<$kind:camel Error>]),
+                )*
+            }
+
+            pub struct $list<
+                $(
+                    [<$kind:camel Set>],
+                )*
+            >
+            where
+                $(
+                    [<$kind:camel Set>]: $crate::actuators::ActuatorSet<Action = $action>,
+                )*
+            {
+                $(
+                    [<$kind:snake _set>]: [<$kind:camel Set>],
+                )*
+            }
+
+            impl<
+                $(
+                    [<$kind:camel Set>],
+                )*
+            > $list<
+                $(
+                    [<$kind:camel Set>],
+                )*
+            >
+            where
+                $(
+                    [<$kind:camel Set>]: $crate::actuators::ActuatorSet<Action = $action>,
+                )*
+            {
+                pub fn new(
+                    $(
+                        [<$kind:snake _set>]: [<$kind:camel Set>],
+                    )*
+                ) -> Self {
+                    Self {
+                        $(
+                            [<$kind:snake _set>],
+                        )*
+                    }
+                }
+            }
+
+            impl<
+                $(
+                    [<$kind:camel Set>],
+                )*
+            > $crate::actuators::ActuatorSetList for $list<
+                $(
+                    [<$kind:camel Set>],
+                )*
+            >
+            where
+                $(
+                    [<$kind:camel Set>]: $crate::actuators::ActuatorSet<Action = $action>,
+                )*
+            {
+                type Command = $command<
+                    $(
+                        [<$kind:camel Set>]::Id,
+                    )*
+                >;
+                type Error = $error<
+                    $(
+                        [<$kind:camel Set>]::Id,
+                        [<$kind:camel Set>]::Error,
+                    )*
+                >;
+
+                fn run(&mut self, command: &Self::Command) {
+                    match command {
+                        $(
+                            $command::[<$kind:camel>](id, action) => {
+                                self.[<$kind:snake _set>].run(id, action)
+                            },
+                        )*
+                    }
+                }
+
+                fn poll(&mut self, command: &Self::Command) -> core::task::Poll<Result<(), Self::Error>> {
+                    match command {
+                        $(
+                            $command::[<$kind:camel>](id, _) => {
+                                self
+                                    .[<$kind:snake _set>]
+                                    .poll(id)
+                                    .map_err(|err| $error::[<$kind:camel>](*id, err))
+                            },
+                        )*
+                    }
+                }
+
+                fn pause(&mut self, command: &Self::Command) {
+                    match command {
+                        $(
+                            $command::[<$kind:camel>](id, _) => self.[<$kind:snake _set>].pause(id),
+                        )*
+                    }
+                }
+
+                fn resume(&mut self, command: &Self::Command) {
+                    match command {
+                        $(
+                            $command::[<$kind:camel>](id, _) => self.[<$kind:snake _set>].resume(id),
+                        )*
+                    }
+                }
+
+                fn cancel(&mut self, command: &Self::Command) {
+                    match command {
+                        $(
+                            $command::[<$kind:camel>](id, _) => self.[<$kind:snake _set>].cancel(id),
+                        )*
+                    }
+                }
+
+                fn poll_all(&mut self) -> core::task::Poll<Result<(), Self::Error>> {
+                    let mut is_ready = true;
+
+                    $(
+                        for id in [<$kind:camel Set>]::IDS {
+                            match self.[<$kind:snake _set>].poll(id) {
+                                core::task::Poll::Ready(Ok(())) => {}
+                                core::task::Poll::Ready(Err(err)) => {
+                                    return core::task::Poll::Ready(Err($error::[<$kind:camel>](*id, err)))
+                                }
+                                core::task::Poll::Pending => is_ready = false,
+                            }
+                        }
+                    )*
+
+                    if is_ready {
+                        core::task::Poll::Ready(Ok(()))
+                    } else {
+                        core::task::Poll::Pending
+                    }
+                }
+
+                fn emergency_stop_all(&mut self) {
+                    $(
+                        for id in [<$kind:camel Set>]::IDS {
+                            self.[<$kind:snake _set>].emergency_stop(id);
+                        }
+                    )*
+                }
+            }
+        }
+    };
 }
 
 #[macro_export]
@@ -67,7 +342,7 @@ macro_rules! actuator_set {
         $error:ident
     ) => {
         $crate::paste! {
-            #[derive(Copy, Clone, Debug, defmt::Format)]
+            #[derive(Copy, Clone, Debug, defmt::Format, PartialEq, Eq)]
             pub enum $id {
                 $(
                     [<$actuator:camel>],
@@ -151,6 +426,12 @@ macro_rules! actuator_set {
                     )*
                 >;
 
+                const IDS: &'static [Self::Id] = &[
+                    $(
+                        $id::[<$actuator:camel>],
+                    )*
+                ];
+
                 fn run(&mut self, id: &Self::Id, action: &Self::Action) {
                     match id {
                         $(
@@ -175,6 +456,85 @@ macro_rules! actuator_set {
                         )*
                     }
                 }
+
+                fn pause(&mut self, id: &Self::Id) {
+                    match id {
+                        $(
+                            $id::[<$actuator:camel>] => {
+                                self
+                                    .[<$actuator:snake $type:snake>]
+                                    .pause()
+                            },
+                        )*
+                    }
+                }
+
+                fn resume(&mut self, id: &Self::Id) {
+                    match id {
+                        $(
+                            $id::[<$actuator:camel>] => {
+                                self
+                                    .[<$actuator:snake $type:snake>]
+                                    .resume()
+                            },
+                        )*
+                    }
+                }
+
+                fn cancel(&mut self, id: &Self::Id) {
+                    match id {
+                        $(
+                            $id::[<$actuator:camel>] => {
+                                self
+                                    .[<$actuator:snake $type:snake>]
+                                    .cancel()
+                            },
+                        )*
+                    }
+                }
+
+                fn emergency_stop(&mut self, id: &Self::Id) {
+                    match id {
+                        $(
+                            $id::[<$actuator:camel>] => {
+                                self
+                                    .[<$actuator:snake $type:snake>]
+                                    .emergency_stop()
+                            },
+                        )*
+                    }
+                }
+            }
+
+            impl<
+                Status,
+                $(
+                    [<$actuator:camel $type:camel>],
+                )*
+            > $crate::actuators::ActuatorSetStatus<Status> for $set<
+                $(
+                    [<$actuator:camel $type:camel>],
+                )*
+            >
+            where
+                $(
+                    [<$actuator:camel $type:camel>]: $crate::actuators::Actuator<Action = $action>,
+                    [<$actuator:camel $type:camel>]::Error: core::fmt::Debug,
+                    [<$actuator:camel $type:camel>]: $crate::actuators::ActuatorStatus,
+                    <[<$actuator:camel $type:camel>] as $crate::actuators::ActuatorStatus>::Status: Into<Status>,
+                )*
+            {
+                fn status(&self, id: &Self::Id) -> Status {
+                    match id {
+                        $(
+                            $id::[<$actuator:camel>] => {
+                                $crate::actuators::ActuatorStatus::status(
+                                    &self.[<$actuator:snake $type:snake>]
+                                ).into()
+                            },
+                        )*
+                    }
+                }
             }
         }
     };
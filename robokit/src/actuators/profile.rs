@@ -0,0 +1,322 @@
+// https://docs.rs/ramp-maker/latest/ramp_maker/trait.MotionProfile.html
+
+use num::{abs, Float};
+use stepper::ramp_maker::{MotionProfile, Trapezoidal};
+
+pub trait AxisProfile: MotionProfile<Velocity = f64, Delay = f64> {
+    fn from_limits(
+        max_acceleration_in_steps_per_sec_per_sec: f64,
+        max_jerk_in_steps_per_sec_per_sec_per_sec: Option<f64>,
+    ) -> Self;
+}
+
+impl AxisProfile for Trapezoidal<f64> {
+    fn from_limits(
+        max_acceleration_in_steps_per_sec_per_sec: f64,
+        _max_jerk_in_steps_per_sec_per_sec_per_sec: Option<f64>,
+    ) -> Self {
+        Trapezoidal::new(max_acceleration_in_steps_per_sec_per_sec)
+    }
+}
+
+const S_CURVE_STEP_SEARCH_COUNT: usize = 32;
+const S_CURVE_STEP_TOLERANCE: f64 = 1e-9;
+
+// the acceleration ramps up and down at the max jerk, instead of jumping
+#[derive(Clone, Copy, Debug)]
+pub struct SCurve {
+    max_acceleration: f64,
+    max_jerk: f64,
+    max_velocity: Option<f64>,
+    delay_initial: f64,
+    velocity: f64,
+    acceleration: f64,
+    steps_left: u32,
+}
+
+impl SCurve {
+    pub fn new(
+        max_acceleration_in_steps_per_sec_per_sec: f64,
+        max_jerk_in_steps_per_sec_per_sec_per_sec: f64,
+    ) -> Self {
+        let max_acceleration = max_acceleration_in_steps_per_sec_per_sec;
+        let max_jerk = max_jerk_in_steps_per_sec_per_sec_per_sec;
+
+        let delay_initial =
+            Float::cbrt(6_f64 / max_jerk).max(Float::sqrt(2_f64 / max_acceleration));
+
+        Self {
+            max_acceleration,
+            max_jerk,
+            max_velocity: None,
+            delay_initial,
+            velocity: 0_f64,
+            acceleration: 0_f64,
+            steps_left: 0,
+        }
+    }
+
+    fn min_velocity(&self) -> f64 {
+        1_f64 / self.delay_initial
+    }
+
+    fn release_velocity(&self, acceleration: f64) -> f64 {
+        acceleration * acceleration / (2_f64 * self.max_jerk)
+    }
+
+    fn advance(
+        &self,
+        velocity: f64,
+        acceleration: f64,
+        target_acceleration: f64,
+        duration: f64,
+    ) -> (f64, f64, f64) {
+        let ramp_duration = abs(target_acceleration - acceleration) / self.max_jerk;
+        let (mut distance, mut velocity, mut acceleration) = (0_f64, velocity, acceleration);
+
+        let jerk_duration = ramp_duration.min(duration);
+        if jerk_duration > 0_f64 {
+            let jerk = if target_acceleration > acceleration {
+                self.max_jerk
+            } else {
+                -self.max_jerk
+            };
+            distance += velocity * jerk_duration
+                + acceleration * jerk_duration * jerk_duration / 2_f64
+                + jerk * jerk_duration * jerk_duration * jerk_duration / 6_f64;
+            velocity += acceleration * jerk_duration + jerk * jerk_duration * jerk_duration / 2_f64;
+            acceleration = if jerk_duration < ramp_duration {
+                acceleration + jerk * jerk_duration
+            } else {
+                target_acceleration
+            };
+        } else {
+            acceleration = target_acceleration;
+        }
+
+        let hold_duration = duration - jerk_duration;
+        if hold_duration > 0_f64 {
+            distance +=
+                velocity * hold_duration + acceleration * hold_duration * hold_duration / 2_f64;
+            velocity += acceleration * hold_duration;
+        }
+
+        (distance, velocity, acceleration)
+    }
+
+    fn braking_distance(&self, velocity: f64, acceleration: f64) -> f64 {
+        let (mut distance, mut velocity, mut acceleration) = (0_f64, velocity, acceleration);
+
+        if acceleration > 0_f64 {
+            let (release_distance, release_velocity, _) =
+                self.advance(velocity, acceleration, 0_f64, acceleration / self.max_jerk);
+            distance += release_distance;
+            velocity = release_velocity;
+            acceleration = 0_f64;
+        }
+
+        // ramp up to the peak deceleration, hold it, then ramp it down as the velocity runs out
+        let deceleration = -acceleration;
+        let peak =
+            Float::sqrt((2_f64 * self.max_jerk * velocity + deceleration * deceleration) / 2_f64)
+                .min(self.max_acceleration)
+                .max(deceleration);
+
+        let (ramp_distance, velocity, _) = self.advance(
+            velocity,
+            -deceleration,
+            -peak,
+            (peak - deceleration) / self.max_jerk,
+        );
+        let hold_duration = ((velocity - self.release_velocity(peak)) / peak).max(0_f64);
+        let (hold_distance, velocity, _) = self.advance(velocity, -peak, -peak, hold_duration);
+        let (release_distance, _, _) = self.advance(velocity, -peak, 0_f64, peak / self.max_jerk);
+
+        distance + ramp_distance + hold_distance + release_distance
+    }
+
+    fn step(&self, target_acceleration: f64) -> (f64, f64, f64) {
+        let (velocity, acceleration) = (self.velocity, self.acceleration);
+        let distance =
+            |delay: f64| self.advance(velocity, acceleration, target_acceleration, delay);
+
+        let (mut min_delay, mut max_delay) = (0_f64, self.delay_initial);
+        let (crawl_distance, crawl_velocity, crawl_acceleration) = distance(max_delay);
+        if crawl_distance < 1_f64 {
+            return (max_delay, crawl_velocity, crawl_acceleration);
+        }
+
+        // newton's method, halving instead when a guess jumps out of bounds
+        let mut delay = if velocity > 0_f64 {
+            (1_f64 / velocity).min(max_delay)
+        } else {
+            max_delay
+        };
+        for _ in 0..S_CURVE_STEP_SEARCH_COUNT {
+            let (step_distance, step_velocity, _) = distance(delay);
+            if abs(step_distance - 1_f64) < S_CURVE_STEP_TOLERANCE {
+                break;
+            }
+            if step_distance < 1_f64 {
+                min_delay = delay;
+            } else {
+                max_delay = delay;
+            }
+
+            let next_delay = delay - (step_distance - 1_f64) / step_velocity;
+            delay = if step_velocity > 0_f64 && next_delay > min_delay && next_delay < max_delay {
+                next_delay
+            } else {
+                (min_delay + max_delay) / 2_f64
+            };
+        }
+
+        let (_, velocity, acceleration) = distance(delay);
+        (delay, velocity, acceleration)
+    }
+
+    fn braking_acceleration(&self) -> f64 {
+        if self.acceleration > 0_f64 || self.velocity <= self.release_velocity(self.acceleration) {
+            0_f64
+        } else {
+            -self.max_acceleration
+        }
+    }
+
+    fn cruise_acceleration(&self, max_velocity: f64) -> f64 {
+        let release_velocity = self.release_velocity(self.acceleration);
+
+        if self.velocity < max_velocity {
+            if self.acceleration >= 0_f64 && self.velocity + release_velocity >= max_velocity {
+                0_f64
+            } else {
+                self.max_acceleration
+            }
+        } else if self.velocity > max_velocity {
+            if self.acceleration <= 0_f64 && self.velocity - release_velocity <= max_velocity {
+                0_f64
+            } else {
+                -self.max_acceleration
+            }
+        } else {
+            0_f64
+        }
+    }
+}
+
+impl MotionProfile for SCurve {
+    type Velocity = f64;
+    type Delay = f64;
+
+    fn enter_position_mode(&mut self, max_velocity: Self::Velocity, num_steps: u32) {
+        self.max_velocity = if max_velocity > 0_f64 {
+            Some(max_velocity)
+        } else {
+            None
+        };
+        self.steps_left = num_steps;
+    }
+
+    fn next_delay(&mut self) -> Option<Self::Delay> {
+        let is_moving = self.velocity > self.min_velocity();
+        let is_stopping = self.steps_left == 0 || self.max_velocity.is_none();
+        if is_stopping
+            && (!is_moving || self.braking_distance(self.velocity, self.acceleration) < 1_f64)
+        {
+            self.velocity = 0_f64;
+            self.acceleration = 0_f64;
+            return None;
+        }
+
+        let cruise = match self.max_velocity {
+            Some(max_velocity) if self.steps_left > 0 => {
+                let (delay, mut velocity, mut acceleration) =
+                    self.step(self.cruise_acceleration(max_velocity));
+
+                let is_overshoot = (self.velocity < max_velocity && velocity > max_velocity)
+                    || (self.velocity > max_velocity && velocity < max_velocity);
+                if is_overshoot {
+                    velocity = max_velocity;
+                    acceleration = 0_f64;
+                }
+
+                let steps_left = (self.steps_left - 1) as f64;
+                if steps_left >= self.braking_distance(velocity, acceleration) {
+                    Some((delay, velocity, acceleration))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        let (delay, mut velocity, mut acceleration) = match cruise {
+            Some(step) => step,
+            None => self.step(self.braking_acceleration()),
+        };
+
+        if velocity < self.min_velocity() {
+            velocity = self.min_velocity();
+            acceleration = acceleration.max(0_f64);
+        }
+
+        self.velocity = velocity;
+        self.acceleration = acceleration;
+        self.steps_left = self.steps_left.saturating_sub(1);
+
+        Some(delay)
+    }
+}
+
+impl AxisProfile for SCurve {
+    fn from_limits(
+        max_acceleration_in_steps_per_sec_per_sec: f64,
+        max_jerk_in_steps_per_sec_per_sec_per_sec: Option<f64>,
+    ) -> Self {
+        SCurve::new(
+            max_acceleration_in_steps_per_sec_per_sec,
+            max_jerk_in_steps_per_sec_per_sec_per_sec.unwrap_or(f64::INFINITY),
+        )
+    }
+}
+
+pub enum AxisRamp {
+    Trapezoidal(Trapezoidal<f64>),
+    SCurve(SCurve),
+}
+
+impl MotionProfile for AxisRamp {
+    type Velocity = f64;
+    type Delay = f64;
+
+    fn enter_position_mode(&mut self, max_velocity: Self::Velocity, num_steps: u32) {
+        match self {
+            AxisRamp::Trapezoidal(profile) => profile.enter_position_mode(max_velocity, num_steps),
+            AxisRamp::SCurve(profile) => profile.enter_position_mode(max_velocity, num_steps),
+        }
+    }
+
+    fn next_delay(&mut self) -> Option<Self::Delay> {
+        match self {
+            AxisRamp::Trapezoidal(profile) => profile.next_delay(),
+            AxisRamp::SCurve(profile) => profile.next_delay(),
+        }
+    }
+}
+
+impl AxisProfile for AxisRamp {
+    fn from_limits(
+        max_acceleration_in_steps_per_sec_per_sec: f64,
+        max_jerk_in_steps_per_sec_per_sec_per_sec: Option<f64>,
+    ) -> Self {
+        match max_jerk_in_steps_per_sec_per_sec_per_sec {
+            Some(_) => AxisRamp::SCurve(SCurve::from_limits(
+                max_acceleration_in_steps_per_sec_per_sec,
+                max_jerk_in_steps_per_sec_per_sec_per_sec,
+            )),
+            None => AxisRamp::Trapezoidal(Trapezoidal::from_limits(
+                max_acceleration_in_steps_per_sec_per_sec,
+                None,
+            )),
+        }
+    }
+}
@@ -73,6 +73,9 @@ where
     <Serial as Write<u8>>::Error: Debug,
     <Serial as Read<u8>>::Error: Debug,
 {
+    // P04-02 takes -6000 to 6000 rpm
+    pub const MAX_RPM: i16 = 6000;
+
     pub fn new(serial: Serial) -> Self {
         Self {
             modbus: ModbusSerial::new(serial, 1),
@@ -91,7 +94,10 @@ where
             Poll::Ready(Ok(is_response_ready)) => {
                 if is_response_ready {
                     // handle modbus response
-                    match self.modbus_response_type.unwrap() {
+                    let modbus_response_type = self
+                        .modbus_response_type
+                        .ok_or(ModbusSerialError::Unexpected)?;
+                    match modbus_response_type {
                         JmcHsv57ModbusResponseType::InitControlMode => self.modbus.parse_ok()?,
                         JmcHsv57ModbusResponseType::InitSpeedSource => self.modbus.parse_ok()?,
                         JmcHsv57ModbusResponseType::InitAcceleration => self.modbus.parse_ok()?,
@@ -100,7 +106,7 @@ where
                         JmcHsv57ModbusResponseType::GetSpeed => {
                             let mut result: Vec<u16, 1> = Vec::new();
                             self.modbus.parse_u16(&mut result)?;
-                            let rpm_in_u16 = result.get(0).unwrap();
+                            let rpm_in_u16 = result.first().ok_or(ModbusSerialError::Unexpected)?;
                             let rpm_in_i16 = u16_to_i16(*rpm_in_u16);
                             self.current_rpm = Some(rpm_in_i16);
                         }
@@ -108,9 +114,9 @@ where
 
                     return Poll::Pending;
                 } else {
-                    if !self.modbus_requests.is_empty() {
+                    if let Some(modbus_request) = self.modbus_requests.pop_front() {
                         // setup next modbus request
-                        match self.modbus_requests.pop_front().unwrap() {
+                        match modbus_request {
                             JmcHsv57ModbusRequest::InitControlMode => {
                                 // set P01-01 (0x0065) to 1
                                 self.modbus_response_type =
@@ -294,4 +300,12 @@ where
     fn poll(&mut self) -> Poll<Result<(), Self::Error>> {
         self.driver.poll()
     }
+
+    fn cancel(&mut self) {
+        self.driver.set(SpindleStatus::Off)
+    }
+
+    fn emergency_stop(&mut self) {
+        self.driver.set(SpindleStatus::Off)
+    }
 }
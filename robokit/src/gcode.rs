@@ -0,0 +1,575 @@
+// https://marlinfw.org/meta/gcode/
+// https://linuxcnc.org/docs/html/gcode/g-code.html
+
+use core::fmt::Debug;
+use core::marker::PhantomData;
+use defmt::Format;
+use fugit::TimerDurationU32 as TimerDuration;
+use heapless::Vec;
+
+use crate::actuators::{
+    axis::AxisAction,
+    spindle::{SpindleAction, SpindleStatus},
+};
+use crate::motion::{
+    ArcCenter, ArcDirection, MotionAction, MotionArc, MotionTargets, MOTION_AXES_COUNT,
+};
+use crate::runner::Command;
+
+// spindle, group start, an axis for each of XYZABCUVW, group end
+pub const GCODE_LINE_COMMANDS_COUNT: usize = 12;
+
+const AXIS_LETTERS: &[char] = &['X', 'Y', 'Z', 'A', 'B', 'C', 'U', 'V', 'W'];
+
+#[derive(Clone, Copy, Debug, Format, PartialEq)]
+pub enum GcodeError {
+    UnexpectedCharacter(char),
+    InvalidNumber { letter: char },
+    UnsupportedG(f64),
+    UnsupportedM(f64),
+    UnknownAxis(char),
+    NoMotionMode,
+    NoFeedRate,
+    NoSpindle,
+    NoDwellTime,
+    FeedRateOutOfRange(f64),
+    SpindleSpeedOutOfRange(f64),
+    DwellTimeOutOfRange(f64),
+    TooManyCommands,
+    // more axes on a line than a motion moves together
+    TooManyAxes,
+    NoArcCenter,
+    // a radius alone doesn't say where the center of a full circle is
+    RadiusFullCircle,
+}
+
+#[derive(Clone, Copy, Debug, Format)]
+pub struct GcodeSettings {
+    // velocity of G0 moves
+    pub rapid_velocity_in_millimeters_per_sec: f64,
+    // velocity and back off of G28 homing
+    pub home_velocity_in_millimeters_per_sec: f64,
+    pub home_back_off_distance_in_millimeters: f64,
+    // how far the straight segments of G2 and G3 arcs may stray from the arc
+    pub arc_tolerance_in_millimeters: f64,
+}
+
+#[derive(Clone, Copy, Debug, Format, PartialEq)]
+enum GcodeMotion {
+    Rapid,
+    Linear,
+    Arc(ArcDirection),
+}
+
+#[derive(Clone, Copy, Debug, Format, PartialEq)]
+enum GcodePlane {
+    Xy,
+    Zx,
+    Yz,
+}
+
+impl GcodePlane {
+    // the axis letters of the plane, then of the helix
+    fn letters(&self) -> (char, char, char) {
+        match self {
+            GcodePlane::Xy => ('X', 'Y', 'Z'),
+            GcodePlane::Zx => ('Z', 'X', 'Y'),
+            GcodePlane::Yz => ('Y', 'Z', 'X'),
+        }
+    }
+
+    // which of I, J and K offset the center along the plane
+    fn offset_indexes(&self) -> (usize, usize) {
+        match self {
+            GcodePlane::Xy => (0, 1),
+            GcodePlane::Zx => (2, 0),
+            GcodePlane::Yz => (1, 2),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Format, PartialEq)]
+enum GcodeDistance {
+    Absolute,
+    Relative,
+}
+
+type CommandsAlias<const TIMER_HZ: u32, LedId, AxisId, SpindleId, SwitchId, ExtraCommand> = Vec<
+    Command<TIMER_HZ, LedId, AxisId, SpindleId, SwitchId, ExtraCommand>,
+    GCODE_LINE_COMMANDS_COUNT,
+>;
+
+pub struct GcodeInterpreter<
+    'a,
+    const TIMER_HZ: u32,
+    LedId,
+    AxisId,
+    SpindleId,
+    SwitchId,
+    ExtraCommand = (),
+> where
+    LedId: Copy + Debug + Format,
+    AxisId: Copy + Debug + Format,
+    SpindleId: Copy + Debug + Format,
+    SwitchId: Copy + Debug + Format,
+    ExtraCommand: Copy + Debug + Format,
+{
+    axes: &'a [(char, AxisId)],
+    spindle: Option<SpindleId>,
+    settings: GcodeSettings,
+    motion: Option<GcodeMotion>,
+    distance: GcodeDistance,
+    plane: GcodePlane,
+    feed_rate_in_millimeters_per_min: Option<f64>,
+    spindle_speed_in_rpm: i16,
+    ids: PhantomData<(LedId, SwitchId, ExtraCommand)>,
+}
+
+// the words of a single line
+#[derive(Default)]
+struct GcodeLine {
+    motion: Option<GcodeMotion>,
+    distance: Option<GcodeDistance>,
+    plane: Option<GcodePlane>,
+    is_dwell: bool,
+    is_home: bool,
+    spindle: Option<bool>,
+    axes: [Option<f64>; 9],
+    feed_rate: Option<f64>,
+    spindle_speed: Option<f64>,
+    dwell_in_millis: Option<f64>,
+    // I, J and K
+    arc_offsets: [Option<f64>; 3],
+    arc_radius: Option<f64>,
+}
+
+impl<'a, const TIMER_HZ: u32, LedId, AxisId, SpindleId, SwitchId, ExtraCommand>
+    GcodeInterpreter<'a, TIMER_HZ, LedId, AxisId, SpindleId, SwitchId, ExtraCommand>
+where
+    LedId: Copy + Debug + Format,
+    AxisId: Copy + Debug + Format,
+    SpindleId: Copy + Debug + Format,
+    SwitchId: Copy + Debug + Format,
+    ExtraCommand: Copy + Debug + Format,
+{
+    // axes maps axis letters (e.g. 'X') to your axis ids
+    pub fn new(axes: &'a [(char, AxisId)], settings: GcodeSettings) -> Self {
+        Self {
+            axes,
+            spindle: None,
+            settings,
+            motion: None,
+            distance: GcodeDistance::Absolute,
+            plane: GcodePlane::Xy,
+            feed_rate_in_millimeters_per_min: None,
+            spindle_speed_in_rpm: 0,
+            ids: PhantomData,
+        }
+    }
+
+    pub fn with_spindle(mut self, spindle: SpindleId) -> Self {
+        self.spindle = Some(spindle);
+        self
+    }
+
+    pub fn interpret_line(
+        &mut self,
+        line: &str,
+    ) -> Result<CommandsAlias<TIMER_HZ, LedId, AxisId, SpindleId, SwitchId, ExtraCommand>, GcodeError>
+    {
+        let words = parse_line(line)?;
+
+        // modal state
+        if let Some(motion) = words.motion {
+            self.motion = Some(motion);
+        }
+        if let Some(distance) = words.distance {
+            self.distance = distance;
+        }
+        if let Some(plane) = words.plane {
+            self.plane = plane;
+        }
+        if let Some(feed_rate) = words.feed_rate {
+            // a move at no speed would never finish
+            if feed_rate <= 0_f64 {
+                return Err(GcodeError::FeedRateOutOfRange(feed_rate));
+            }
+            self.feed_rate_in_millimeters_per_min = Some(feed_rate);
+        }
+        if let Some(spindle_speed) = words.spindle_speed {
+            if !words.is_dwell {
+                if !(0_f64..=i16::MAX as f64).contains(&spindle_speed) {
+                    return Err(GcodeError::SpindleSpeedOutOfRange(spindle_speed));
+                }
+                self.spindle_speed_in_rpm = spindle_speed as i16;
+            }
+        }
+
+        let mut commands = Vec::new();
+
+        if let Some(is_on) = words.spindle {
+            let spindle = self.spindle.ok_or(GcodeError::NoSpindle)?;
+            let status = match is_on {
+                true => SpindleStatus::On {
+                    rpm: self.spindle_speed_in_rpm,
+                },
+                false => SpindleStatus::Off,
+            };
+            push(
+                &mut commands,
+                Command::Spindle(spindle, SpindleAction::Set { status }),
+            )?;
+        }
+
+        if words.is_dwell {
+            let dwell_in_millis = words
+                .dwell_in_millis
+                .or(words.spindle_speed.map(|secs| secs * 1000_f64))
+                .ok_or(GcodeError::NoDwellTime)?;
+            if !(0_f64..=u32::MAX as f64).contains(&dwell_in_millis) {
+                return Err(GcodeError::DwellTimeOutOfRange(dwell_in_millis));
+            }
+            push(
+                &mut commands,
+                Command::Wait {
+                    duration: TimerDuration::<TIMER_HZ>::millis(dwell_in_millis as u32),
+                },
+            )?;
+
+            return Ok(commands);
+        }
+
+        // axis words are homed, not moved to
+        if words.is_home {
+            let is_all = words.axes.iter().all(|value| value.is_none());
+            let mut home_commands = Vec::<_, GCODE_LINE_COMMANDS_COUNT>::new();
+            for (letter_index, value) in words.axes.iter().enumerate() {
+                if !is_all && value.is_none() {
+                    continue;
+                }
+                let letter = AXIS_LETTERS[letter_index];
+                let axis = match self.axis(letter) {
+                    Some(axis) => axis,
+                    // homing all only homes the mapped axes
+                    None if is_all => continue,
+                    None => return Err(GcodeError::UnknownAxis(letter)),
+                };
+                push(
+                    &mut home_commands,
+                    Command::Axis(
+                        axis,
+                        AxisAction::Home {
+                            max_velocity_in_millimeters_per_sec: self
+                                .settings
+                                .home_velocity_in_millimeters_per_sec,
+                            back_off_distance_in_millimeters: self
+                                .settings
+                                .home_back_off_distance_in_millimeters,
+                        },
+                    ),
+                )?;
+            }
+            push_group(&mut commands, &home_commands)?;
+
+            return Ok(commands);
+        }
+
+        // an arc without axis words is a full circle
+        let has_arc_center =
+            words.arc_radius.is_some() || words.arc_offsets.iter().any(|value| value.is_some());
+        if words.axes.iter().all(|value| value.is_none()) && !has_arc_center {
+            return Ok(commands);
+        }
+
+        let max_velocity_in_millimeters_per_sec = match self.motion {
+            None => return Err(GcodeError::NoMotionMode),
+            Some(GcodeMotion::Rapid) => self.settings.rapid_velocity_in_millimeters_per_sec,
+            Some(GcodeMotion::Linear) | Some(GcodeMotion::Arc(_)) => {
+                let feed_rate = self
+                    .feed_rate_in_millimeters_per_min
+                    .ok_or(GcodeError::NoFeedRate)?;
+                feed_rate / 60_f64
+            }
+        };
+
+        if let Some(GcodeMotion::Arc(direction)) = self.motion {
+            let command = self.arc(&words, direction, max_velocity_in_millimeters_per_sec)?;
+            push(&mut commands, command)?;
+
+            return Ok(commands);
+        }
+
+        // the axes move together in a straight line
+        let mut targets: MotionTargets<AxisId> = [None; MOTION_AXES_COUNT];
+        let mut targets_count = 0;
+        for (letter_index, value) in words.axes.iter().enumerate() {
+            let value = match value {
+                Some(value) => *value,
+                None => continue,
+            };
+            let letter = AXIS_LETTERS[letter_index];
+            let axis = self.axis(letter).ok_or(GcodeError::UnknownAxis(letter))?;
+            let target = targets
+                .get_mut(targets_count)
+                .ok_or(GcodeError::TooManyAxes)?;
+            *target = Some((axis, value));
+            targets_count += 1;
+        }
+
+        let command = match (targets, self.distance) {
+            ([Some((axis, value)), None, ..], GcodeDistance::Absolute) => Command::Axis(
+                axis,
+                AxisAction::MoveAbsolute {
+                    max_velocity_in_millimeters_per_sec,
+                    position_in_millimeters: value,
+                },
+            ),
+            ([Some((axis, value)), None, ..], GcodeDistance::Relative) => Command::Axis(
+                axis,
+                AxisAction::MoveRelative {
+                    max_velocity_in_millimeters_per_sec,
+                    distance_in_millimeters: value,
+                },
+            ),
+            (positions_in_millimeters, GcodeDistance::Absolute) => {
+                Command::Motion(MotionAction::LinearMoveAbsolute {
+                    max_velocity_in_millimeters_per_sec,
+                    positions_in_millimeters,
+                })
+            }
+            (distances_in_millimeters, GcodeDistance::Relative) => {
+                Command::Motion(MotionAction::LinearMoveRelative {
+                    max_velocity_in_millimeters_per_sec,
+                    distances_in_millimeters,
+                })
+            }
+        };
+        push(&mut commands, command)?;
+
+        Ok(commands)
+    }
+
+    fn arc(
+        &self,
+        words: &GcodeLine,
+        direction: ArcDirection,
+        max_velocity_in_millimeters_per_sec: f64,
+    ) -> Result<Command<TIMER_HZ, LedId, AxisId, SpindleId, SwitchId, ExtraCommand>, GcodeError>
+    {
+        let (first_letter, second_letter, helix_letter) = self.plane.letters();
+        let value = |letter: char| {
+            AXIS_LETTERS
+                .iter()
+                .position(|axis_letter| *axis_letter == letter)
+                .and_then(|letter_index| words.axes[letter_index])
+        };
+
+        // only the axes of the plane and the helix move along an arc
+        for (letter_index, value) in words.axes.iter().enumerate() {
+            let letter = AXIS_LETTERS[letter_index];
+            if value.is_some() && ![first_letter, second_letter, helix_letter].contains(&letter) {
+                return Err(GcodeError::TooManyAxes);
+            }
+        }
+
+        let first_axis = self
+            .axis(first_letter)
+            .ok_or(GcodeError::UnknownAxis(first_letter))?;
+        let second_axis = self
+            .axis(second_letter)
+            .ok_or(GcodeError::UnknownAxis(second_letter))?;
+        let helix = match value(helix_letter) {
+            Some(helix_value) => {
+                let helix_axis = self
+                    .axis(helix_letter)
+                    .ok_or(GcodeError::UnknownAxis(helix_letter))?;
+                Some((helix_axis, helix_value))
+            }
+            None => None,
+        };
+
+        let (first_offset_index, second_offset_index) = self.plane.offset_indexes();
+        let center = match (
+            words.arc_radius,
+            words.arc_offsets[first_offset_index],
+            words.arc_offsets[second_offset_index],
+        ) {
+            (Some(radius_in_millimeters), _, _) => {
+                // an absolute arc could still end where it starts, which the motion rejects
+                let ends = [value(first_letter), value(second_letter)];
+                let is_full_circle = match self.distance {
+                    GcodeDistance::Absolute => ends.iter().all(Option::is_none),
+                    GcodeDistance::Relative => ends
+                        .iter()
+                        .all(|distance| distance.unwrap_or(0_f64) == 0_f64),
+                };
+                if is_full_circle {
+                    return Err(GcodeError::RadiusFullCircle);
+                }
+
+                ArcCenter::Radius {
+                    radius_in_millimeters,
+                }
+            }
+            (None, None, None) => return Err(GcodeError::NoArcCenter),
+            (None, first_offset, second_offset) => ArcCenter::Offset {
+                first_in_millimeters: first_offset.unwrap_or(0_f64),
+                second_in_millimeters: second_offset.unwrap_or(0_f64),
+            },
+        };
+
+        let arc = MotionArc {
+            plane: (first_axis, second_axis),
+            direction,
+            end_in_millimeters: (value(first_letter), value(second_letter)),
+            center,
+            helix,
+            tolerance_in_millimeters: self.settings.arc_tolerance_in_millimeters,
+        };
+
+        Ok(Command::Motion(match self.distance {
+            GcodeDistance::Absolute => MotionAction::ArcMoveAbsolute {
+                max_velocity_in_millimeters_per_sec,
+                arc,
+            },
+            GcodeDistance::Relative => MotionAction::ArcMoveRelative {
+                max_velocity_in_millimeters_per_sec,
+                arc,
+            },
+        }))
+    }
+
+    fn axis(&self, letter: char) -> Option<AxisId> {
+        self.axes
+            .iter()
+            .find(|(axis_letter, _)| axis_letter.eq_ignore_ascii_case(&letter))
+            .map(|(_, axis)| *axis)
+    }
+}
+
+fn push<Command>(
+    commands: &mut Vec<Command, GCODE_LINE_COMMANDS_COUNT>,
+    command: Command,
+) -> Result<(), GcodeError> {
+    commands
+        .push(command)
+        .map_err(|_| GcodeError::TooManyCommands)
+}
+
+// run more than one command together
+fn push_group<const TIMER_HZ: u32, LedId, AxisId, SpindleId, SwitchId, ExtraCommand>(
+    commands: &mut Vec<
+        Command<TIMER_HZ, LedId, AxisId, SpindleId, SwitchId, ExtraCommand>,
+        GCODE_LINE_COMMANDS_COUNT,
+    >,
+    group: &[Command<TIMER_HZ, LedId, AxisId, SpindleId, SwitchId, ExtraCommand>],
+) -> Result<(), GcodeError>
+where
+    LedId: Copy + Debug + Format,
+    AxisId: Copy + Debug + Format,
+    SpindleId: Copy + Debug + Format,
+    SwitchId: Copy + Debug + Format,
+    ExtraCommand: Copy + Debug + Format,
+{
+    if group.len() > 1 {
+        push(commands, Command::GroupStart)?;
+    }
+    for command in group {
+        push(commands, *command)?;
+    }
+    if group.len() > 1 {
+        push(commands, Command::GroupEnd)?;
+    }
+
+    Ok(())
+}
+
+fn parse_line(line: &str) -> Result<GcodeLine, GcodeError> {
+    let mut words = GcodeLine::default();
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((_, letter)) = chars.next() {
+        let letter = letter.to_ascii_uppercase();
+
+        match letter {
+            // the rest is a comment or checksum
+            ';' | '*' => break,
+            '(' => {
+                for (_, c) in chars.by_ref() {
+                    if c == ')' {
+                        break;
+                    }
+                }
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            c if c.is_ascii_alphabetic() => {}
+            c => return Err(GcodeError::UnexpectedCharacter(c)),
+        }
+
+        while let Some((_, c)) = chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            chars.next();
+        }
+        let start = match chars.peek() {
+            Some((start, _)) => *start,
+            None => line.len(),
+        };
+        let mut end = start;
+        while let Some((index, c)) = chars.peek() {
+            if !(c.is_ascii_digit() || *c == '.' || *c == '-' || *c == '+') {
+                break;
+            }
+            end = index + c.len_utf8();
+            chars.next();
+        }
+        let value: f64 = line[start..end]
+            .parse()
+            .map_err(|_| GcodeError::InvalidNumber { letter })?;
+
+        match letter {
+            'G' => match value as u32 {
+                // no sub codes (e.g. G28.1)
+                code if code as f64 != value => return Err(GcodeError::UnsupportedG(value)),
+                0 => words.motion = Some(GcodeMotion::Rapid),
+                1 => words.motion = Some(GcodeMotion::Linear),
+                2 => words.motion = Some(GcodeMotion::Arc(ArcDirection::Clockwise)),
+                3 => words.motion = Some(GcodeMotion::Arc(ArcDirection::CounterClockwise)),
+                4 => words.is_dwell = true,
+                17 => words.plane = Some(GcodePlane::Xy),
+                18 => words.plane = Some(GcodePlane::Zx),
+                19 => words.plane = Some(GcodePlane::Yz),
+                // millimeters are the only units
+                21 => {}
+                28 => words.is_home = true,
+                90 => words.distance = Some(GcodeDistance::Absolute),
+                91 => words.distance = Some(GcodeDistance::Relative),
+                _ => return Err(GcodeError::UnsupportedG(value)),
+            },
+            'M' => match value as u32 {
+                // no sub codes (e.g. M3.1)
+                code if code as f64 != value => return Err(GcodeError::UnsupportedM(value)),
+                3 => words.spindle = Some(true),
+                5 => words.spindle = Some(false),
+                _ => return Err(GcodeError::UnsupportedM(value)),
+            },
+            'F' => words.feed_rate = Some(value),
+            'S' => words.spindle_speed = Some(value),
+            'P' => words.dwell_in_millis = Some(value),
+            'I' => words.arc_offsets[0] = Some(value),
+            'J' => words.arc_offsets[1] = Some(value),
+            'K' => words.arc_offsets[2] = Some(value),
+            'R' => words.arc_radius = Some(value),
+            // line numbers
+            'N' => {}
+            _ => match AXIS_LETTERS.iter().position(|axis| *axis == letter) {
+                Some(letter_index) => words.axes[letter_index] = Some(value),
+                None => return Err(GcodeError::UnexpectedCharacter(letter)),
+            },
+        }
+    }
+
+    Ok(words)
+}
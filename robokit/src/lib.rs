@@ -8,35 +8,365 @@ extern crate alloc;
 
 pub mod actuators;
 pub mod error;
+pub mod gcode;
 pub mod modbus;
+pub mod motion;
 pub mod robot;
 pub mod runner;
 pub mod scheduler;
 pub mod sensors;
+pub mod simulation;
+pub mod stream;
 pub mod timer;
 pub mod util;
+pub mod validation;
 
 pub use paste::paste;
 
-pub use crate::actuators::axis::{AxisAction, AxisDevice, AxisError, AxisLimitSide};
+pub use crate::actuators::axis::{AxisAction, AxisDevice, AxisError, AxisLimitSide, AxisStatus};
 pub use crate::actuators::led::{LedAction, LedDevice, LedError};
+pub use crate::actuators::profile::{AxisProfile, AxisRamp, SCurve};
 pub use crate::actuators::spindle::{
     SpindleAction, SpindleDevice, SpindleDriverJmcHsv57, SpindleError, SpindleStatus,
 };
-pub use crate::actuators::{Actuator, ActuatorSet, EmptyActuatorSet};
-pub use crate::robot::{Robot, RobotBuilder};
-pub use crate::runner::Command;
+pub use crate::actuators::{
+    Actuator, ActuatorSet, ActuatorSetList, ActuatorSetStatus, ActuatorStatus, EmptyActuatorSet,
+    EmptyActuatorSetList,
+};
+pub use crate::gcode::{GcodeError, GcodeInterpreter, GcodeSettings};
+pub use crate::motion::{
+    ArcCenter, ArcDirection, MotionAction, MotionArc, MotionError, MotionSegments, MotionTargets,
+    MOTION_AXES_COUNT,
+};
+pub use crate::robot::{Robot, RobotBuilder, RobotStatus};
+pub use crate::runner::{Command, TimedCommand};
+pub use crate::scheduler::{
+    ErrorPolicy, Program, ProgramBuildError, ProgramPart, ProgramSelectError, RunMode,
+    SchedulerEvent, SchedulerObserver, SchedulerPhase,
+};
 pub use crate::sensors::switch::{
     SwitchActiveHigh, SwitchActiveLow, SwitchDevice, SwitchError, SwitchStatus, SwitchUpdate,
 };
-pub use crate::sensors::Sensor;
+pub use crate::sensors::{EmptySensorSet, Sensor, SensorSet};
+pub use crate::simulation::{
+    SimulatedAxis, SimulatedAxisError, SimulatedAxisStatus, SimulatedPin, SimulatedSpindleDriver,
+    Simulation, SimulationReport,
+};
+pub use crate::stream::{CommandProducer, CommandQueue, CommandStream, StreamItem};
 pub use crate::timer::{SubTimer, SubTimerError, SuperTimer};
+pub use crate::validation::{
+    AxisLimits, AxisValidator, ProgramError, SpindleLimits, ValidationError, ValidationSettings,
+};
 
 #[cfg(test)]
 mod unit_tests {
+    use alloc::rc::Rc;
     use core::assert_eq;
+    use core::cell::RefCell;
+    use core::fmt::Debug;
+    use core::sync::atomic::{AtomicU32, Ordering};
+    use core::task::Poll;
+    use fugit::{
+        TimerDurationU32 as TimerDuration, TimerDurationU64, TimerInstantU32 as TimerInstant,
+    };
+    use fugit_timer::Timer as FugitTimer;
+    use heapless::Vec;
+    use num::{abs, Float};
+    use stepper::ramp_maker::{MotionProfile, Trapezoidal};
 
+    use super::actuators::{
+        axis::{AxisAction, AxisDevice, AxisError, AxisLimitSide, AxisStatus},
+        led::LedAction,
+        profile::{AxisProfile, AxisRamp, SCurve},
+        spindle::{SpindleAction, SpindleStatus},
+        Actuator, ActuatorSet, ActuatorSetStatus, ActuatorStatus, EmptyActuatorSet,
+        EmptyActuatorSetList,
+    };
+    use super::gcode::{GcodeError, GcodeInterpreter, GcodeSettings};
+    use super::motion::{ArcCenter, ArcDirection, MotionAction, MotionArc, MotionError};
+    use super::robot::{RobotBuilder, RobotStatus};
+    use super::runner::{Command, Runner, RunnerAction, RunnerError, TimedCommand};
+    use super::scheduler::{
+        CommandFlow, CommandFlowError, CommandSource, ErrorPolicy, Program, ProgramCommand,
+        ProgramPart, ProgramSelectError, RunMode, Scheduler, SchedulerEvent, SchedulerObserver,
+        SchedulerPhase,
+    };
+    use super::sensors::{
+        switch::{SwitchStatus, SwitchUpdate},
+        EmptySensorSet, Sensor,
+    };
+    use super::simulation::{SimulatedAxis, SimulatedAxisError, SimulatedPin, Simulation};
+    use super::stream::{CommandQueue, CommandStream, StreamItem};
+    use super::timer::SubTimer;
     use super::util;
+    use super::validation::{
+        AxisLimits, AxisValidator, ProgramError, ValidationError, ValidationSettings,
+    };
+
+    // defmt needs a global logger to link, tests have nowhere to log to
+    #[defmt::global_logger]
+    struct NoopLogger;
+
+    unsafe impl defmt::Logger for NoopLogger {
+        fn acquire() {}
+        unsafe fn flush() {}
+        unsafe fn release() {}
+        unsafe fn write(_bytes: &[u8]) {}
+    }
+
+    defmt::timestamp!("");
+
+    #[derive(Debug, PartialEq)]
+    enum MockError {
+        Fail,
+        Flow(CommandFlowError),
+    }
+
+    impl From<CommandFlowError> for MockError {
+        fn from(err: CommandFlowError) -> Self {
+            MockError::Flow(err)
+        }
+    }
+
+    struct MockRunner {
+        runs: Rc<RefCell<Vec<u8, 64>>>,
+        failed: Option<u8>,
+    }
+
+    impl MockRunner {
+        fn new(runs: Rc<RefCell<Vec<u8, 64>>>) -> Self {
+            Self { runs, failed: None }
+        }
+    }
+
+    impl Actuator for MockRunner {
+        type Action = RunnerAction<u8>;
+        type Error = MockError;
+
+        fn run(&mut self, action: &Self::Action) {
+            match action {
+                RunnerAction::Run(command) => {
+                    self.runs.borrow_mut().push(*command).unwrap();
+                    if *command == FAIL {
+                        self.failed = Some(*command);
+                    }
+                }
+                RunnerAction::Reset => self.failed = None,
+                RunnerAction::Retry => {
+                    if let Some(command) = self.failed {
+                        self.runs.borrow_mut().push(command).unwrap();
+                    }
+                }
+            }
+        }
+
+        fn poll(&mut self) -> Poll<Result<(), Self::Error>> {
+            match self.failed {
+                Some(_) => Poll::Ready(Err(MockError::Fail)),
+                None => Poll::Ready(Ok(())),
+            }
+        }
+
+        fn cancel(&mut self) {}
+
+        fn emergency_stop(&mut self) {}
+    }
+
+    const GROUP_START: u8 = 100;
+    const GROUP_END: u8 = 101;
+    // always fails
+    const FAIL: u8 = 102;
+    const LABEL: u8 = 103;
+    const CALL: u8 = 104;
+    const RETURN: u8 = 105;
+    const REPEAT: u8 = 106;
+    const REPEAT_END: u8 = 107;
+    // to a label that doesn't exist
+    const JUMP: u8 = 108;
+
+    impl ProgramCommand for u8 {
+        fn flow(&self) -> CommandFlow {
+            match *self {
+                GROUP_START => CommandFlow::GroupStart,
+                GROUP_END => CommandFlow::GroupEnd,
+                LABEL => CommandFlow::Label { label: "sub" },
+                CALL => CommandFlow::Call { label: "sub" },
+                RETURN => CommandFlow::Return,
+                REPEAT => CommandFlow::RepeatStart { count: 2 },
+                REPEAT_END => CommandFlow::RepeatEnd,
+                JUMP => CommandFlow::Jump { label: "nowhere" },
+                _ => CommandFlow::Run,
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum MockEvent {
+        Phase(SchedulerPhase),
+        Started(usize),
+        Finished(usize),
+        Cycle(usize),
+        Retry(usize),
+        Error,
+    }
+
+    #[derive(Default)]
+    struct MockObserver {
+        events: Vec<MockEvent, 32>,
+    }
+
+    impl SchedulerObserver<u8, MockError> for MockObserver {
+        fn notify(&mut self, event: SchedulerEvent<'_, u8, MockError>) {
+            let event = match event {
+                SchedulerEvent::Phase(phase) => MockEvent::Phase(phase),
+                SchedulerEvent::Started { command_index, .. } => MockEvent::Started(command_index),
+                SchedulerEvent::Finished { command_index } => MockEvent::Finished(command_index),
+                SchedulerEvent::Cycle { cycle_count } => MockEvent::Cycle(cycle_count),
+                SchedulerEvent::Retry { retry_count } => MockEvent::Retry(retry_count),
+                SchedulerEvent::Error(_) => MockEvent::Error,
+            };
+            self.events.push(event).unwrap();
+        }
+    }
+
+    struct MockSwitch {
+        update: Rc<RefCell<Option<SwitchUpdate>>>,
+    }
+
+    impl Sensor for MockSwitch {
+        type Message = SwitchUpdate;
+        type Error = ();
+
+        fn sense(&mut self) -> Result<Option<Self::Message>, Self::Error> {
+            Ok(self.update.borrow_mut().take())
+        }
+    }
+
+    // done the second time it's waited on, so an axis steps without a clock
+    struct MockTimer {
+        is_waiting: bool,
+    }
+
+    impl FugitTimer<1_000_000> for MockTimer {
+        type Error = ();
+
+        fn now(&mut self) -> TimerInstant<1_000_000> {
+            TimerInstant::from_ticks(0)
+        }
+
+        fn start(&mut self, _duration: TimerDuration<1_000_000>) -> Result<(), Self::Error> {
+            self.is_waiting = true;
+            Ok(())
+        }
+
+        fn cancel(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn wait(&mut self) -> nb::Result<(), Self::Error> {
+            if self.is_waiting {
+                self.is_waiting = false;
+                Err(nb::Error::WouldBlock)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    // a real axis, stepped without a clock, between two mock limit switches
+    fn mock_axis(
+        limit_min: &Rc<RefCell<Option<SwitchUpdate>>>,
+        limit_max: &Rc<RefCell<Option<SwitchUpdate>>>,
+    ) -> impl Actuator<Action = AxisAction, Error = AxisError<impl Debug, (), ()>>
+           + ActuatorStatus<Status = AxisStatus> {
+        AxisDevice::new_dq542ma(
+            SimulatedPin,
+            SimulatedPin,
+            MockTimer { is_waiting: false },
+            1_000_f64,
+            10_f64,
+            MockSwitch {
+                update: limit_min.clone(),
+            },
+            MockSwitch {
+                update: limit_max.clone(),
+            },
+            AxisLimitSide::Min,
+        )
+        .unwrap()
+    }
+
+    fn switch_update(status: SwitchStatus) -> Option<SwitchUpdate> {
+        Some(SwitchUpdate { status })
+    }
+
+    crate::sensor_set!(
+        Switch { part_present },
+        SwitchUpdate,
+        SwitchId,
+        SwitchSet,
+        SwitchSetError
+    );
+
+    crate::actuator_set!(Axis { x }, AxisAction, AxisId, AxisSet, AxisSetError);
+    crate::actuator_set!(
+        Axis { x, y },
+        AxisAction,
+        XyAxisId,
+        XyAxisSet,
+        XyAxisSetError
+    );
+
+    // an actuator of a kind robokit doesn't know about
+    struct MockRelay {
+        is_on: Rc<RefCell<bool>>,
+    }
+
+    impl Actuator for MockRelay {
+        type Action = bool;
+        type Error = ();
+
+        fn run(&mut self, action: &Self::Action) {
+            *self.is_on.borrow_mut() = *action;
+        }
+
+        fn poll(&mut self) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn cancel(&mut self) {}
+
+        fn emergency_stop(&mut self) {
+            *self.is_on.borrow_mut() = false;
+        }
+    }
+
+    crate::actuator_set!(Relay { main }, bool, RelayId, RelaySet, RelaySetError);
+
+    crate::actuator_set_list!(
+        { relay: bool },
+        ExtraCommand,
+        ExtraSets,
+        ExtraSetsError
+    );
+
+    fn run_scheduler<
+        RunCommands,
+        const START: usize,
+        const STOP: usize,
+        const ERROR: usize,
+        Observer,
+    >(
+        scheduler: &mut Scheduler<u8, MockRunner, RunCommands, START, STOP, ERROR, Observer>,
+    ) where
+        RunCommands: CommandSource<u8>,
+        Observer: SchedulerObserver<u8, MockError>,
+    {
+        scheduler.start();
+        for _ in 0..100 {
+            let _ = scheduler.poll();
+        }
+    }
 
     #[test]
     fn i16_to_u16() {
@@ -59,4 +389,2093 @@ mod unit_tests {
         assert_eq!(util::u16_to_i16(65534), -2);
         assert_eq!(util::u16_to_i16(32768), -32768);
     }
+
+    #[test]
+    fn scheduler_repeat() {
+        let runs = Rc::new(RefCell::new(Vec::new()));
+        let mut scheduler: Scheduler<u8, MockRunner, Vec<u8, 2>, 1, 1, 0, ()> = Scheduler::new(
+            MockRunner::new(runs.clone()),
+            Vec::from_slice(&[1, 2]).unwrap(),
+            Vec::from_slice(&[0]).unwrap(),
+            Vec::from_slice(&[9]).unwrap(),
+            Vec::new(),
+            RunMode::Repeat { count: 3 },
+            ErrorPolicy::Halt,
+        );
+
+        run_scheduler(&mut scheduler);
+
+        assert_eq!(scheduler.cycle_count(), 3);
+        assert_eq!(*runs.borrow(), [0, 1, 2, 1, 2, 1, 2, 9]);
+
+        // no cycles, as the program shows
+        let runs = Rc::new(RefCell::new(Vec::new()));
+        let mut scheduler: Scheduler<u8, MockRunner, Vec<u8, 2>, 1, 1, 0, ()> = Scheduler::new(
+            MockRunner::new(runs.clone()),
+            Vec::from_slice(&[1, 2]).unwrap(),
+            Vec::from_slice(&[0]).unwrap(),
+            Vec::from_slice(&[9]).unwrap(),
+            Vec::new(),
+            RunMode::Repeat { count: 0 },
+            ErrorPolicy::Halt,
+        );
+
+        assert!(scheduler
+            .program()
+            .all(|(part, _, _)| part != ProgramPart::Run));
+        run_scheduler(&mut scheduler);
+
+        assert_eq!(scheduler.cycle_count(), 0);
+        assert_eq!(*runs.borrow(), [0, 9]);
+        assert_eq!(scheduler.phase(), SchedulerPhase::Idle);
+    }
+
+    #[test]
+    fn scheduler_group() {
+        let runs = Rc::new(RefCell::new(Vec::new()));
+        let mut scheduler: Scheduler<u8, MockRunner, Vec<u8, 6>, 0, 0, 0, ()> = Scheduler::new(
+            MockRunner::new(runs.clone()),
+            Vec::from_slice(&[1, GROUP_START, 2, 3, GROUP_END, 4]).unwrap(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            RunMode::Once,
+            ErrorPolicy::Halt,
+        );
+
+        scheduler.start();
+        while !runs.borrow().contains(&2) {
+            let _ = scheduler.poll();
+        }
+
+        // the group is started together
+        assert_eq!(*runs.borrow(), [1, 2, 3]);
+        assert_eq!(scheduler.command_index(), Some(4));
+        assert_eq!(scheduler.commands_count(), Some(6));
+
+        run_scheduler(&mut scheduler);
+
+        assert_eq!(*runs.borrow(), [1, 2, 3, 1, 2, 3, 4]);
+
+        // a flow command in a group is an error, rather than skipped, and isn't retried
+        let runs = Rc::new(RefCell::new(Vec::new()));
+        let mut scheduler: Scheduler<u8, MockRunner, Vec<u8, 5>, 0, 0, 0, ()> = Scheduler::new(
+            MockRunner::new(runs.clone()),
+            Vec::from_slice(&[GROUP_START, 2, CALL, 3, GROUP_END]).unwrap(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            RunMode::Once,
+            ErrorPolicy::Retry { count: 2 },
+        );
+
+        assert_eq!(
+            scheduler.flow_error(),
+            Some((2, CommandFlowError::FlowInGroup))
+        );
+        run_scheduler(&mut scheduler);
+
+        assert_eq!(*runs.borrow(), [2]);
+        assert_eq!(
+            scheduler.error(),
+            Some(&MockError::Flow(CommandFlowError::FlowInGroup))
+        );
+    }
+
+    #[test]
+    fn scheduler_flow() {
+        let runs = Rc::new(RefCell::new(Vec::new()));
+        let mut scheduler: Scheduler<u8, MockRunner, Vec<u8, 10>, 0, 0, 0, ()> = Scheduler::new(
+            MockRunner::new(runs.clone()),
+            Vec::from_slice(&[1, REPEAT, 2, CALL, REPEAT_END, 3, RETURN, LABEL, 4, RETURN])
+                .unwrap(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            RunMode::Once,
+            ErrorPolicy::Halt,
+        );
+
+        run_scheduler(&mut scheduler);
+
+        // the return after 3 ends the run commands, before the subroutine
+        assert_eq!(*runs.borrow(), [1, 2, 4, 2, 4, 3]);
+        assert_eq!(scheduler.phase(), SchedulerPhase::Idle);
+
+        let mut scheduler: Scheduler<u8, MockRunner, Vec<u8, 2>, 0, 0, 0, ()> = Scheduler::new(
+            MockRunner::new(runs.clone()),
+            Vec::from_slice(&[1, JUMP]).unwrap(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            RunMode::Once,
+            ErrorPolicy::Retry { count: 2 },
+        );
+
+        runs.borrow_mut().clear();
+        run_scheduler(&mut scheduler);
+
+        // a jump that can never work isn't retried
+        assert_eq!(*runs.borrow(), [1]);
+
+        assert_eq!(
+            scheduler.error(),
+            Some(&MockError::Flow(CommandFlowError::UnknownLabel {
+                label: "nowhere"
+            }))
+        );
+
+        // a group end outside of a group is an error, rather than skipped
+        let mut scheduler: Scheduler<u8, MockRunner, Vec<u8, 3>, 0, 0, 0, ()> = Scheduler::new(
+            MockRunner::new(runs.clone()),
+            Vec::from_slice(&[1, GROUP_END, 2]).unwrap(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            RunMode::Once,
+            ErrorPolicy::Halt,
+        );
+
+        assert_eq!(
+            scheduler.flow_error(),
+            Some((1, CommandFlowError::UnexpectedGroupEnd))
+        );
+        runs.borrow_mut().clear();
+        run_scheduler(&mut scheduler);
+
+        assert_eq!(*runs.borrow(), [1]);
+        assert_eq!(
+            scheduler.error(),
+            Some(&MockError::Flow(CommandFlowError::UnexpectedGroupEnd))
+        );
+    }
+
+    #[test]
+    fn scheduler_error_policy() {
+        let runs = Rc::new(RefCell::new(Vec::new()));
+        let mut scheduler: Scheduler<u8, MockRunner, Vec<u8, 3>, 0, 1, 1, ()> = Scheduler::new(
+            MockRunner::new(runs.clone()),
+            Vec::from_slice(&[1, FAIL, 2]).unwrap(),
+            Vec::new(),
+            Vec::from_slice(&[9]).unwrap(),
+            Vec::from_slice(&[8]).unwrap(),
+            RunMode::Once,
+            ErrorPolicy::Retry { count: 2 },
+        );
+
+        run_scheduler(&mut scheduler);
+
+        // retried twice, then the error commands instead of the stop commands
+        assert_eq!(*runs.borrow(), [1, FAIL, FAIL, FAIL, 8]);
+        assert_eq!(scheduler.error(), Some(&MockError::Fail));
+
+        // latched until reset
+        run_scheduler(&mut scheduler);
+        assert_eq!(runs.borrow().len(), 5);
+
+        scheduler.reset();
+        assert_eq!(scheduler.error(), None);
+        run_scheduler(&mut scheduler);
+        assert_eq!(runs.borrow().len(), 10);
+    }
+
+    #[test]
+    fn scheduler_step() {
+        let runs = Rc::new(RefCell::new(Vec::new()));
+        let mut scheduler: Scheduler<u8, MockRunner, Vec<u8, 3>, 0, 0, 0, ()> = Scheduler::new(
+            MockRunner::new(runs.clone()),
+            Vec::from_slice(&[1, 2, 3]).unwrap(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            RunMode::Once,
+            ErrorPolicy::Halt,
+        );
+        scheduler.set_step_mode(true);
+
+        run_scheduler(&mut scheduler);
+        assert_eq!(*runs.borrow(), [1]);
+        assert_eq!(scheduler.phase(), SchedulerPhase::Step);
+
+        scheduler.step();
+        for _ in 0..10 {
+            let _ = scheduler.poll();
+        }
+        assert_eq!(*runs.borrow(), [1, 2]);
+
+        // switch back mid-program
+        scheduler.set_step_mode(false);
+        for _ in 0..10 {
+            let _ = scheduler.poll();
+        }
+        assert_eq!(*runs.borrow(), [1, 2, 3]);
+        assert_eq!(scheduler.phase(), SchedulerPhase::Idle);
+    }
+
+    #[test]
+    fn scheduler_events() {
+        let runs = Rc::new(RefCell::new(Vec::new()));
+        let mut scheduler: Scheduler<u8, MockRunner, Vec<u8, 5>, 0, 0, 0, MockObserver> =
+            Scheduler::new(
+                MockRunner::new(runs.clone()),
+                Vec::from_slice(&[1, GROUP_START, 2, 3, GROUP_END]).unwrap(),
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                RunMode::Once,
+                ErrorPolicy::Halt,
+            )
+            .with_observer(MockObserver::default());
+
+        run_scheduler(&mut scheduler);
+
+        assert_eq!(scheduler.phase(), SchedulerPhase::Idle);
+        assert_eq!(
+            scheduler.observer().events,
+            [
+                MockEvent::Phase(SchedulerPhase::Start),
+                MockEvent::Phase(SchedulerPhase::Run),
+                MockEvent::Started(0),
+                MockEvent::Finished(0),
+                MockEvent::Started(2),
+                MockEvent::Started(3),
+                MockEvent::Finished(4),
+                MockEvent::Cycle(1),
+                MockEvent::Phase(SchedulerPhase::Stop),
+                MockEvent::Phase(SchedulerPhase::Idle),
+            ]
+        );
+    }
+
+    #[test]
+    fn scheduler_stream() {
+        let mut queue: CommandQueue<u8, 4> = CommandQueue::new();
+        let (mut producer, consumer) = queue.split();
+        let runs = Rc::new(RefCell::new(Vec::new()));
+        let mut scheduler: Scheduler<u8, MockRunner, CommandStream<u8, 4>, 0, 0, 0, ()> =
+            Scheduler::new(
+                MockRunner::new(runs.clone()),
+                CommandStream::new(consumer),
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                RunMode::Once,
+                ErrorPolicy::Halt,
+            );
+
+        producer.enqueue(StreamItem::Command(1)).unwrap();
+        producer.enqueue(StreamItem::Command(2)).unwrap();
+        producer.enqueue(StreamItem::Command(3)).unwrap();
+        assert!(producer.enqueue(StreamItem::End).is_err());
+
+        // runs what has arrived, then waits for more
+        run_scheduler(&mut scheduler);
+        assert_eq!(*runs.borrow(), [1, 2, 3]);
+        assert!(scheduler.poll().is_pending());
+
+        producer.enqueue(StreamItem::End).unwrap();
+        for _ in 0..10 {
+            let _ = scheduler.poll();
+        }
+        assert!(scheduler.poll().is_ready());
+    }
+
+    type EmptyCommand = Command<1_000, (), (), (), ()>;
+
+    #[test]
+    fn runner_wait() {
+        let now = Rc::new(AtomicU32::new(0));
+        let mut runner: Runner<
+            1_000,
+            1,
+            EmptyActuatorSet<LedAction<1_000>>,
+            EmptyActuatorSet<AxisAction>,
+            EmptyActuatorSet<SpindleAction>,
+            EmptySensorSet<SwitchUpdate>,
+            EmptyActuatorSetList,
+        > = Runner::new(
+            SubTimer::new(now.clone()),
+            EmptyActuatorSet::new(),
+            EmptyActuatorSet::new(),
+            EmptyActuatorSet::new(),
+            EmptySensorSet::new(),
+            EmptyActuatorSetList::new(),
+        );
+
+        runner.run(&RunnerAction::Run(Command::Wait {
+            duration: TimerDuration::<1_000>::from_ticks(10),
+        }));
+        now.store(5, Ordering::SeqCst);
+        assert!(runner.poll().is_pending());
+
+        // time spent paused doesn't count
+        runner.pause();
+        now.store(20, Ordering::SeqCst);
+        assert!(runner.poll().is_pending());
+        runner.resume();
+        now.store(24, Ordering::SeqCst);
+        assert!(runner.poll().is_pending());
+        now.store(25, Ordering::SeqCst);
+        assert!(runner.poll().is_ready());
+
+        runner.run(&RunnerAction::Run(Command::Timeout {
+            duration: TimerDuration::<1_000>::from_ticks(5),
+            command: TimedCommand::Wait {
+                duration: TimerDuration::<1_000>::from_ticks(10),
+            },
+        }));
+        now.store(30, Ordering::SeqCst);
+        assert!(matches!(
+            runner.poll(),
+            Poll::Ready(Err(RunnerError::Timeout(Command::Wait { .. })))
+        ));
+        assert!(runner.poll().is_pending());
+    }
+
+    #[test]
+    fn runner_timeout_error_policy() {
+        let now = Rc::new(AtomicU32::new(0));
+        let runner: Runner<
+            1_000,
+            1,
+            EmptyActuatorSet<LedAction<1_000>>,
+            EmptyActuatorSet<AxisAction>,
+            EmptyActuatorSet<SpindleAction>,
+            EmptySensorSet<SwitchUpdate>,
+            EmptyActuatorSetList,
+        > = Runner::new(
+            SubTimer::new(now.clone()),
+            EmptyActuatorSet::new(),
+            EmptyActuatorSet::new(),
+            EmptyActuatorSet::new(),
+            EmptySensorSet::new(),
+            EmptyActuatorSetList::new(),
+        );
+        let mut scheduler: Scheduler<EmptyCommand, _, Vec<EmptyCommand, 1>, 0, 0, 1, ()> =
+            Scheduler::new(
+                runner,
+                Vec::from_slice(&[Command::Timeout {
+                    duration: TimerDuration::<1_000>::from_ticks(5),
+                    command: TimedCommand::Wait {
+                        duration: TimerDuration::<1_000>::from_ticks(10),
+                    },
+                }])
+                .unwrap(),
+                Vec::new(),
+                Vec::new(),
+                Vec::from_slice(&[Command::Wait {
+                    duration: TimerDuration::<1_000>::from_ticks(1),
+                }])
+                .unwrap(),
+                RunMode::Once,
+                ErrorPolicy::Retry { count: 1 },
+            );
+        scheduler.start();
+        for _ in 0..10 {
+            let _ = scheduler.poll();
+        }
+        assert_eq!(scheduler.phase(), SchedulerPhase::Run);
+
+        // retried once
+        now.store(5, Ordering::SeqCst);
+        for _ in 0..10 {
+            let _ = scheduler.poll();
+        }
+        assert_eq!(scheduler.phase(), SchedulerPhase::Run);
+        assert!(scheduler.error().is_none());
+
+        // then the error commands
+        now.store(10, Ordering::SeqCst);
+        for _ in 0..10 {
+            let _ = scheduler.poll();
+        }
+        assert_eq!(scheduler.phase(), SchedulerPhase::Error);
+        now.store(11, Ordering::SeqCst);
+        for _ in 0..10 {
+            let _ = scheduler.poll();
+        }
+        assert_eq!(scheduler.phase(), SchedulerPhase::Faulted);
+        assert!(matches!(
+            scheduler.error(),
+            Some(RunnerError::Timeout(Command::Wait { .. }))
+        ));
+    }
+
+    #[test]
+    fn runner_overflow() {
+        let now = Rc::new(AtomicU32::new(0));
+        let mut runner: Runner<
+            1_000,
+            1,
+            EmptyActuatorSet<LedAction<1_000>>,
+            EmptyActuatorSet<AxisAction>,
+            EmptyActuatorSet<SpindleAction>,
+            EmptySensorSet<SwitchUpdate>,
+            EmptyActuatorSetList,
+        > = Runner::new(
+            SubTimer::new(now.clone()),
+            EmptyActuatorSet::new(),
+            EmptyActuatorSet::new(),
+            EmptyActuatorSet::new(),
+            EmptySensorSet::new(),
+            EmptyActuatorSetList::new(),
+        );
+
+        // the second command doesn't fit, so it fails instead of panicking
+        runner.run(&RunnerAction::Run(Command::Wait {
+            duration: TimerDuration::<1_000>::from_ticks(10),
+        }));
+        runner.run(&RunnerAction::Run(Command::Wait {
+            duration: TimerDuration::<1_000>::from_ticks(5),
+        }));
+        assert!(matches!(
+            runner.poll(),
+            Poll::Ready(Err(RunnerError::TooManyActiveCommands(
+                Command::Wait { .. }
+            )))
+        ));
+        now.store(10, Ordering::SeqCst);
+        assert!(runner.poll().is_pending());
+
+        // once there's room, a retry runs it
+        runner.run(&RunnerAction::Retry);
+        now.store(14, Ordering::SeqCst);
+        assert!(runner.poll().is_pending());
+        now.store(15, Ordering::SeqCst);
+        assert!(runner.poll().is_ready());
+
+        // no such led
+        runner.run(&RunnerAction::Run(Command::Led(
+            (),
+            LedAction::Set { is_on: true },
+        )));
+        assert!(matches!(
+            runner.poll(),
+            Poll::Ready(Err(RunnerError::Led((), ())))
+        ));
+    }
+
+    #[test]
+    fn runner_wait_switch() {
+        let now = Rc::new(AtomicU32::new(0));
+        let update = Rc::new(RefCell::new(Some(SwitchUpdate {
+            status: SwitchStatus::Off,
+        })));
+        let mut runner: Runner<
+            1_000,
+            1,
+            EmptyActuatorSet<LedAction<1_000>>,
+            EmptyActuatorSet<AxisAction>,
+            EmptyActuatorSet<SpindleAction>,
+            SwitchSet<MockSwitch>,
+            EmptyActuatorSetList,
+        > = Runner::new(
+            SubTimer::new(now.clone()),
+            EmptyActuatorSet::new(),
+            EmptyActuatorSet::new(),
+            EmptyActuatorSet::new(),
+            SwitchSet::new(MockSwitch {
+                update: update.clone(),
+            }),
+            EmptyActuatorSetList::new(),
+        );
+
+        runner.run(&RunnerAction::Run(Command::WaitSwitch {
+            switch: SwitchId::PartPresent,
+            status: SwitchStatus::On,
+            timeout: None,
+        }));
+        assert!(runner.poll().is_pending());
+        *update.borrow_mut() = Some(SwitchUpdate {
+            status: SwitchStatus::On,
+        });
+        assert!(runner.poll().is_ready());
+
+        // the switch is already on, no update is needed
+        runner.run(&RunnerAction::Run(Command::WaitSwitch {
+            switch: SwitchId::PartPresent,
+            status: SwitchStatus::On,
+            timeout: None,
+        }));
+        assert!(matches!(runner.poll(), Poll::Ready(Ok(()))));
+
+        runner.run(&RunnerAction::Run(Command::WaitSwitch {
+            switch: SwitchId::PartPresent,
+            status: SwitchStatus::Off,
+            timeout: Some(TimerDuration::<1_000>::from_ticks(10)),
+        }));
+        assert!(runner.poll().is_pending());
+        now.store(10, Ordering::SeqCst);
+        assert!(matches!(
+            runner.poll(),
+            Poll::Ready(Err(RunnerError::Timeout(Command::WaitSwitch {
+                switch: SwitchId::PartPresent,
+                ..
+            })))
+        ));
+    }
+
+    #[test]
+    fn runner_retry_axis() {
+        let now = Rc::new(AtomicU32::new(0));
+        let limit_min = Rc::new(RefCell::new(switch_update(SwitchStatus::Off)));
+        let limit_max = Rc::new(RefCell::new(switch_update(SwitchStatus::Off)));
+        let mut runner: Runner<
+            1_000,
+            1,
+            EmptyActuatorSet<LedAction<1_000>>,
+            _,
+            EmptyActuatorSet<SpindleAction>,
+            EmptySensorSet<SwitchUpdate>,
+            EmptyActuatorSetList,
+        > = Runner::new(
+            SubTimer::new(now.clone()),
+            EmptyActuatorSet::new(),
+            AxisSet::new(mock_axis(&limit_min, &limit_max)),
+            EmptyActuatorSet::new(),
+            EmptySensorSet::new(),
+            EmptyActuatorSetList::new(),
+        );
+
+        // timed out part way through the move
+        runner.run(&RunnerAction::Run(Command::Timeout {
+            duration: TimerDuration::<1_000>::from_ticks(5),
+            command: TimedCommand::Axis(
+                AxisId::X,
+                AxisAction::MoveRelative {
+                    max_velocity_in_millimeters_per_sec: 10_f64,
+                    distance_in_millimeters: 5_f64,
+                },
+            ),
+        }));
+        for _ in 0..40 {
+            assert!(runner.poll().is_pending());
+        }
+        now.store(5, Ordering::SeqCst);
+        assert!(matches!(
+            runner.poll(),
+            Poll::Ready(Err(RunnerError::Timeout(Command::Axis(AxisId::X, _))))
+        ));
+
+        // the retry stops the axis, then goes on to where the move was headed, not further
+        runner.run(&RunnerAction::Retry);
+        let mut result = Poll::Pending;
+        for _ in 0..100_000 {
+            result = runner.poll();
+            if result.is_ready() {
+                break;
+            }
+        }
+        assert!(matches!(result, Poll::Ready(Ok(()))));
+        let axis: AxisStatus = runner.axes().status(&AxisId::X);
+        assert_eq!(axis.logical_position_in_millimeters, 5_f64);
+        assert_eq!(axis.real_position_in_millimeters, 5_f64);
+    }
+
+    #[test]
+    fn robot_simulate() {
+        let simulation = Simulation::<1_000>::new(
+            TimerDuration::<1_000>::from_ticks(1),
+            TimerDurationU64::<1_000>::secs(60),
+        );
+        let x_axis = SimulatedAxis::new(
+            simulation.sub(),
+            100_f64,
+            10_f64,
+            0_f64,
+            50_f64,
+            AxisLimitSide::Min,
+        );
+        let mut robot = RobotBuilder::new(simulation.sub())
+            .with_axes(AxisSet::new(x_axis))
+            .build()
+            .with_run_commands(&[
+                Command::Axis(
+                    AxisId::X,
+                    AxisAction::MoveAbsolute {
+                        max_velocity_in_millimeters_per_sec: 10_f64,
+                        position_in_millimeters: 40_f64,
+                    },
+                ),
+                Command::Axis(
+                    AxisId::X,
+                    AxisAction::MoveRelative {
+                        max_velocity_in_millimeters_per_sec: 10_f64,
+                        distance_in_millimeters: 20_f64,
+                    },
+                ),
+            ])
+            .with_start_commands(&[Command::Axis(
+                AxisId::X,
+                AxisAction::Home {
+                    max_velocity_in_millimeters_per_sec: 10_f64,
+                    back_off_distance_in_millimeters: 5_f64,
+                },
+            )])
+            .build::<1>();
+
+        let report = robot.simulate(&simulation);
+        assert!(report.is_complete);
+        assert_eq!(report.limit_violations_count(), 1);
+        assert!(robot.error().is_none());
+        // 70mm at no more than 10mm/s
+        assert!(report.duration >= TimerDurationU64::<1_000>::secs(7));
+        assert!(report.duration < TimerDurationU64::<1_000>::secs(60));
+
+        // every axis is in the report
+        assert_eq!(report.axes.len(), 1);
+        let (_, x_axis) = report.axes[0];
+        assert!(x_axis.is_homed);
+        assert_eq!(x_axis.real_position_in_millimeters, 60_f64);
+        assert_eq!(x_axis.min_position_in_millimeters, 0_f64);
+        assert_eq!(x_axis.max_position_in_millimeters, 60_f64);
+        assert_eq!(x_axis.travel_in_millimeters, 70_f64);
+
+        // rejected like on a real axis, before homing and past the soft limits
+        let x_axis = |simulation: &Simulation<1_000>| {
+            SimulatedAxis::new(
+                simulation.sub(),
+                100_f64,
+                10_f64,
+                0_f64,
+                50_f64,
+                AxisLimitSide::Min,
+            )
+            .with_soft_limits(0_f64, 50_f64)
+        };
+        let move_to = |position_in_millimeters| {
+            Command::Axis(
+                AxisId::X,
+                AxisAction::MoveAbsolute {
+                    max_velocity_in_millimeters_per_sec: 10_f64,
+                    position_in_millimeters,
+                },
+            )
+        };
+        let home = Command::Axis(
+            AxisId::X,
+            AxisAction::Home {
+                max_velocity_in_millimeters_per_sec: 10_f64,
+                back_off_distance_in_millimeters: 5_f64,
+            },
+        );
+        for (commands, error) in [
+            ([move_to(10_f64), home], SimulatedAxisError::NotHomed),
+            (
+                [home, move_to(60_f64)],
+                SimulatedAxisError::SoftLimit {
+                    requested: 60_f64,
+                    min: 0_f64,
+                    max: 50_f64,
+                },
+            ),
+        ] {
+            let mut robot = RobotBuilder::new(simulation.sub())
+                .with_axes(AxisSet::new(x_axis(&simulation)))
+                .build()
+                .with_run_commands(&commands)
+                .build::<1>();
+
+            let report = robot.simulate(&simulation);
+            assert!(!report.is_complete);
+            assert!(matches!(
+                (robot.error(), error),
+                (
+                    Some(RunnerError::Axis(
+                        AxisId::X,
+                        AxisSetError::XAxis(AxisError::NotHomed)
+                    )),
+                    AxisError::NotHomed,
+                ) | (
+                    Some(RunnerError::Axis(
+                        AxisId::X,
+                        AxisSetError::XAxis(AxisError::SoftLimit { .. })
+                    )),
+                    AxisError::SoftLimit { .. },
+                )
+            ));
+        }
+    }
+
+    #[test]
+    fn robot_validate() {
+        let now = Rc::new(AtomicU32::new(0));
+        let x_axis = SimulatedAxis::<1_000>::new(
+            SubTimer::new(now.clone()),
+            100_f64,
+            10_f64,
+            0_f64,
+            50_f64,
+            AxisLimitSide::Min,
+        );
+        let move_to = |position_in_millimeters| -> Command<1_000, (), AxisId, (), ()> {
+            Command::Axis(
+                AxisId::X,
+                AxisAction::MoveAbsolute {
+                    max_velocity_in_millimeters_per_sec: 10_f64,
+                    position_in_millimeters,
+                },
+            )
+        };
+        let mut robot = RobotBuilder::new(SubTimer::new(now.clone()))
+            .with_axes(AxisSet::new(x_axis))
+            .build()
+            .with_run_commands(&[
+                move_to(40_f64),
+                Command::Axis(
+                    AxisId::X,
+                    AxisAction::MoveRelative {
+                        max_velocity_in_millimeters_per_sec: 10_f64,
+                        distance_in_millimeters: 20_f64,
+                    },
+                ),
+            ])
+            .with_stop_commands(&[move_to(0_f64)])
+            .build::<1>();
+        let settings = ValidationSettings {
+            axes: &[(
+                AxisId::X,
+                AxisLimits {
+                    min_position_in_millimeters: 0_f64,
+                    max_position_in_millimeters: 50_f64,
+                    max_velocity_in_millimeters_per_sec: 20_f64,
+                },
+            )],
+            spindles: &[],
+        };
+
+        // no home before the first absolute move
+        assert_eq!(
+            robot.validate(&settings),
+            Err(ProgramError {
+                part: ProgramPart::Run,
+                command_index: 0,
+                error: ValidationError::NotHomed { axis: AxisId::X },
+            })
+        );
+
+        let mut robot = RobotBuilder::new(SubTimer::<1_000>::new(now.clone()))
+            .with_axes(AxisSet::new(SimulatedAxis::<1_000>::new(
+                SubTimer::new(now.clone()),
+                100_f64,
+                10_f64,
+                0_f64,
+                50_f64,
+                AxisLimitSide::Min,
+            )))
+            .build()
+            .with_run_commands(&[Command::GroupEnd])
+            .build::<1>();
+        assert_eq!(
+            robot.validate(&settings),
+            Err(ProgramError {
+                part: ProgramPart::Run,
+                command_index: 0,
+                error: ValidationError::Flow(CommandFlowError::UnexpectedGroupEnd),
+            })
+        );
+
+        let mut validator = AxisValidator::new(AxisId::X, Some(settings.axes[0].1));
+        let home = |max_velocity_in_millimeters_per_sec| -> Command<1_000, (), AxisId, (), ()> {
+            Command::Axis(
+                AxisId::X,
+                AxisAction::Home {
+                    max_velocity_in_millimeters_per_sec,
+                    back_off_distance_in_millimeters: 5_f64,
+                },
+            )
+        };
+        assert!(matches!(
+            validator.check(&home(30_f64), |_| None),
+            Err(ValidationError::<AxisId, ()>::VelocityOutOfRange { .. })
+        ));
+        assert_eq!(validator.check(&home(10_f64), |_| None), Ok(()));
+        assert_eq!(validator.check(&move_to(40_f64), |_| None), Ok(()));
+        assert!(matches!(
+            validator.check(&move_to(60_f64), |_| None),
+            Err(ValidationError::<AxisId, ()>::PositionOutOfRange { .. })
+        ));
+
+        // an arc between two ends in range can still bulge out of range
+        let limits = AxisLimits {
+            min_position_in_millimeters: 0_f64,
+            max_position_in_millimeters: 50_f64,
+            max_velocity_in_millimeters_per_sec: 20_f64,
+        };
+        let settings = ValidationSettings {
+            axes: &[(XyAxisId::X, limits), (XyAxisId::Y, limits)],
+            spindles: &[],
+        };
+        let home = |axis| {
+            Command::Axis(
+                axis,
+                AxisAction::Home {
+                    max_velocity_in_millimeters_per_sec: 10_f64,
+                    back_off_distance_in_millimeters: 5_f64,
+                },
+            )
+        };
+        // a half circle from (10, 5) to (30, 5), counter clockwise dips to y = -5
+        let half_circle = |direction| {
+            Command::Motion(MotionAction::ArcMoveRelative {
+                max_velocity_in_millimeters_per_sec: 10_f64,
+                arc: MotionArc {
+                    plane: (XyAxisId::X, XyAxisId::Y),
+                    direction,
+                    end_in_millimeters: (Some(20_f64), None),
+                    center: ArcCenter::Offset {
+                        first_in_millimeters: 10_f64,
+                        second_in_millimeters: 0_f64,
+                    },
+                    helix: None,
+                    tolerance_in_millimeters: 0.1_f64,
+                },
+            })
+        };
+        let validate = |direction| {
+            let axis = || {
+                SimulatedAxis::<1_000>::new(
+                    SubTimer::new(now.clone()),
+                    100_f64,
+                    10_f64,
+                    0_f64,
+                    50_f64,
+                    AxisLimitSide::Min,
+                )
+            };
+            RobotBuilder::new(SubTimer::<1_000>::new(now.clone()))
+                .with_axes(XyAxisSet::new(axis(), axis()))
+                .build()
+                .with_start_commands(&[home(XyAxisId::X), home(XyAxisId::Y)])
+                .with_run_commands(&[
+                    Command::Motion(MotionAction::LinearMoveAbsolute {
+                        max_velocity_in_millimeters_per_sec: 10_f64,
+                        positions_in_millimeters: [
+                            Some((XyAxisId::X, 10_f64)),
+                            Some((XyAxisId::Y, 5_f64)),
+                            None,
+                        ],
+                    }),
+                    half_circle(direction),
+                ])
+                .build::<1>()
+                .validate(&settings)
+        };
+        assert_eq!(validate(ArcDirection::Clockwise), Ok(()));
+        assert!(matches!(
+            validate(ArcDirection::CounterClockwise),
+            Err(ProgramError {
+                part: ProgramPart::Run,
+                command_index: 1,
+                error: ValidationError::PositionOutOfRange {
+                    axis: XyAxisId::Y,
+                    position_in_millimeters,
+                    ..
+                },
+            }) if abs(position_in_millimeters + 5_f64) < 1e-9
+        ));
+    }
+
+    #[test]
+    fn robot_extras() {
+        let now = Rc::new(AtomicU32::new(0));
+        let is_on = Rc::new(RefCell::new(false));
+        let mut robot = RobotBuilder::new(SubTimer::<1_000>::new(now.clone()))
+            .with_extras(ExtraSets::new(RelaySet::new(MockRelay {
+                is_on: is_on.clone(),
+            })))
+            .build()
+            .with_run_commands(&[Command::Extra(ExtraCommand::Relay(RelayId::Main, true))])
+            .build::<1>();
+
+        robot.start();
+        for _ in 0..10 {
+            let _ = robot.poll();
+        }
+        assert_eq!(robot.phase(), SchedulerPhase::Idle);
+        assert!(*is_on.borrow());
+
+        robot.emergency_stop();
+        assert!(!*is_on.borrow());
+    }
+
+    #[test]
+    fn robot_axis_limit() {
+        let now = Rc::new(AtomicU32::new(0));
+        let limit_min = Rc::new(RefCell::new(switch_update(SwitchStatus::Off)));
+        let limit_max = Rc::new(RefCell::new(switch_update(SwitchStatus::Off)));
+        let move_by = |distance_in_millimeters| {
+            Command::Axis(
+                AxisId::X,
+                AxisAction::MoveRelative {
+                    max_velocity_in_millimeters_per_sec: 10_f64,
+                    distance_in_millimeters,
+                },
+            )
+        };
+        let mut robot = RobotBuilder::new(SubTimer::<1_000>::new(now.clone()))
+            .with_axes(AxisSet::new(mock_axis(&limit_min, &limit_max)))
+            .build()
+            .with_run_commands(&[move_by(50_f64)])
+            .with_error_commands(&[move_by(-2_f64)])
+            .with_error_policy(ErrorPolicy::RunErrorCommands)
+            .build::<1>();
+
+        // the axis hits its max limit switch part way
+        robot.start();
+        for _ in 0..1_000 {
+            let _ = robot.poll();
+        }
+        assert_eq!(robot.phase(), SchedulerPhase::Run);
+        limit_max.replace(switch_update(SwitchStatus::On));
+        let _ = robot.poll();
+        let limit_position = robot.status::<1>().axes[0].1.real_position_in_millimeters;
+
+        // the error commands back it off the switch, then the robot latches
+        for _ in 0..100_000 {
+            if robot.phase() == SchedulerPhase::Faulted {
+                break;
+            }
+            let _ = robot.poll();
+        }
+        assert_eq!(robot.phase(), SchedulerPhase::Faulted);
+        assert!(robot.error().is_some());
+        assert!(
+            abs(robot.status::<1>().axes[0].1.real_position_in_millimeters
+                - (limit_position - 2_f64))
+                < 0.2_f64
+        );
+
+        // once reset, the robot can be started again
+        limit_max.replace(switch_update(SwitchStatus::Off));
+        robot.reset();
+        robot.start();
+        for _ in 0..100_000 {
+            let _ = robot.poll();
+            if robot.phase() == SchedulerPhase::Idle {
+                break;
+            }
+        }
+        assert_eq!(robot.phase(), SchedulerPhase::Idle);
+        assert!(robot.error().is_none());
+        assert!(
+            abs(robot.status::<1>().axes[0].1.real_position_in_millimeters
+                - (limit_position + 48_f64))
+                < 0.2_f64
+        );
+    }
+
+    #[test]
+    fn robot_stop_during_move() {
+        let now = Rc::new(AtomicU32::new(0));
+        let limit_min = Rc::new(RefCell::new(switch_update(SwitchStatus::On)));
+        let limit_max = Rc::new(RefCell::new(switch_update(SwitchStatus::Off)));
+        let move_to = |position_in_millimeters| {
+            Command::Axis(
+                AxisId::X,
+                AxisAction::MoveAbsolute {
+                    max_velocity_in_millimeters_per_sec: 10_f64,
+                    position_in_millimeters,
+                },
+            )
+        };
+        let mut robot = RobotBuilder::new(SubTimer::<1_000>::new(now.clone()))
+            .with_axes(AxisSet::new(mock_axis(&limit_min, &limit_max)))
+            .build()
+            .with_start_commands(&[Command::Axis(
+                AxisId::X,
+                AxisAction::Home {
+                    max_velocity_in_millimeters_per_sec: 10_f64,
+                    back_off_distance_in_millimeters: 1_f64,
+                },
+            )])
+            .with_run_commands(&[move_to(50_f64)])
+            .with_stop_commands(&[move_to(0_f64)])
+            .build::<1>();
+
+        // homed on the min limit switch, which is then left behind
+        robot.start();
+        for _ in 0..100_000 {
+            let _ = robot.poll();
+            if robot.status::<1>().axes[0].1.is_homed {
+                break;
+            }
+        }
+        limit_min.replace(switch_update(SwitchStatus::Off));
+
+        // stopped part way through the move
+        for _ in 0..1_000 {
+            let _ = robot.poll();
+        }
+        assert_eq!(robot.phase(), SchedulerPhase::Run);
+        robot.stop();
+
+        // the axis is still homed, so the stop commands move it back
+        for _ in 0..100_000 {
+            let _ = robot.poll();
+            if robot.phase() == SchedulerPhase::Idle {
+                break;
+            }
+        }
+        assert_eq!(robot.phase(), SchedulerPhase::Idle);
+        let axis = robot.status::<1>().axes[0].1;
+        assert!(axis.is_homed);
+        assert_eq!(axis.real_position_in_millimeters, 0_f64);
+    }
+
+    #[test]
+    fn robot_status() {
+        let now = Rc::new(AtomicU32::new(0));
+        let limit_min = Rc::new(RefCell::new(switch_update(SwitchStatus::On)));
+        let limit_max = Rc::new(RefCell::new(switch_update(SwitchStatus::Off)));
+        let move_by = |distance_in_millimeters| {
+            Command::Axis(
+                AxisId::X,
+                AxisAction::MoveRelative {
+                    max_velocity_in_millimeters_per_sec: 10_f64,
+                    distance_in_millimeters,
+                },
+            )
+        };
+        let mut robot = RobotBuilder::new(SubTimer::<1_000>::new(now.clone()))
+            .with_axes(AxisSet::new(mock_axis(&limit_min, &limit_max)))
+            .build()
+            .with_start_commands(&[Command::Axis(
+                AxisId::X,
+                AxisAction::Home {
+                    max_velocity_in_millimeters_per_sec: 10_f64,
+                    back_off_distance_in_millimeters: 1_f64,
+                },
+            )])
+            .with_run_commands(&[move_by(10_f64), move_by(20_f64)])
+            .build::<1>();
+
+        let status = robot.status::<1>();
+        assert_eq!(status.phase, SchedulerPhase::Idle);
+        assert_eq!(status.command_index, None);
+        assert_eq!(status.commands_count, Some(2));
+        assert!(status.active_command.is_none());
+        assert_eq!(status.axes[0].0, AxisId::X);
+        assert!(!status.axes[0].1.is_homed);
+
+        // homing isn't one of the run commands
+        robot.start();
+        for _ in 0..10 {
+            let _ = robot.poll();
+        }
+        let status = robot.status::<1>();
+        assert_eq!(status.phase, SchedulerPhase::Start);
+        assert_eq!(status.command_index, None);
+        assert!(matches!(
+            status.active_command,
+            Some(Command::Axis(AxisId::X, AxisAction::Home { .. }))
+        ));
+
+        // homed, then part way through the first move
+        for _ in 0..100_000 {
+            let _ = robot.poll();
+            if robot.phase() == SchedulerPhase::Run {
+                break;
+            }
+        }
+        limit_min.replace(switch_update(SwitchStatus::Off));
+        for _ in 0..50 {
+            let _ = robot.poll();
+        }
+        let status = robot.status::<1>();
+        assert_eq!(status.phase, SchedulerPhase::Run);
+        assert_eq!(status.command_index, Some(0));
+        assert!(matches!(
+            status.active_command,
+            Some(Command::Axis(
+                AxisId::X,
+                AxisAction::MoveRelative {
+                    distance_in_millimeters,
+                    ..
+                }
+            )) if distance_in_millimeters == 10_f64
+        ));
+        let axis = status.axes[0].1;
+        assert!(axis.is_homed);
+        assert!(axis.real_position_in_millimeters > 0_f64);
+        assert!(axis.real_position_in_millimeters < 10_f64);
+
+        // on to the second move
+        for _ in 0..100_000 {
+            let _ = robot.poll();
+            if robot.status::<1>().command_index == Some(1) {
+                break;
+            }
+        }
+        // started on the next poll
+        let _ = robot.poll();
+        let status = robot.status::<1>();
+        assert!(matches!(
+            status.active_command,
+            Some(Command::Axis(
+                AxisId::X,
+                AxisAction::MoveRelative {
+                    distance_in_millimeters,
+                    ..
+                }
+            )) if distance_in_millimeters == 20_f64
+        ));
+        assert!(status.axes[0].1.real_position_in_millimeters >= 10_f64);
+
+        for _ in 0..100_000 {
+            let _ = robot.poll();
+            if robot.phase() == SchedulerPhase::Idle {
+                break;
+            }
+        }
+        let status = robot.status::<1>();
+        assert_eq!(status.phase, SchedulerPhase::Idle);
+        assert_eq!(status.command_index, None);
+        assert!(status.active_command.is_none());
+        assert!(status.axes[0].1.is_homed);
+        assert_eq!(status.axes[0].1.real_position_in_millimeters, 30_f64);
+    }
+
+    #[test]
+    fn robot_pause_resume() {
+        let now = Rc::new(AtomicU32::new(0));
+        let limits: [Rc<RefCell<Option<SwitchUpdate>>>; 4] =
+            core::array::from_fn(|_| Rc::new(RefCell::new(switch_update(SwitchStatus::Off))));
+        let move_by = |id, distance_in_millimeters| {
+            Command::Axis(
+                id,
+                AxisAction::MoveRelative {
+                    max_velocity_in_millimeters_per_sec: 100_f64,
+                    distance_in_millimeters,
+                },
+            )
+        };
+        let positions = |status: RobotStatus<_, XyAxisId, 2>| {
+            [
+                status.axes[0].1.real_position_in_millimeters,
+                status.axes[1].1.real_position_in_millimeters,
+            ]
+        };
+
+        // paused part way through a single command, from `RunLoop`
+        let mut robot = RobotBuilder::new(SubTimer::<1_000>::new(now.clone()))
+            .with_axes(XyAxisSet::new(
+                mock_axis(&limits[0], &limits[1]),
+                mock_axis(&limits[2], &limits[3]),
+            ))
+            .build()
+            .with_run_commands(&[move_by(XyAxisId::X, 50_f64)])
+            .build::<2>();
+        robot.start();
+        for _ in 0..300 {
+            let _ = robot.poll();
+        }
+        robot.pause();
+        assert_eq!(robot.phase(), SchedulerPhase::Paused);
+
+        // slows down to a stop short of the target
+        let paused_at = positions(robot.status())[0];
+        for _ in 0..10_000 {
+            let _ = robot.poll();
+        }
+        let stopped_at = positions(robot.status())[0];
+        assert!(stopped_at > paused_at + 1_f64 && stopped_at < 50_f64);
+        for _ in 0..10_000 {
+            let _ = robot.poll();
+        }
+        assert_eq!(positions(robot.status())[0], stopped_at);
+
+        // then carries on to the original target
+        robot.resume();
+        assert_eq!(robot.phase(), SchedulerPhase::Run);
+        for _ in 0..100_000 {
+            let _ = robot.poll();
+            if robot.phase() == SchedulerPhase::Idle {
+                break;
+            }
+        }
+        assert_eq!(robot.phase(), SchedulerPhase::Idle);
+        assert_eq!(positions(robot.status())[0], 50_f64);
+
+        // paused while a group waits for the rest of its commands, from `RunGroup`
+        for limit in limits.iter() {
+            limit.replace(switch_update(SwitchStatus::Off));
+        }
+        let mut queue = CommandQueue::<_, 8>::new();
+        let (mut producer, consumer) = queue.split();
+        let mut robot = RobotBuilder::new(SubTimer::<1_000>::new(now.clone()))
+            .with_axes(XyAxisSet::new(
+                mock_axis(&limits[0], &limits[1]),
+                mock_axis(&limits[2], &limits[3]),
+            ))
+            .build()
+            .with_run_stream(CommandStream::new(consumer))
+            .build::<2>();
+        for command in [
+            Command::GroupStart,
+            move_by(XyAxisId::X, 30_f64),
+            move_by(XyAxisId::Y, 40_f64),
+        ] {
+            producer.enqueue(StreamItem::Command(command)).unwrap();
+        }
+        robot.start();
+        for _ in 0..300 {
+            let _ = robot.poll();
+        }
+        robot.pause();
+        assert_eq!(robot.phase(), SchedulerPhase::Paused);
+
+        // both axes slow down to a stop
+        let paused_at = positions(robot.status());
+        for _ in 0..10_000 {
+            let _ = robot.poll();
+        }
+        let stopped_at = positions(robot.status());
+        assert!(stopped_at[0] > paused_at[0] + 1_f64 && stopped_at[0] < 30_f64);
+        assert!(stopped_at[1] > paused_at[1] + 1_f64 && stopped_at[1] < 40_f64);
+        for _ in 0..10_000 {
+            let _ = robot.poll();
+        }
+        assert_eq!(positions(robot.status()), stopped_at);
+
+        // and carry on once resumed and the group is closed
+        robot.resume();
+        assert_eq!(robot.phase(), SchedulerPhase::Run);
+        producer
+            .enqueue(StreamItem::Command(Command::GroupEnd))
+            .unwrap();
+        producer.enqueue(StreamItem::End).unwrap();
+        for _ in 0..100_000 {
+            let _ = robot.poll();
+            if robot.phase() == SchedulerPhase::Idle {
+                break;
+            }
+        }
+        assert_eq!(robot.phase(), SchedulerPhase::Idle);
+        assert_eq!(positions(robot.status()), [30_f64, 40_f64]);
+    }
+
+    #[test]
+    fn robot_emergency_stop() {
+        let now = Rc::new(AtomicU32::new(0));
+        let limit_min = Rc::new(RefCell::new(switch_update(SwitchStatus::On)));
+        let limit_max = Rc::new(RefCell::new(switch_update(SwitchStatus::Off)));
+        let move_to = |position_in_millimeters| {
+            Command::Axis(
+                AxisId::X,
+                AxisAction::MoveAbsolute {
+                    max_velocity_in_millimeters_per_sec: 10_f64,
+                    position_in_millimeters,
+                },
+            )
+        };
+        let mut robot = RobotBuilder::new(SubTimer::<1_000>::new(now.clone()))
+            .with_axes(AxisSet::new(mock_axis(&limit_min, &limit_max)))
+            .build()
+            .with_start_commands(&[Command::Axis(
+                AxisId::X,
+                AxisAction::Home {
+                    max_velocity_in_millimeters_per_sec: 10_f64,
+                    back_off_distance_in_millimeters: 1_f64,
+                },
+            )])
+            .with_run_commands(&[move_to(50_f64)])
+            .with_stop_commands(&[move_to(0_f64)])
+            .build::<1>();
+
+        // homed, then stopped part way through the move
+        robot.start();
+        for _ in 0..100_000 {
+            let _ = robot.poll();
+            if robot.status::<1>().axes[0].1.is_homed {
+                break;
+            }
+        }
+        limit_min.replace(switch_update(SwitchStatus::Off));
+        for _ in 0..1_000 {
+            let _ = robot.poll();
+        }
+        assert_eq!(robot.phase(), SchedulerPhase::Run);
+        robot.emergency_stop();
+
+        // the stop commands are skipped, and the axis has to be homed again
+        let stopped_at = robot.status::<1>().axes[0].1.real_position_in_millimeters;
+        assert!(stopped_at > 0_f64);
+        for _ in 0..10_000 {
+            let _ = robot.poll();
+        }
+        assert_eq!(robot.phase(), SchedulerPhase::Estop);
+        let axis = robot.status::<1>().axes[0].1;
+        assert!(!axis.is_homed);
+        assert_eq!(axis.real_position_in_millimeters, stopped_at);
+
+        // latched until reset
+        robot.start();
+        let _ = robot.poll();
+        assert_eq!(robot.phase(), SchedulerPhase::Estop);
+        robot.toggle();
+        let _ = robot.poll();
+        assert_eq!(robot.phase(), SchedulerPhase::Estop);
+        assert_eq!(
+            robot.status::<1>().axes[0].1.real_position_in_millimeters,
+            stopped_at
+        );
+
+        robot.reset();
+        let _ = robot.poll();
+        assert_eq!(robot.phase(), SchedulerPhase::Idle);
+        robot.start();
+        let _ = robot.poll();
+        assert_eq!(robot.phase(), SchedulerPhase::Start);
+    }
+
+    #[test]
+    fn motion_unhomed() {
+        let now = Rc::new(AtomicU32::new(0));
+        let limits: [Rc<RefCell<Option<SwitchUpdate>>>; 4] =
+            core::array::from_fn(|_| Rc::new(RefCell::new(switch_update(SwitchStatus::Off))));
+        let relative_line = Command::Motion(MotionAction::LinearMoveRelative {
+            max_velocity_in_millimeters_per_sec: 10_f64,
+            distances_in_millimeters: [
+                Some((XyAxisId::X, 10_f64)),
+                Some((XyAxisId::Y, 10_f64)),
+                None,
+            ],
+        });
+        // a quarter circle back to x, ending 10mm further along y
+        let relative_arc = Command::Motion(MotionAction::ArcMoveRelative {
+            max_velocity_in_millimeters_per_sec: 10_f64,
+            arc: MotionArc {
+                plane: (XyAxisId::X, XyAxisId::Y),
+                direction: ArcDirection::CounterClockwise,
+                end_in_millimeters: (Some(-10_f64), Some(10_f64)),
+                center: ArcCenter::Offset {
+                    first_in_millimeters: -10_f64,
+                    second_in_millimeters: 0_f64,
+                },
+                helix: None,
+                tolerance_in_millimeters: 0.1_f64,
+            },
+        });
+        let absolute_line = Command::Motion(MotionAction::LinearMoveAbsolute {
+            max_velocity_in_millimeters_per_sec: 10_f64,
+            positions_in_millimeters: [
+                Some((XyAxisId::X, 0_f64)),
+                Some((XyAxisId::Y, 0_f64)),
+                None,
+            ],
+        });
+        let mut robot = RobotBuilder::new(SubTimer::<1_000>::new(now.clone()))
+            .with_axes(XyAxisSet::new(
+                mock_axis(&limits[0], &limits[1]),
+                mock_axis(&limits[2], &limits[3]),
+            ))
+            .build()
+            .with_run_commands(&[relative_line, relative_arc, absolute_line])
+            .build::<2>();
+
+        robot.start();
+        for _ in 0..1_000_000 {
+            let _ = robot.poll();
+            if robot.phase() == SchedulerPhase::Faulted {
+                break;
+            }
+        }
+
+        // only the absolute line needs the axes to be homed
+        assert_eq!(robot.phase(), SchedulerPhase::Faulted);
+        assert!(matches!(
+            robot.error(),
+            Some(RunnerError::Axis(
+                XyAxisId::X,
+                XyAxisSetError::XAxis(AxisError::NotHomed)
+            ))
+        ));
+        let axes = robot.status::<2>().axes;
+        assert!(abs(axes[0].1.real_position_in_millimeters - 0_f64) < 0.2_f64);
+        assert!(abs(axes[1].1.real_position_in_millimeters - 20_f64) < 0.2_f64);
+    }
+
+    #[test]
+    fn robot_programs() {
+        let simulation = Simulation::<1_000>::new(
+            TimerDuration::<1_000>::from_ticks(1),
+            TimerDurationU64::<1_000>::secs(60),
+        );
+        let x_axis = SimulatedAxis::new(
+            simulation.sub(),
+            100_f64,
+            10_f64,
+            0_f64,
+            50_f64,
+            AxisLimitSide::Min,
+        );
+        let home = Command::Axis(
+            AxisId::X,
+            AxisAction::Home {
+                max_velocity_in_millimeters_per_sec: 10_f64,
+                back_off_distance_in_millimeters: 5_f64,
+            },
+        );
+        let move_to = |position_in_millimeters| {
+            Command::Axis(
+                AxisId::X,
+                AxisAction::MoveAbsolute {
+                    max_velocity_in_millimeters_per_sec: 10_f64,
+                    position_in_millimeters,
+                },
+            )
+        };
+        let mut robot = RobotBuilder::new(simulation.sub())
+            .with_axes(AxisSet::new(x_axis))
+            .build()
+            .with_programs::<2, 2, 1, 0>(&[
+                Program::new("short", &[home], &[move_to(10_f64)], &[]).unwrap(),
+                Program::new("long", &[home], &[move_to(20_f64), move_to(30_f64)], &[]).unwrap(),
+            ])
+            .build::<1>();
+
+        assert_eq!(
+            robot.select_program("medium"),
+            Err(ProgramSelectError::UnknownProgram)
+        );
+        assert_eq!(robot.program_id(), None);
+
+        assert_eq!(robot.select_program("short"), Ok(()));
+        let report = robot.simulate(&simulation);
+        assert_eq!(report.axes[0].1.real_position_in_millimeters, 10_f64);
+
+        assert_eq!(robot.select_program("long"), Ok(()));
+        assert_eq!(robot.program_id(), Some("long"));
+        let report = robot.simulate(&simulation);
+        assert_eq!(report.axes[0].1.real_position_in_millimeters, 30_f64);
+
+        robot.start();
+        assert_eq!(
+            robot.select_program("short"),
+            Err(ProgramSelectError::NotIdle)
+        );
+        assert_eq!(robot.program_id(), Some("long"));
+    }
+
+    #[test]
+    fn axis_s_curve() {
+        let delays = |mut profile: SCurve, max_velocity: f64, steps: u32| {
+            profile.enter_position_mode(max_velocity, steps);
+            let mut delays: Vec<f64, 10_000> = Vec::new();
+            while let Some(delay) = profile.next_delay() {
+                delays.push(delay).unwrap();
+            }
+            delays
+        };
+
+        // every step is taken, without going faster than the max velocity
+        for steps in [1, 2, 10, 1_000, 10_000] {
+            let s_curve = delays(SCurve::new(1_000_f64, 10_000_f64), 2_500_f64, steps);
+            assert_eq!(s_curve.len(), steps as usize);
+            assert!(s_curve
+                .iter()
+                .all(|delay| *delay >= 1_f64 / 2_500_f64 - 1e-9));
+        }
+
+        // the acceleration ramps up, so it starts gentler than a trapezoid
+        let s_curve = delays(SCurve::new(1_000_f64, 10_000_f64), 2_500_f64, 10_000);
+        let mut trapezoidal = Trapezoidal::new(1_000_f64);
+        trapezoidal.enter_position_mode(2_500_f64, 10_000);
+        let trapezoidal_start: f64 = (0..10).filter_map(|_| trapezoidal.next_delay()).sum();
+        let s_curve_start: f64 = s_curve.iter().take(10).sum();
+        assert!(s_curve_start > trapezoidal_start);
+
+        // a new target while moving, with room to stop, takes exactly its steps
+        for steps in [3_000, 10_000] {
+            let mut profile = SCurve::new(1_000_f64, 10_000_f64);
+            profile.enter_position_mode(2_500_f64, 10_000);
+            for _ in 0..1_000 {
+                profile.next_delay();
+            }
+            profile.enter_position_mode(2_500_f64, steps);
+            let retarget_steps = (0..20_000).map_while(|_| profile.next_delay()).count();
+            assert_eq!(retarget_steps, steps as usize);
+        }
+
+        // a new target while moving slows down to a stop, past the target
+        let mut profile = SCurve::new(1_000_f64, 10_000_f64);
+        profile.enter_position_mode(2_500_f64, 10_000);
+        for _ in 0..5_000 {
+            profile.next_delay();
+        }
+        profile.enter_position_mode(2_500_f64, 1);
+        let stop_steps = (0..10_000).map_while(|_| profile.next_delay()).count();
+        assert!(stop_steps > 1 && stop_steps < 10_000);
+
+        // an axis ramp is a trapezoid until it has a max jerk
+        let mut ramp = AxisRamp::from_limits(1_000_f64, None);
+        ramp.enter_position_mode(2_500_f64, 10_000);
+        let ramp_start: f64 = (0..10).filter_map(|_| ramp.next_delay()).sum();
+        assert_eq!(ramp_start, trapezoidal_start);
+        let mut ramp = AxisRamp::from_limits(1_000_f64, Some(10_000_f64));
+        ramp.enter_position_mode(2_500_f64, 10_000);
+        let ramp_start: f64 = (0..10).filter_map(|_| ramp.next_delay()).sum();
+        assert_eq!(ramp_start, s_curve_start);
+    }
+
+    #[test]
+    fn axis_max_jerk() {
+        let limit_min = Rc::new(RefCell::new(None));
+        let limit_max = Rc::new(RefCell::new(None));
+        let axis = || {
+            AxisDevice::new_dq542ma(
+                SimulatedPin,
+                SimulatedPin,
+                MockTimer { is_waiting: false },
+                1_000_f64,
+                10_f64,
+                MockSwitch {
+                    update: limit_min.clone(),
+                },
+                MockSwitch {
+                    update: limit_max.clone(),
+                },
+                AxisLimitSide::Min,
+            )
+            .unwrap()
+        };
+        let simulation = Simulation::<1_000>::new(
+            TimerDuration::<1_000>::from_ticks(1),
+            TimerDurationU64::<1_000>::secs(60),
+        );
+        for max_jerk in [0_f64, -1_f64, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                axis().with_max_jerk(max_jerk),
+                Err(AxisError::MaxJerkOutOfRange(_))
+            ));
+            let simulated_axis = SimulatedAxis::new(
+                simulation.sub(),
+                1_000_f64,
+                10_f64,
+                0_f64,
+                100_f64,
+                AxisLimitSide::Min,
+            );
+            assert!(matches!(
+                simulated_axis.with_max_jerk(max_jerk),
+                Err(AxisError::MaxJerkOutOfRange(_))
+            ));
+        }
+        assert!(axis().with_max_jerk(1_000_f64).is_ok());
+    }
+
+    #[test]
+    fn axis_soft_limits() {
+        let limit_min = Rc::new(RefCell::new(Some(SwitchUpdate {
+            status: SwitchStatus::Off,
+        })));
+        let limit_max = Rc::new(RefCell::new(Some(SwitchUpdate {
+            status: SwitchStatus::Off,
+        })));
+        let mut axis = AxisDevice::new_dq542ma(
+            SimulatedPin,
+            SimulatedPin,
+            MockTimer { is_waiting: false },
+            1_000_f64,
+            10_f64,
+            MockSwitch {
+                update: limit_min.clone(),
+            },
+            MockSwitch {
+                update: limit_max.clone(),
+            },
+            AxisLimitSide::Min,
+        )
+        .unwrap()
+        .with_soft_limits(10_f64, 100_f64);
+
+        let run_until_ready = |axis: &mut AxisDevice<_, _, _>, action: AxisAction| {
+            axis.run(&action);
+            for polls in 0..100_000 {
+                // the axis finds its min limit switch, then backs off it
+                if let AxisAction::Home { .. } = action {
+                    if polls == 100 {
+                        limit_min.replace(Some(SwitchUpdate {
+                            status: SwitchStatus::On,
+                        }));
+                    } else if polls == 200 {
+                        limit_min.replace(Some(SwitchUpdate {
+                            status: SwitchStatus::Off,
+                        }));
+                    }
+                }
+                if let Poll::Ready(result) = axis.poll() {
+                    return result;
+                }
+            }
+            panic!("the axis never finished");
+        };
+
+        // before homing, the soft limits aren't enforced
+        let result = run_until_ready(
+            &mut axis,
+            AxisAction::MoveRelative {
+                max_velocity_in_millimeters_per_sec: 10_f64,
+                distance_in_millimeters: 5_f64,
+            },
+        );
+        assert!(result.is_ok());
+
+        let result = run_until_ready(
+            &mut axis,
+            AxisAction::Home {
+                max_velocity_in_millimeters_per_sec: 10_f64,
+                back_off_distance_in_millimeters: 1_f64,
+            },
+        );
+        assert!(result.is_ok());
+        assert!(axis.status().is_homed);
+
+        // after homing, a move past the soft limits is rejected before it starts
+        let result = run_until_ready(
+            &mut axis,
+            AxisAction::MoveRelative {
+                max_velocity_in_millimeters_per_sec: 10_f64,
+                distance_in_millimeters: 150_f64,
+            },
+        );
+        assert!(matches!(
+            result,
+            Err(AxisError::SoftLimit { requested, min, max })
+                if requested == 150_f64 && min == 10_f64 && max == 100_f64
+        ));
+        assert_eq!(axis.status().logical_position_in_millimeters, 0_f64);
+        assert_eq!(axis.get_current_step(), 0);
+
+        let result = run_until_ready(
+            &mut axis,
+            AxisAction::MoveAbsolute {
+                max_velocity_in_millimeters_per_sec: 10_f64,
+                position_in_millimeters: 20_f64,
+            },
+        );
+        assert!(result.is_ok());
+        assert_eq!(axis.get_current_step(), 200);
+    }
+
+    #[test]
+    fn axis_homed() {
+        let limit_min = Rc::new(RefCell::new(None));
+        let limit_max = Rc::new(RefCell::new(None));
+        let new_axis = || {
+            // the switches are only sensed on a change, so each axis starts from a fresh one
+            for limit in [&limit_min, &limit_max] {
+                limit.replace(Some(SwitchUpdate {
+                    status: SwitchStatus::Off,
+                }));
+            }
+            AxisDevice::new_dq542ma(
+                SimulatedPin,
+                SimulatedPin,
+                MockTimer { is_waiting: false },
+                1_000_f64,
+                10_f64,
+                MockSwitch {
+                    update: limit_min.clone(),
+                },
+                MockSwitch {
+                    update: limit_max.clone(),
+                },
+                AxisLimitSide::Min,
+            )
+            .unwrap()
+        };
+        let run_until_ready = |axis: &mut AxisDevice<_, _, _>, action: AxisAction| {
+            axis.run(&action);
+            for _ in 0..100_000 {
+                if let Poll::Ready(result) = axis.poll() {
+                    return result;
+                }
+            }
+            panic!("the axis never finished");
+        };
+        let home = AxisAction::Home {
+            max_velocity_in_millimeters_per_sec: 10_f64,
+            back_off_distance_in_millimeters: 1_f64,
+        };
+        let move_to = |position_in_millimeters| AxisAction::MoveAbsolute {
+            max_velocity_in_millimeters_per_sec: 10_f64,
+            position_in_millimeters,
+        };
+
+        // an absolute move before homing is rejected, unless allowed
+        let mut axis = new_axis();
+        assert!(!axis.status().is_homed);
+        let result = run_until_ready(&mut axis, move_to(20_f64));
+        assert!(matches!(result, Err(AxisError::NotHomed)));
+        assert_eq!(axis.get_current_step(), 0);
+
+        let mut unhomed_axis = new_axis().with_unhomed_absolute_moves();
+        assert!(run_until_ready(&mut unhomed_axis, move_to(20_f64)).is_ok());
+        assert_eq!(unhomed_axis.get_current_step(), 200);
+
+        // starting on the min limit switch, homing only backs off it
+        limit_min.replace(Some(SwitchUpdate {
+            status: SwitchStatus::On,
+        }));
+        assert!(run_until_ready(&mut axis, home).is_ok());
+        assert!(axis.status().is_homed);
+        limit_min.replace(Some(SwitchUpdate {
+            status: SwitchStatus::Off,
+        }));
+        assert!(run_until_ready(&mut axis, move_to(20_f64)).is_ok());
+        assert!(axis.status().is_homed);
+
+        // a cancel slows down to a stop without losing steps, so the axis stays homed
+        axis.run(&move_to(50_f64));
+        for _ in 0..100 {
+            let _ = axis.poll();
+        }
+        axis.cancel();
+        while axis.poll().is_pending() {}
+        assert!(axis.status().is_homed);
+        assert!(run_until_ready(&mut axis, move_to(20_f64)).is_ok());
+
+        // but not a cancel part way through homing
+        axis.run(&home);
+        for _ in 0..100 {
+            let _ = axis.poll();
+        }
+        axis.cancel();
+        while axis.poll().is_pending() {}
+        assert!(!axis.status().is_homed);
+        let result = run_until_ready(&mut axis, move_to(20_f64));
+        assert!(matches!(result, Err(AxisError::NotHomed)));
+
+        // nor hitting a limit switch
+        limit_min.replace(Some(SwitchUpdate {
+            status: SwitchStatus::On,
+        }));
+        assert!(run_until_ready(&mut axis, home).is_ok());
+        limit_min.replace(Some(SwitchUpdate {
+            status: SwitchStatus::Off,
+        }));
+        limit_max.replace(Some(SwitchUpdate {
+            status: SwitchStatus::On,
+        }));
+        let result = run_until_ready(&mut axis, move_to(20_f64));
+        assert!(matches!(result, Err(AxisError::Limit(AxisLimitSide::Max))));
+        assert!(!axis.status().is_homed);
+    }
+
+    #[test]
+    fn axis_limit_stop() {
+        let limit_min = Rc::new(RefCell::new(Some(SwitchUpdate {
+            status: SwitchStatus::Off,
+        })));
+        let limit_max = Rc::new(RefCell::new(Some(SwitchUpdate {
+            status: SwitchStatus::Off,
+        })));
+        let mut axis = AxisDevice::new_dq542ma(
+            SimulatedPin,
+            SimulatedPin,
+            MockTimer { is_waiting: false },
+            1_000_f64,
+            10_f64,
+            MockSwitch {
+                update: limit_min.clone(),
+            },
+            MockSwitch {
+                update: limit_max.clone(),
+            },
+            AxisLimitSide::Min,
+        )
+        .unwrap();
+        let set_limit_max = |status| limit_max.replace(Some(SwitchUpdate { status }));
+        let poll_until_ready = |axis: &mut AxisDevice<_, _, _>| {
+            for _ in 0..100_000 {
+                if let Poll::Ready(result) = axis.poll() {
+                    return result;
+                }
+            }
+            panic!("the axis never finished");
+        };
+        let move_forward = AxisAction::MoveRelative {
+            max_velocity_in_millimeters_per_sec: 10_f64,
+            distance_in_millimeters: 50_f64,
+        };
+
+        // the axis stops where it hits the limit switch
+        axis.run(&move_forward);
+        for _ in 0..100 {
+            let _ = axis.poll();
+        }
+        set_limit_max(SwitchStatus::On);
+        let result = poll_until_ready(&mut axis);
+        assert!(matches!(result, Err(AxisError::Limit(AxisLimitSide::Max))));
+        let limit_step = axis.get_current_step();
+        assert!(limit_step > 0 && limit_step < 500);
+        assert_eq!(
+            axis.status().logical_position_in_millimeters,
+            axis.get_real_position()
+        );
+
+        // a cancel or pause after the limit error is done straight away, without stepping
+        axis.cancel();
+        assert!(poll_until_ready(&mut axis).is_ok());
+        // only a step pulse that had already started finishes
+        let stop_step = axis.get_current_step();
+        assert!(stop_step - limit_step <= 1);
+        axis.pause();
+        assert!(axis.poll().is_pending());
+        axis.resume();
+        assert!(poll_until_ready(&mut axis).is_ok());
+        assert_eq!(axis.get_current_step(), stop_step);
+
+        // as is a cancel of a move into the limit switch, before it's polled
+        axis.run(&move_forward);
+        axis.cancel();
+        assert!(poll_until_ready(&mut axis).is_ok());
+        assert_eq!(axis.get_current_step(), stop_step);
+
+        // a limit hit while slowing down for a pause stops there too
+        set_limit_max(SwitchStatus::Off);
+        axis.run(&move_forward);
+        for _ in 0..100 {
+            let _ = axis.poll();
+        }
+        axis.pause();
+        let _ = axis.poll();
+        set_limit_max(SwitchStatus::On);
+        let result = poll_until_ready(&mut axis);
+        assert!(matches!(result, Err(AxisError::Limit(AxisLimitSide::Max))));
+        assert!(poll_until_ready(&mut axis).is_ok());
+
+        // and the axis can still move away from the limit switch
+        let result = {
+            axis.run(&AxisAction::MoveRelative {
+                max_velocity_in_millimeters_per_sec: 10_f64,
+                distance_in_millimeters: -1_f64,
+            });
+            poll_until_ready(&mut axis)
+        };
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn motion_linear() {
+        let simulation = Simulation::<1_000>::new(
+            TimerDuration::<1_000>::from_ticks(1),
+            TimerDurationU64::<1_000>::secs(60),
+        );
+        let x_axis = SimulatedAxis::new(
+            simulation.sub(),
+            100_f64,
+            100_f64,
+            0_f64,
+            100_f64,
+            AxisLimitSide::Min,
+        )
+        .with_unhomed_absolute_moves();
+        let y_axis = SimulatedAxis::new(
+            simulation.sub(),
+            50_f64,
+            100_f64,
+            0_f64,
+            100_f64,
+            AxisLimitSide::Min,
+        )
+        .with_unhomed_absolute_moves();
+        let mut axes = XyAxisSet::new(x_axis, y_axis);
+
+        // a 50mm line, x has 3/5 of it and y has 4/5, y is the slowest to accelerate
+        let motion = MotionAction::LinearMoveAbsolute {
+            max_velocity_in_millimeters_per_sec: 10_f64,
+            positions_in_millimeters: [
+                Some((XyAxisId::X, 30_f64)),
+                Some((XyAxisId::Y, 40_f64)),
+                None,
+            ],
+        };
+        let actions = motion.plan(|id| ActuatorSetStatus::<AxisStatus>::status(&axes, id));
+        assert!(matches!(
+            actions,
+            [
+                Some((
+                    XyAxisId::X,
+                    AxisAction::MoveCoordinated {
+                        max_velocity_in_millimeters_per_sec: x_velocity,
+                        max_acceleration_in_millimeters_per_sec_per_sec: x_acceleration,
+                        ..
+                    }
+                )),
+                Some((
+                    XyAxisId::Y,
+                    AxisAction::MoveCoordinated {
+                        max_velocity_in_millimeters_per_sec: y_velocity,
+                        max_acceleration_in_millimeters_per_sec_per_sec: y_acceleration,
+                        ..
+                    }
+                )),
+                None,
+            ] if abs(x_velocity - 6_f64) < 1e-9
+                && abs(y_velocity - 8_f64) < 1e-9
+                && abs(x_acceleration - 37.5_f64) < 1e-9
+                && abs(y_acceleration - 50_f64) < 1e-9
+        ));
+
+        // both axes start and finish together
+        for (id, action) in actions.iter().flatten() {
+            axes.run(id, action);
+        }
+        let mut finished_at = [None, None];
+        let mut ticks = 0;
+        while finished_at.contains(&None) && ticks < 60_000 {
+            simulation.tick();
+            ticks += 1;
+            for (index, id) in [XyAxisId::X, XyAxisId::Y].iter().enumerate() {
+                if finished_at[index].is_none() && axes.poll(id).is_ready() {
+                    finished_at[index] = Some(ticks);
+                }
+            }
+        }
+        let (x_ticks, y_ticks) = (finished_at[0].unwrap(), finished_at[1].unwrap());
+        assert!(x_ticks > 5_000);
+        assert!(abs(x_ticks - y_ticks) <= 5);
+    }
+
+    #[test]
+    fn gcode_lines() {
+        let axes = [('X', 0_u8), ('Y', 1_u8)];
+        let mut gcode: GcodeInterpreter<1_000, (), u8, u8, ()> = GcodeInterpreter::new(
+            &axes,
+            GcodeSettings {
+                rapid_velocity_in_millimeters_per_sec: 50_f64,
+                home_velocity_in_millimeters_per_sec: 10_f64,
+                home_back_off_distance_in_millimeters: 2_f64,
+                arc_tolerance_in_millimeters: 0.01_f64,
+            },
+        )
+        .with_spindle(0);
+
+        let commands = gcode.interpret_line("G1 X10 F600 ; comment").unwrap();
+        assert!(matches!(
+            commands.as_slice(),
+            [Command::Axis(
+                0,
+                AxisAction::MoveAbsolute {
+                    max_velocity_in_millimeters_per_sec,
+                    position_in_millimeters,
+                }
+            )] if *max_velocity_in_millimeters_per_sec == 10_f64 && *position_in_millimeters == 10_f64
+        ));
+
+        // axes in the same line move together in a straight line
+        let commands = gcode.interpret_line("G91 G0 X-1.5 Y2").unwrap();
+        assert!(matches!(
+            commands.as_slice(),
+            [Command::Motion(MotionAction::LinearMoveRelative {
+                distances_in_millimeters: [Some((0, x)), Some((1, y)), None],
+                ..
+            })] if *x == -1.5_f64 && *y == 2_f64
+        ));
+
+        let commands = gcode.interpret_line("M3 S1200").unwrap();
+        assert!(matches!(
+            commands.as_slice(),
+            [Command::Spindle(
+                0,
+                SpindleAction::Set {
+                    status: SpindleStatus::On { rpm: 1200 }
+                }
+            )]
+        ));
+
+        let commands = gcode.interpret_line("G4 P250").unwrap();
+        assert!(matches!(
+            commands.as_slice(),
+            [Command::Wait { duration }] if duration.to_millis() == 250
+        ));
+
+        assert_eq!(
+            gcode.interpret_line("G1 Z1").unwrap_err(),
+            GcodeError::UnknownAxis('Z')
+        );
+        assert_eq!(
+            gcode.interpret_line("G2 X1").unwrap_err(),
+            GcodeError::NoArcCenter
+        );
+        assert_eq!(
+            gcode.interpret_line("G2 R5").unwrap_err(),
+            GcodeError::RadiusFullCircle
+        );
+        assert_eq!(
+            gcode.interpret_line("G91 G2 X0 Y0 R5").unwrap_err(),
+            GcodeError::RadiusFullCircle
+        );
+        assert_eq!(
+            gcode.interpret_line("G1 X1 F0").unwrap_err(),
+            GcodeError::FeedRateOutOfRange(0_f64)
+        );
+        assert_eq!(
+            gcode.interpret_line("G1 X1 F-100").unwrap_err(),
+            GcodeError::FeedRateOutOfRange(-100_f64)
+        );
+        assert_eq!(
+            gcode.interpret_line("G4 P-250").unwrap_err(),
+            GcodeError::DwellTimeOutOfRange(-250_f64)
+        );
+        assert_eq!(
+            gcode.interpret_line("G4 S-1").unwrap_err(),
+            GcodeError::DwellTimeOutOfRange(-1000_f64)
+        );
+        assert_eq!(
+            gcode.interpret_line("M3.1").unwrap_err(),
+            GcodeError::UnsupportedM(3.1_f64)
+        );
+    }
+
+    #[test]
+    fn motion_arc() {
+        let axes = [('X', 0_u8), ('Y', 1_u8)];
+        let mut gcode: GcodeInterpreter<1_000, (), u8, u8, ()> = GcodeInterpreter::new(
+            &axes,
+            GcodeSettings {
+                rapid_velocity_in_millimeters_per_sec: 50_f64,
+                home_velocity_in_millimeters_per_sec: 10_f64,
+                home_back_off_distance_in_millimeters: 2_f64,
+                arc_tolerance_in_millimeters: 0.01_f64,
+            },
+        );
+
+        // a clockwise half circle from the origin around (5, 0)
+        let commands = gcode.interpret_line("G17 G2 X10 Y0 I5 J0 F600").unwrap();
+        let [Command::Motion(action)] = commands.as_slice() else {
+            panic!("expected a single motion");
+        };
+        let mut segments = action.segments(|_| AxisStatus::default()).unwrap();
+
+        let mut count = 0;
+        let mut end = (0_f64, 0_f64);
+        let mut motion = Some(segments.current());
+        while let Some(MotionAction::LinearMoveAbsolute {
+            positions_in_millimeters: [Some((0, x)), Some((1, y)), None],
+            ..
+        }) = motion
+        {
+            count += 1;
+            assert!(abs(Float::hypot(x - 5_f64, y) - 5_f64) < 1e-9);
+            assert!(y >= -1e-9);
+            end = (x, y);
+            motion = segments.advance();
+        }
+        assert_eq!(count, 25);
+        assert_eq!(end, (10_f64, 0_f64));
+
+        // a radius can't place the center of a full circle
+        let commands = gcode.interpret_line("G90 G2 X0 Y0 R5").unwrap();
+        let [Command::Motion(action)] = commands.as_slice() else {
+            panic!("expected a single motion");
+        };
+        assert_eq!(
+            action.segments(|_| AxisStatus::default()).unwrap_err(),
+            MotionError::RadiusFullCircle
+        );
+    }
 }
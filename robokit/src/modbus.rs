@@ -225,8 +225,8 @@ where
             }
             ModbusSerialStatus::Reading => {
                 // if we've read enough, stop reading and return result
-                if self.response_bytes_length.is_some()
-                    && self.response_bytes.len() == (self.response_bytes_length.unwrap() as usize)
+                if self.response_bytes_length.map(|length| length as usize)
+                    == Some(self.response_bytes.len())
                 {
                     self.status = ModbusSerialStatus::Idle;
                     self.response_ready = true;
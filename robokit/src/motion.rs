@@ -0,0 +1,532 @@
+// coordinated moves across the axes of an axis set, e.g. a diagonal line through x and y
+
+use core::f64::consts::PI;
+use defmt::Format;
+use num::{abs, Float};
+
+use crate::actuators::axis::{AxisAction, AxisStatus};
+
+pub const MOTION_AXES_COUNT: usize = 3;
+
+const MIN_ARC_TOLERANCE_IN_MILLIMETERS: f64 = 0.001;
+
+pub type MotionTargets<AxisId> = [Option<(AxisId, f64)>; MOTION_AXES_COUNT];
+
+#[derive(Clone, Copy, Debug, Format)]
+pub enum MotionAction<AxisId> {
+    LinearMoveAbsolute {
+        max_velocity_in_millimeters_per_sec: f64,
+        positions_in_millimeters: MotionTargets<AxisId>,
+    },
+    LinearMoveRelative {
+        max_velocity_in_millimeters_per_sec: f64,
+        distances_in_millimeters: MotionTargets<AxisId>,
+    },
+    ArcMoveAbsolute {
+        max_velocity_in_millimeters_per_sec: f64,
+        arc: MotionArc<AxisId>,
+    },
+    ArcMoveRelative {
+        max_velocity_in_millimeters_per_sec: f64,
+        arc: MotionArc<AxisId>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum MotionError {
+    RadiusFullCircle,
+}
+
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum ArcDirection {
+    // looking down on the plane, from the first axis towards the second
+    Clockwise,
+    CounterClockwise,
+}
+
+#[derive(Clone, Copy, Debug, Format)]
+pub enum ArcCenter {
+    // from where the arc starts, like the I, J and K of G-code
+    Offset {
+        first_in_millimeters: f64,
+        second_in_millimeters: f64,
+    },
+    // the shorter way around, or the longer way around if negative
+    Radius {
+        radius_in_millimeters: f64,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Format)]
+pub struct MotionArc<AxisId> {
+    pub plane: (AxisId, AxisId),
+    pub direction: ArcDirection,
+    pub end_in_millimeters: (Option<f64>, Option<f64>),
+    pub center: ArcCenter,
+    pub helix: Option<(AxisId, f64)>,
+    pub tolerance_in_millimeters: f64,
+}
+
+#[derive(Clone, Copy, Debug, Format)]
+struct ArcPlan {
+    start: (f64, f64),
+    center: (f64, f64),
+    start_radius: f64,
+    end_radius: f64,
+    start_angle: f64,
+    sweep_angle: f64,
+    end: (f64, f64),
+    helix_start: f64,
+    helix_end: f64,
+}
+
+#[derive(Clone, Copy, Debug, Format)]
+pub struct MotionSegments<AxisId> {
+    action: MotionAction<AxisId>,
+    arc: Option<ArcPlan>,
+    segments_count: usize,
+    segment_index: usize,
+}
+
+impl<AxisId> MotionSegments<AxisId>
+where
+    AxisId: Copy,
+{
+    pub fn current(&self) -> MotionAction<AxisId> {
+        let (arc_plan, arc, max_velocity_in_millimeters_per_sec) = match (self.arc, self.action) {
+            (
+                Some(arc_plan),
+                MotionAction::ArcMoveAbsolute {
+                    max_velocity_in_millimeters_per_sec,
+                    arc,
+                }
+                | MotionAction::ArcMoveRelative {
+                    max_velocity_in_millimeters_per_sec,
+                    arc,
+                },
+            ) => (arc_plan, arc, max_velocity_in_millimeters_per_sec),
+            _ => return self.action,
+        };
+
+        let segment_start = self.segment_index as f64 / self.segments_count as f64;
+        let segment_end = (self.segment_index + 1) as f64 / self.segments_count as f64;
+        let ((first, second), helix) = arc_plan.point(segment_end);
+        let helix_axis = arc.helix.map(|(axis, _)| axis);
+
+        match self.action {
+            // each segment goes on from where the last one ended, so the arc stays relative
+            MotionAction::ArcMoveRelative { .. } => {
+                let ((first_start, second_start), helix_start) = arc_plan.point(segment_start);
+                MotionAction::LinearMoveRelative {
+                    max_velocity_in_millimeters_per_sec,
+                    distances_in_millimeters: [
+                        Some((arc.plane.0, first - first_start)),
+                        Some((arc.plane.1, second - second_start)),
+                        helix_axis.map(|axis| (axis, helix - helix_start)),
+                    ],
+                }
+            }
+            _ => MotionAction::LinearMoveAbsolute {
+                max_velocity_in_millimeters_per_sec,
+                positions_in_millimeters: [
+                    Some((arc.plane.0, first)),
+                    Some((arc.plane.1, second)),
+                    helix_axis.map(|axis| (axis, helix)),
+                ],
+            },
+        }
+    }
+
+    pub fn advance(&mut self) -> Option<MotionAction<AxisId>> {
+        if self.segment_index + 1 >= self.segments_count {
+            return None;
+        }
+
+        self.segment_index += 1;
+        Some(self.current())
+    }
+}
+
+impl<AxisId> MotionSegments<AxisId>
+where
+    AxisId: Copy + PartialEq,
+{
+    pub fn arc_bounds(&self, axis: AxisId) -> Option<(f64, f64)> {
+        let (arc_plan, arc) = match (self.arc, self.action) {
+            (
+                Some(arc_plan),
+                MotionAction::ArcMoveAbsolute { arc, .. }
+                | MotionAction::ArcMoveRelative { arc, .. },
+            ) => (arc_plan, arc),
+            _ => return None,
+        };
+
+        // a circle is furthest along its first axis every half turn, and along its second a quarter turn on
+        let turn_offset = if axis == arc.plane.0 {
+            Some(0_f64)
+        } else if axis == arc.plane.1 {
+            Some(PI / 2_f64)
+        } else if arc.helix.is_some_and(|(helix_axis, _)| helix_axis == axis) {
+            None
+        } else {
+            return None;
+        };
+        let coordinate = |((first, second), helix): ((f64, f64), f64)| {
+            if axis == arc.plane.0 {
+                first
+            } else if axis == arc.plane.1 {
+                second
+            } else {
+                helix
+            }
+        };
+
+        let start = coordinate(arc_plan.point(0_f64));
+        let end = coordinate(arc_plan.point(1_f64));
+        let (mut min, mut max) = (start.min(end), start.max(end));
+
+        if let Some(turn_offset) = turn_offset {
+            let from_angle = arc_plan
+                .start_angle
+                .min(arc_plan.start_angle + arc_plan.sweep_angle);
+            let to_angle = arc_plan
+                .start_angle
+                .max(arc_plan.start_angle + arc_plan.sweep_angle);
+            let mut angle = Float::ceil((from_angle - turn_offset) / PI) * PI + turn_offset;
+            while angle < to_angle {
+                let part = (angle - arc_plan.start_angle) / arc_plan.sweep_angle;
+                let value = coordinate(arc_plan.point(part));
+                min = min.min(value);
+                max = max.max(value);
+                angle += PI;
+            }
+        }
+
+        Some((min, max))
+    }
+}
+
+impl ArcPlan {
+    fn point(&self, part: f64) -> ((f64, f64), f64) {
+        let helix = self.helix_start + (self.helix_end - self.helix_start) * part;
+        if part <= 0_f64 {
+            return (self.start, helix);
+        }
+        if part >= 1_f64 {
+            return (self.end, helix);
+        }
+
+        let angle = self.start_angle + self.sweep_angle * part;
+        let radius = self.start_radius + (self.end_radius - self.start_radius) * part;
+        (
+            (
+                self.center.0 + radius * Float::cos(angle),
+                self.center.1 + radius * Float::sin(angle),
+            ),
+            helix,
+        )
+    }
+}
+
+impl<AxisId> MotionAction<AxisId>
+where
+    AxisId: Copy,
+{
+    pub fn axes(&self) -> impl Iterator<Item = AxisId> {
+        let axes = match self {
+            MotionAction::LinearMoveAbsolute {
+                positions_in_millimeters: targets,
+                ..
+            }
+            | MotionAction::LinearMoveRelative {
+                distances_in_millimeters: targets,
+                ..
+            } => targets.map(|target| target.map(|(axis, _)| axis)),
+            MotionAction::ArcMoveAbsolute { arc, .. }
+            | MotionAction::ArcMoveRelative { arc, .. } => [
+                Some(arc.plane.0),
+                Some(arc.plane.1),
+                arc.helix.map(|(axis, _)| axis),
+            ],
+        };
+
+        axes.into_iter().flatten()
+    }
+
+    pub fn segments<Status>(&self, status: Status) -> Result<MotionSegments<AxisId>, MotionError>
+    where
+        Status: Fn(&AxisId) -> AxisStatus,
+    {
+        let (arc, is_relative) = match *self {
+            MotionAction::ArcMoveAbsolute { arc, .. } => (arc, false),
+            MotionAction::ArcMoveRelative { arc, .. } => (arc, true),
+            MotionAction::LinearMoveAbsolute { .. } | MotionAction::LinearMoveRelative { .. } => {
+                return Ok(MotionSegments {
+                    action: *self,
+                    arc: None,
+                    segments_count: 1,
+                    segment_index: 0,
+                })
+            }
+        };
+
+        let start = (
+            status(&arc.plane.0).logical_position_in_millimeters,
+            status(&arc.plane.1).logical_position_in_millimeters,
+        );
+        let end_of = |start: f64, end: Option<f64>| match (end, is_relative) {
+            (None, _) => start,
+            (Some(end), false) => end,
+            (Some(distance), true) => start + distance,
+        };
+        let end = (
+            end_of(start.0, arc.end_in_millimeters.0),
+            end_of(start.1, arc.end_in_millimeters.1),
+        );
+        let (helix_start, helix_end) = match arc.helix {
+            Some((axis, value)) => {
+                let helix_start = status(&axis).logical_position_in_millimeters;
+                (helix_start, end_of(helix_start, Some(value)))
+            }
+            None => (0_f64, 0_f64),
+        };
+
+        let center = match arc.center {
+            ArcCenter::Offset {
+                first_in_millimeters,
+                second_in_millimeters,
+            } => (
+                start.0 + first_in_millimeters,
+                start.1 + second_in_millimeters,
+            ),
+            ArcCenter::Radius {
+                radius_in_millimeters,
+            } => {
+                let chord = (end.0 - start.0, end.1 - start.1);
+                let chord_length = Float::sqrt(chord.0 * chord.0 + chord.1 * chord.1);
+                if chord_length < MIN_ARC_TOLERANCE_IN_MILLIMETERS {
+                    return Err(MotionError::RadiusFullCircle);
+                }
+                let radius = abs(radius_in_millimeters);
+                // a radius too short for the chord makes a half circle
+                let height =
+                    Float::sqrt((radius * radius - chord_length * chord_length / 4_f64).max(0_f64));
+                // counter clockwise the short way around, the center is left of the chord
+                let is_left = (arc.direction == ArcDirection::CounterClockwise)
+                    == (radius_in_millimeters >= 0_f64);
+                let side = if is_left { 1_f64 } else { -1_f64 };
+                let (normal_first, normal_second) =
+                    (-chord.1 / chord_length, chord.0 / chord_length);
+                (
+                    (start.0 + end.0) / 2_f64 + side * height * normal_first,
+                    (start.1 + end.1) / 2_f64 + side * height * normal_second,
+                )
+            }
+        };
+
+        let start_radius = Float::hypot(start.0 - center.0, start.1 - center.1);
+        let end_radius = Float::hypot(end.0 - center.0, end.1 - center.1);
+        let start_angle = Float::atan2(start.1 - center.1, start.0 - center.0);
+        let end_angle = Float::atan2(end.1 - center.1, end.0 - center.0);
+
+        // ending where it starts goes all the way around
+        let mut sweep_angle = end_angle - start_angle;
+        match arc.direction {
+            ArcDirection::CounterClockwise if sweep_angle <= 0_f64 => sweep_angle += 2_f64 * PI,
+            ArcDirection::Clockwise if sweep_angle >= 0_f64 => sweep_angle -= 2_f64 * PI,
+            _ => {}
+        }
+
+        // the sagitta of each chord is at most the tolerance
+        let radius = start_radius.max(end_radius);
+        let tolerance = arc
+            .tolerance_in_millimeters
+            .max(MIN_ARC_TOLERANCE_IN_MILLIMETERS);
+        let max_segment_angle = if tolerance < radius {
+            2_f64 * Float::acos(1_f64 - tolerance / radius)
+        } else {
+            PI
+        };
+        let segments_count = Float::ceil(abs(sweep_angle) / max_segment_angle).max(1_f64) as usize;
+
+        Ok(MotionSegments {
+            action: *self,
+            arc: Some(ArcPlan {
+                start,
+                center,
+                start_radius,
+                end_radius,
+                start_angle,
+                sweep_angle,
+                end,
+                helix_start,
+                helix_end,
+            }),
+            segments_count,
+            segment_index: 0,
+        })
+    }
+
+    pub fn plan<Status>(&self, status: Status) -> [Option<(AxisId, AxisAction)>; MOTION_AXES_COUNT]
+    where
+        Status: Fn(&AxisId) -> AxisStatus,
+    {
+        let (max_velocity_in_millimeters_per_sec, targets, is_relative) = match *self {
+            MotionAction::LinearMoveAbsolute {
+                max_velocity_in_millimeters_per_sec,
+                positions_in_millimeters,
+            } => (
+                max_velocity_in_millimeters_per_sec,
+                positions_in_millimeters,
+                false,
+            ),
+            MotionAction::LinearMoveRelative {
+                max_velocity_in_millimeters_per_sec,
+                distances_in_millimeters,
+            } => (
+                max_velocity_in_millimeters_per_sec,
+                distances_in_millimeters,
+                true,
+            ),
+            // an arc starts with its first segment, an arc that can't be split doesn't move
+            MotionAction::ArcMoveAbsolute { .. } | MotionAction::ArcMoveRelative { .. } => {
+                return match self.segments(&status) {
+                    Ok(segments) => segments.current().plan(status),
+                    Err(_) => [None; MOTION_AXES_COUNT],
+                };
+            }
+        };
+
+        let mut moves = [None; MOTION_AXES_COUNT];
+        let mut length_squared = 0_f64;
+        for (index, target) in targets.iter().enumerate() {
+            if let Some((axis, value)) = target {
+                let status = status(axis);
+                let start = status.logical_position_in_millimeters;
+                let position = if is_relative { start + value } else { *value };
+                let distance = position - start;
+                length_squared += distance * distance;
+                moves[index] = Some((*axis, position, distance, status));
+            }
+        }
+        let length = Float::sqrt(length_squared);
+
+        // the line accelerates (and jerks) only as fast as its slowest axis allows
+        let mut max_acceleration_in_millimeters_per_sec_per_sec = f64::INFINITY;
+        let mut max_jerk_in_millimeters_per_sec_per_sec_per_sec: Option<f64> = None;
+        for (_, _, distance, status) in moves.iter().flatten() {
+            if *distance != 0_f64 {
+                let max_acceleration = status.max_acceleration_in_millimeters_per_sec_per_sec
+                    * length
+                    / abs(*distance);
+                if max_acceleration < max_acceleration_in_millimeters_per_sec_per_sec {
+                    max_acceleration_in_millimeters_per_sec_per_sec = max_acceleration;
+                }
+                if let Some(max_jerk) = status.max_jerk_in_millimeters_per_sec_per_sec_per_sec {
+                    let max_jerk = max_jerk * length / abs(*distance);
+                    if max_jerk_in_millimeters_per_sec_per_sec_per_sec
+                        .is_none_or(|line_max_jerk| max_jerk < line_max_jerk)
+                    {
+                        max_jerk_in_millimeters_per_sec_per_sec_per_sec = Some(max_jerk);
+                    }
+                }
+            }
+        }
+
+        let mut actions = [None; MOTION_AXES_COUNT];
+        for (index, axis_move) in moves.iter().enumerate() {
+            if let Some((axis, position, distance, _)) = axis_move {
+                let action = if *distance == 0_f64 && is_relative {
+                    AxisAction::MoveRelative {
+                        max_velocity_in_millimeters_per_sec,
+                        distance_in_millimeters: 0_f64,
+                    }
+                } else if *distance == 0_f64 {
+                    AxisAction::MoveAbsolute {
+                        max_velocity_in_millimeters_per_sec,
+                        position_in_millimeters: *position,
+                    }
+                } else {
+                    let share = abs(*distance) / length;
+                    AxisAction::MoveCoordinated {
+                        max_velocity_in_millimeters_per_sec: max_velocity_in_millimeters_per_sec
+                            * share,
+                        max_acceleration_in_millimeters_per_sec_per_sec:
+                            max_acceleration_in_millimeters_per_sec_per_sec * share,
+                        max_jerk_in_millimeters_per_sec_per_sec_per_sec:
+                            max_jerk_in_millimeters_per_sec_per_sec_per_sec
+                                .map(|max_jerk| max_jerk * share),
+                        position_in_millimeters: *position,
+                        is_relative,
+                    }
+                };
+                actions[index] = Some((*axis, action));
+            }
+        }
+
+        actions
+    }
+}
+
+impl<AxisId> MotionAction<AxisId>
+where
+    AxisId: Copy + PartialEq,
+{
+    pub fn axis_action(&self, axis: AxisId) -> Option<AxisAction> {
+        let (max_velocity_in_millimeters_per_sec, targets, is_relative) = match *self {
+            MotionAction::LinearMoveAbsolute {
+                max_velocity_in_millimeters_per_sec,
+                positions_in_millimeters,
+            } => (
+                max_velocity_in_millimeters_per_sec,
+                positions_in_millimeters,
+                false,
+            ),
+            MotionAction::LinearMoveRelative {
+                max_velocity_in_millimeters_per_sec,
+                distances_in_millimeters,
+            } => (
+                max_velocity_in_millimeters_per_sec,
+                distances_in_millimeters,
+                true,
+            ),
+            MotionAction::ArcMoveAbsolute {
+                max_velocity_in_millimeters_per_sec,
+                arc,
+            } => (max_velocity_in_millimeters_per_sec, arc.ends(), false),
+            MotionAction::ArcMoveRelative {
+                max_velocity_in_millimeters_per_sec,
+                arc,
+            } => (max_velocity_in_millimeters_per_sec, arc.ends(), true),
+        };
+        let (_, value) = targets
+            .iter()
+            .flatten()
+            .find(|(target_axis, _)| *target_axis == axis)?;
+
+        Some(if is_relative {
+            AxisAction::MoveRelative {
+                max_velocity_in_millimeters_per_sec,
+                distance_in_millimeters: *value,
+            }
+        } else {
+            AxisAction::MoveAbsolute {
+                max_velocity_in_millimeters_per_sec,
+                position_in_millimeters: *value,
+            }
+        })
+    }
+}
+
+impl<AxisId> MotionArc<AxisId>
+where
+    AxisId: Copy,
+{
+    fn ends(&self) -> MotionTargets<AxisId> {
+        [
+            self.end_in_millimeters.0.map(|end| (self.plane.0, end)),
+            self.end_in_millimeters.1.map(|end| (self.plane.1, end)),
+            self.helix,
+        ]
+    }
+}
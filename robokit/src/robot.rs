@@ -1,83 +1,471 @@
 use core::task::Poll;
+use fugit::TimerDurationU64;
 use heapless::Vec;
 
-use crate::actuators::EmptyActuatorSet;
-use crate::actuators::{axis::AxisAction, led::LedAction, spindle::SpindleAction, ActuatorSet};
-use crate::runner::{Command, Runner, RunnerError};
-use crate::scheduler::Scheduler;
+use crate::actuators::{
+    axis::{AxisAction, AxisStatus},
+    led::LedAction,
+    spindle::SpindleAction,
+    ActuatorSet, ActuatorSetStatus,
+};
+use crate::actuators::{ActuatorSetList, EmptyActuatorSet, EmptyActuatorSetList};
+use crate::runner::{CommandAlias, Runner, RunnerErrorAlias};
+use crate::scheduler::{
+    CommandSource, ErrorPolicy, Program, ProgramPart, ProgramSelectError, RunMode, Scheduler,
+    SchedulerObserver, SchedulerPhase,
+};
+use crate::sensors::{switch::SwitchUpdate, EmptySensorSet, SensorSet};
+use crate::simulation::{SimulatedAxisStatus, Simulation, SimulationReport};
+use crate::stream::CommandStream;
+use crate::timer::SubTimer;
+use crate::util::vec_from_array;
+use crate::validation::{
+    check_spindle, AxisValidator, ProgramError, ValidationError, ValidationSettings,
+};
 
-pub struct Robot<
-    const LED_TIMER_HZ: u32,
+type CommandsAlias<
+    const TIMER_HZ: u32,
+    LedSet,
+    AxisSet,
+    SpindleSet,
+    SwitchSet,
+    ExtraSets,
+    const COMMANDS_COUNT: usize,
+> = Vec<CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>, COMMANDS_COUNT>;
+
+type CommandStreamAlias<
+    'a,
+    const TIMER_HZ: u32,
+    LedSet,
+    AxisSet,
+    SpindleSet,
+    SwitchSet,
+    ExtraSets,
+    const QUEUE_SIZE: usize,
+> = CommandStream<
+    'a,
+    CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+    QUEUE_SIZE,
+>;
+
+type CommandsBuilderAlias<
+    const TIMER_HZ: u32,
+    const RUN_COMMANDS_COUNT: usize,
+    const START_COMMANDS_COUNT: usize,
+    const STOP_COMMANDS_COUNT: usize,
+    const ERROR_COMMANDS_COUNT: usize,
+    LedSet,
+    AxisSet,
+    SpindleSet,
+    SwitchSet,
+    ExtraSets,
+    Observer,
+    const PROGRAMS_COUNT: usize,
+> = RobotBuilder2<
+    TIMER_HZ,
+    CommandsAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets, RUN_COMMANDS_COUNT>,
+    START_COMMANDS_COUNT,
+    STOP_COMMANDS_COUNT,
+    ERROR_COMMANDS_COUNT,
+    LedSet,
+    AxisSet,
+    SpindleSet,
+    SwitchSet,
+    ExtraSets,
+    Observer,
+    PROGRAMS_COUNT,
+>;
+
+type CommandStreamBuilderAlias<
+    'a,
+    const TIMER_HZ: u32,
+    const QUEUE_SIZE: usize,
+    const START_COMMANDS_COUNT: usize,
+    const STOP_COMMANDS_COUNT: usize,
+    const ERROR_COMMANDS_COUNT: usize,
+    LedSet,
+    AxisSet,
+    SpindleSet,
+    SwitchSet,
+    ExtraSets,
+    Observer,
+> = RobotBuilder2<
+    TIMER_HZ,
+    CommandStreamAlias<'a, TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets, QUEUE_SIZE>,
+    START_COMMANDS_COUNT,
+    STOP_COMMANDS_COUNT,
+    ERROR_COMMANDS_COUNT,
+    LedSet,
+    AxisSet,
+    SpindleSet,
+    SwitchSet,
+    ExtraSets,
+    Observer,
+    0,
+>;
+
+type RobotStatusAlias<
+    const TIMER_HZ: u32,
+    LedSet,
+    AxisSet,
+    SpindleSet,
+    SwitchSet,
+    ExtraSets,
+    const AXES_COUNT: usize,
+> = RobotStatus<
+    CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+    <AxisSet as ActuatorSet>::Id,
+    AXES_COUNT,
+>;
+
+#[derive(Clone, Debug)]
+pub struct RobotStatus<Command, AxisId, const AXES_COUNT: usize> {
+    pub phase: SchedulerPhase,
+    pub command_index: Option<usize>,
+    pub commands_count: Option<usize>,
+    pub active_command: Option<Command>,
+    pub axes: Vec<(AxisId, AxisStatus), AXES_COUNT>,
+}
+
+type ProgramErrorAlias<AxisSet, SpindleSet> =
+    ProgramError<<AxisSet as ActuatorSet>::Id, <SpindleSet as ActuatorSet>::Id>;
+
+type ProgramAlias<
+    const TIMER_HZ: u32,
+    RunCommands,
+    const START_COMMANDS_COUNT: usize,
+    const STOP_COMMANDS_COUNT: usize,
+    LedSet,
+    AxisSet,
+    SpindleSet,
+    SwitchSet,
+    ExtraSets,
+> = Program<
+    CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+    RunCommands,
+    START_COMMANDS_COUNT,
+    STOP_COMMANDS_COUNT,
+>;
+
+type ProgramCommandsAlias<
+    const TIMER_HZ: u32,
     const RUN_COMMANDS_COUNT: usize,
     const START_COMMANDS_COUNT: usize,
     const STOP_COMMANDS_COUNT: usize,
+    LedSet,
+    AxisSet,
+    SpindleSet,
+    SwitchSet,
+    ExtraSets,
+> = ProgramAlias<
+    TIMER_HZ,
+    CommandsAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets, RUN_COMMANDS_COUNT>,
+    START_COMMANDS_COUNT,
+    STOP_COMMANDS_COUNT,
+    LedSet,
+    AxisSet,
+    SpindleSet,
+    SwitchSet,
+    ExtraSets,
+>;
+
+type RobotPollAlias<'a, const TIMER_HZ: u32, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets> =
+    Poll<
+        Result<
+            (),
+            &'a RunnerErrorAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+        >,
+    >;
+
+pub struct Robot<
+    const TIMER_HZ: u32,
+    RunCommands,
+    const START_COMMANDS_COUNT: usize,
+    const STOP_COMMANDS_COUNT: usize,
+    const ERROR_COMMANDS_COUNT: usize,
     const ACTIVE_COMMANDS_COUNT: usize,
     LedSet,
     AxisSet,
     SpindleSet,
+    SwitchSet,
+    ExtraSets,
+    Observer,
+    const PROGRAMS_COUNT: usize,
 > where
-    LedSet: ActuatorSet<Action = LedAction<LED_TIMER_HZ>>,
-    AxisSet: ActuatorSet<Action = AxisAction>,
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
     SpindleSet: ActuatorSet<Action = SpindleAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+    ExtraSets: ActuatorSetList,
+    RunCommands:
+        CommandSource<CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>>,
+    Observer: SchedulerObserver<
+        CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+        RunnerErrorAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+    >,
 {
     scheduler: Scheduler<
-        Command<LED_TIMER_HZ, LedSet::Id, AxisSet::Id, SpindleSet::Id>,
-        Runner<LED_TIMER_HZ, ACTIVE_COMMANDS_COUNT, LedSet, AxisSet, SpindleSet>,
-        RUN_COMMANDS_COUNT,
+        CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+        Runner<TIMER_HZ, ACTIVE_COMMANDS_COUNT, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+        RunCommands,
         START_COMMANDS_COUNT,
         STOP_COMMANDS_COUNT,
+        ERROR_COMMANDS_COUNT,
+        Observer,
     >,
+    programs: Vec<
+        ProgramAlias<
+            TIMER_HZ,
+            RunCommands,
+            START_COMMANDS_COUNT,
+            STOP_COMMANDS_COUNT,
+            LedSet,
+            AxisSet,
+            SpindleSet,
+            SwitchSet,
+            ExtraSets,
+        >,
+        PROGRAMS_COUNT,
+    >,
+    program_id: Option<&'static str>,
 }
 
 impl<
-        const LED_TIMER_HZ: u32,
-        const RUN_COMMANDS_COUNT: usize,
+        const TIMER_HZ: u32,
+        RunCommands,
         const START_COMMANDS_COUNT: usize,
         const STOP_COMMANDS_COUNT: usize,
+        const ERROR_COMMANDS_COUNT: usize,
         const ACTIVE_COMMANDS_COUNT: usize,
         LedSet,
         AxisSet,
         SpindleSet,
+        SwitchSet,
+        ExtraSets,
     >
     Robot<
-        LED_TIMER_HZ,
-        RUN_COMMANDS_COUNT,
+        TIMER_HZ,
+        RunCommands,
         START_COMMANDS_COUNT,
         STOP_COMMANDS_COUNT,
+        ERROR_COMMANDS_COUNT,
         ACTIVE_COMMANDS_COUNT,
         LedSet,
         AxisSet,
         SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        (),
+        0,
     >
 where
-    LedSet: ActuatorSet<Action = LedAction<LED_TIMER_HZ>>,
-    AxisSet: ActuatorSet<Action = AxisAction>,
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
     SpindleSet: ActuatorSet<Action = SpindleAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+    ExtraSets: ActuatorSetList,
+    RunCommands:
+        CommandSource<CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>>,
 {
     pub fn new(
-        run_commands: Vec<
-            Command<LED_TIMER_HZ, LedSet::Id, AxisSet::Id, SpindleSet::Id>,
-            RUN_COMMANDS_COUNT,
+        runner: Runner<
+            TIMER_HZ,
+            ACTIVE_COMMANDS_COUNT,
+            LedSet,
+            AxisSet,
+            SpindleSet,
+            SwitchSet,
+            ExtraSets,
         >,
+        run_commands: RunCommands,
         start_commands: Vec<
-            Command<LED_TIMER_HZ, LedSet::Id, AxisSet::Id, SpindleSet::Id>,
+            CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
             START_COMMANDS_COUNT,
         >,
         stop_commands: Vec<
-            Command<LED_TIMER_HZ, LedSet::Id, AxisSet::Id, SpindleSet::Id>,
+            CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
             STOP_COMMANDS_COUNT,
         >,
-        leds: LedSet,
-        axes: AxisSet,
-        spindles: SpindleSet,
+        error_commands: Vec<
+            CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+            ERROR_COMMANDS_COUNT,
+        >,
+        run_mode: RunMode,
+        error_policy: ErrorPolicy,
     ) -> Self {
-        let runner: Runner<LED_TIMER_HZ, ACTIVE_COMMANDS_COUNT, _, _, _> =
-            Runner::new(leds, axes, spindles);
-        let scheduler = Scheduler::new(runner, run_commands, start_commands, stop_commands);
+        let scheduler = Scheduler::new(
+            runner,
+            run_commands,
+            start_commands,
+            stop_commands,
+            error_commands,
+            run_mode,
+            error_policy,
+        );
+
+        Self {
+            scheduler,
+            programs: Vec::new(),
+            program_id: None,
+        }
+    }
 
-        Self { scheduler }
+    pub fn with_observer<Observer>(
+        self,
+        observer: Observer,
+    ) -> Robot<
+        TIMER_HZ,
+        RunCommands,
+        START_COMMANDS_COUNT,
+        STOP_COMMANDS_COUNT,
+        ERROR_COMMANDS_COUNT,
+        ACTIVE_COMMANDS_COUNT,
+        LedSet,
+        AxisSet,
+        SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+        0,
+    >
+    where
+        Observer: SchedulerObserver<
+            CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+            RunnerErrorAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+        >,
+    {
+        Robot {
+            scheduler: self.scheduler.with_observer(observer),
+            programs: self.programs,
+            program_id: self.program_id,
+        }
+    }
+}
+
+impl<
+        const TIMER_HZ: u32,
+        RunCommands,
+        const START_COMMANDS_COUNT: usize,
+        const STOP_COMMANDS_COUNT: usize,
+        const ERROR_COMMANDS_COUNT: usize,
+        const ACTIVE_COMMANDS_COUNT: usize,
+        LedSet,
+        AxisSet,
+        SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+    >
+    Robot<
+        TIMER_HZ,
+        RunCommands,
+        START_COMMANDS_COUNT,
+        STOP_COMMANDS_COUNT,
+        ERROR_COMMANDS_COUNT,
+        ACTIVE_COMMANDS_COUNT,
+        LedSet,
+        AxisSet,
+        SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+        0,
+    >
+where
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
+    SpindleSet: ActuatorSet<Action = SpindleAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+    ExtraSets: ActuatorSetList,
+    RunCommands:
+        CommandSource<CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>>,
+    Observer: SchedulerObserver<
+        CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+        RunnerErrorAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+    >,
+{
+    pub fn with_programs<const PROGRAMS_COUNT: usize>(
+        self,
+        programs: Vec<
+            ProgramAlias<
+                TIMER_HZ,
+                RunCommands,
+                START_COMMANDS_COUNT,
+                STOP_COMMANDS_COUNT,
+                LedSet,
+                AxisSet,
+                SpindleSet,
+                SwitchSet,
+                ExtraSets,
+            >,
+            PROGRAMS_COUNT,
+        >,
+    ) -> Robot<
+        TIMER_HZ,
+        RunCommands,
+        START_COMMANDS_COUNT,
+        STOP_COMMANDS_COUNT,
+        ERROR_COMMANDS_COUNT,
+        ACTIVE_COMMANDS_COUNT,
+        LedSet,
+        AxisSet,
+        SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+        PROGRAMS_COUNT,
+    > {
+        Robot {
+            scheduler: self.scheduler,
+            programs,
+            program_id: None,
+        }
     }
+}
 
+impl<
+        const TIMER_HZ: u32,
+        RunCommands,
+        const START_COMMANDS_COUNT: usize,
+        const STOP_COMMANDS_COUNT: usize,
+        const ERROR_COMMANDS_COUNT: usize,
+        const ACTIVE_COMMANDS_COUNT: usize,
+        LedSet,
+        AxisSet,
+        SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+        const PROGRAMS_COUNT: usize,
+    >
+    Robot<
+        TIMER_HZ,
+        RunCommands,
+        START_COMMANDS_COUNT,
+        STOP_COMMANDS_COUNT,
+        ERROR_COMMANDS_COUNT,
+        ACTIVE_COMMANDS_COUNT,
+        LedSet,
+        AxisSet,
+        SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+        PROGRAMS_COUNT,
+    >
+where
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
+    SpindleSet: ActuatorSet<Action = SpindleAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+    ExtraSets: ActuatorSetList,
+    RunCommands:
+        CommandSource<CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>>,
+    Observer: SchedulerObserver<
+        CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+        RunnerErrorAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+    >,
+{
     pub fn start(&mut self) {
         self.scheduler.start()
     }
@@ -86,355 +474,1115 @@ where
         self.scheduler.stop()
     }
 
+    pub fn emergency_stop(&mut self) {
+        self.scheduler.emergency_stop()
+    }
+
+    pub fn reset(&mut self) {
+        self.scheduler.reset()
+    }
+
+    pub fn set_step_mode(&mut self, is_step_mode: bool) {
+        self.scheduler.set_step_mode(is_step_mode)
+    }
+
+    pub fn is_step_mode(&self) -> bool {
+        self.scheduler.is_step_mode()
+    }
+
+    pub fn step(&mut self) {
+        self.scheduler.step()
+    }
+
+    pub fn pause(&mut self) {
+        self.scheduler.pause()
+    }
+
+    pub fn resume(&mut self) {
+        self.scheduler.resume()
+    }
+
     pub fn toggle(&mut self) {
         self.scheduler.toggle()
     }
 
+    pub fn select_program(&mut self, program_id: &'static str) -> Result<(), ProgramSelectError>
+    where
+        RunCommands: Clone,
+    {
+        let program = self
+            .programs
+            .iter()
+            .find(|program| program.id == program_id)
+            .ok_or(ProgramSelectError::UnknownProgram)?;
+
+        self.scheduler.load_program(
+            program.run_commands.clone(),
+            program.start_commands.clone(),
+            program.stop_commands.clone(),
+        )?;
+        self.program_id = Some(program_id);
+
+        Ok(())
+    }
+
+    pub fn program_id(&self) -> Option<&'static str> {
+        self.program_id
+    }
+
+    pub fn cycle_count(&self) -> usize {
+        self.scheduler.cycle_count()
+    }
+
+    pub fn phase(&self) -> SchedulerPhase {
+        self.scheduler.phase()
+    }
+
+    pub fn status<const AXES_COUNT: usize>(
+        &self,
+    ) -> RobotStatusAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets, AXES_COUNT>
+    where
+        AxisSet: ActuatorSetStatus<AxisStatus>,
+    {
+        let runner = self.scheduler.runner();
+
+        let mut axes = Vec::new();
+        for id in AxisSet::IDS {
+            if axes.push((*id, runner.axes().status(id))).is_err() {
+                break;
+            }
+        }
+
+        RobotStatus {
+            phase: self.scheduler.phase(),
+            command_index: self.scheduler.command_index(),
+            commands_count: self.scheduler.commands_count(),
+            active_command: runner.active_command(),
+            axes,
+        }
+    }
+
+    // check the start, run and stop commands before starting, the earliest error is returned
+    pub fn validate(
+        &mut self,
+        settings: &ValidationSettings<'_, AxisSet::Id, SpindleSet::Id>,
+    ) -> Result<(), ProgramErrorAlias<AxisSet, SpindleSet>>
+    where
+        AxisSet::Id: PartialEq,
+        SpindleSet::Id: PartialEq,
+    {
+        if let Some((command_index, error)) = self.scheduler.flow_error() {
+            return Err(program_error(
+                ProgramPart::Run,
+                command_index,
+                ValidationError::Flow(error),
+            ));
+        }
+
+        // the axes are followed through the program together, an arc depends on all of its axes
+        let mut validators: alloc::vec::Vec<AxisValidator<AxisSet::Id>> = AxisSet::IDS
+            .iter()
+            .map(|axis| {
+                let limits = settings
+                    .axes
+                    .iter()
+                    .find(|(id, _)| id == axis)
+                    .map(|(_, limits)| *limits);
+                AxisValidator::new(*axis, limits)
+            })
+            .collect();
+        let mut positions = alloc::vec::Vec::with_capacity(validators.len());
+
+        for (part, command_index, command) in self.scheduler.program() {
+            check_spindle(settings.spindles, &command)
+                .map_err(|error| program_error(part, command_index, error))?;
+
+            positions.clear();
+            positions.extend(
+                AxisSet::IDS
+                    .iter()
+                    .zip(validators.iter().map(AxisValidator::position)),
+            );
+            let position = |axis: &AxisSet::Id| {
+                positions
+                    .iter()
+                    .find(|(id, _)| *id == axis)
+                    .and_then(|(_, position)| *position)
+            };
+
+            for validator in validators.iter_mut() {
+                validator
+                    .check(&command, position)
+                    .map_err(|error| program_error(part, command_index, error))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn simulate(
+        &mut self,
+        simulation: &Simulation<TIMER_HZ>,
+    ) -> SimulationReport<TIMER_HZ, AxisSet::Id>
+    where
+        AxisSet: ActuatorSetStatus<SimulatedAxisStatus>,
+    {
+        let tick_ticks = simulation.tick_duration().ticks() as u64;
+        let max_ticks = simulation.max_duration().ticks();
+        let mut duration_ticks = 0_u64;
+
+        self.scheduler.start();
+        loop {
+            // errors are handled by the error policy, then latch
+            let _ = self.scheduler.poll();
+
+            match self.scheduler.phase() {
+                SchedulerPhase::Idle | SchedulerPhase::Faulted | SchedulerPhase::Estop => break,
+                SchedulerPhase::Step => self.scheduler.step(),
+                _ => {}
+            }
+
+            if duration_ticks >= max_ticks {
+                break;
+            }
+
+            simulation.tick();
+            duration_ticks += tick_ticks;
+        }
+
+        let runner = self.scheduler.runner();
+        let axes = AxisSet::IDS
+            .iter()
+            .map(|id| (*id, runner.axes().status(id)))
+            .collect();
+
+        SimulationReport {
+            is_complete: self.scheduler.phase() == SchedulerPhase::Idle,
+            duration: TimerDurationU64::<TIMER_HZ>::from_ticks(duration_ticks),
+            axes,
+        }
+    }
+
+    pub fn observer(&self) -> &Observer {
+        self.scheduler.observer()
+    }
+
+    pub fn observer_mut(&mut self) -> &mut Observer {
+        self.scheduler.observer_mut()
+    }
+
+    pub fn error(
+        &self,
+    ) -> Option<&RunnerErrorAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>>
+    {
+        self.scheduler.error()
+    }
+
     pub fn poll(
         &mut self,
-    ) -> Poll<
-        Result<
-            (),
-            RunnerError<
-                LedSet::Id,
-                LedSet::Error,
-                AxisSet::Id,
-                AxisSet::Error,
-                SpindleSet::Id,
-                SpindleSet::Error,
-            >,
-        >,
-    > {
+    ) -> RobotPollAlias<'_, TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets> {
         self.scheduler.poll()
     }
 }
 
-pub struct RobotBuilder<const LED_TIMER_HZ: u32, LedSet, AxisSet, SpindleSet>
+fn program_error<AxisId, SpindleId>(
+    part: ProgramPart,
+    command_index: usize,
+    error: ValidationError<AxisId, SpindleId>,
+) -> ProgramError<AxisId, SpindleId> {
+    ProgramError {
+        part,
+        command_index,
+        error,
+    }
+}
+
+pub struct RobotBuilder<const TIMER_HZ: u32, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>
 where
-    LedSet: ActuatorSet<Action = LedAction<LED_TIMER_HZ>>,
-    AxisSet: ActuatorSet<Action = AxisAction>,
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
     SpindleSet: ActuatorSet<Action = SpindleAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+    ExtraSets: ActuatorSetList,
 {
+    timer: SubTimer<TIMER_HZ>,
     leds: LedSet,
     axes: AxisSet,
     spindles: SpindleSet,
+    switches: SwitchSet,
+    extras: ExtraSets,
 }
 
-impl<const LED_TIMER_HZ: u32>
+impl<const TIMER_HZ: u32>
     RobotBuilder<
-        LED_TIMER_HZ,
-        EmptyActuatorSet<LedAction<LED_TIMER_HZ>>,
+        TIMER_HZ,
+        EmptyActuatorSet<LedAction<TIMER_HZ>>,
         EmptyActuatorSet<AxisAction>,
         EmptyActuatorSet<SpindleAction>,
+        EmptySensorSet<SwitchUpdate>,
+        EmptyActuatorSetList,
     >
 {
-    pub fn new() -> Self {
+    pub fn new(timer: SubTimer<TIMER_HZ>) -> Self {
         Self {
-            leds: EmptyActuatorSet::<LedAction<LED_TIMER_HZ>>::new(),
+            timer,
+            leds: EmptyActuatorSet::<LedAction<TIMER_HZ>>::new(),
             axes: EmptyActuatorSet::<AxisAction>::new(),
             spindles: EmptyActuatorSet::<SpindleAction>::new(),
+            switches: EmptySensorSet::<SwitchUpdate>::new(),
+            extras: EmptyActuatorSetList::new(),
         }
     }
 }
 
-impl<const LED_TIMER_HZ: u32, AxisSet, SpindleSet>
-    RobotBuilder<LED_TIMER_HZ, EmptyActuatorSet<LedAction<LED_TIMER_HZ>>, AxisSet, SpindleSet>
+impl<const TIMER_HZ: u32, AxisSet, SpindleSet, SwitchSet, ExtraSets>
+    RobotBuilder<
+        TIMER_HZ,
+        EmptyActuatorSet<LedAction<TIMER_HZ>>,
+        AxisSet,
+        SpindleSet,
+        SwitchSet,
+        ExtraSets,
+    >
 where
-    AxisSet: ActuatorSet<Action = AxisAction>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
     SpindleSet: ActuatorSet<Action = SpindleAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+    ExtraSets: ActuatorSetList,
 {
     pub fn with_leds<LedSet>(
         self,
         leds: LedSet,
-    ) -> RobotBuilder<LED_TIMER_HZ, LedSet, AxisSet, SpindleSet>
+    ) -> RobotBuilder<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>
     where
-        LedSet: ActuatorSet<Action = LedAction<LED_TIMER_HZ>>,
+        LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
     {
         RobotBuilder {
+            timer: self.timer,
             leds,
             axes: self.axes,
             spindles: self.spindles,
+            switches: self.switches,
+            extras: self.extras,
         }
     }
 }
 
-impl<const LED_TIMER_HZ: u32, LedSet, SpindleSet>
-    RobotBuilder<LED_TIMER_HZ, LedSet, EmptyActuatorSet<AxisAction>, SpindleSet>
+impl<const TIMER_HZ: u32, LedSet, SpindleSet, SwitchSet, ExtraSets>
+    RobotBuilder<TIMER_HZ, LedSet, EmptyActuatorSet<AxisAction>, SpindleSet, SwitchSet, ExtraSets>
 where
-    LedSet: ActuatorSet<Action = LedAction<LED_TIMER_HZ>>,
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
     SpindleSet: ActuatorSet<Action = SpindleAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+    ExtraSets: ActuatorSetList,
 {
     pub fn with_axes<AxisSet>(
         self,
         axes: AxisSet,
-    ) -> RobotBuilder<LED_TIMER_HZ, LedSet, AxisSet, SpindleSet>
+    ) -> RobotBuilder<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>
     where
-        AxisSet: ActuatorSet<Action = AxisAction>,
+        AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
     {
         RobotBuilder {
+            timer: self.timer,
             leds: self.leds,
             axes,
             spindles: self.spindles,
+            switches: self.switches,
+            extras: self.extras,
         }
     }
 }
 
-impl<const LED_TIMER_HZ: u32, LedSet, AxisSet>
-    RobotBuilder<LED_TIMER_HZ, LedSet, AxisSet, EmptyActuatorSet<SpindleAction>>
+impl<const TIMER_HZ: u32, LedSet, AxisSet, SwitchSet, ExtraSets>
+    RobotBuilder<TIMER_HZ, LedSet, AxisSet, EmptyActuatorSet<SpindleAction>, SwitchSet, ExtraSets>
 where
-    LedSet: ActuatorSet<Action = LedAction<LED_TIMER_HZ>>,
-    AxisSet: ActuatorSet<Action = AxisAction>,
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+    ExtraSets: ActuatorSetList,
 {
     pub fn with_spindles<SpindleSet>(
         self,
         spindles: SpindleSet,
-    ) -> RobotBuilder<LED_TIMER_HZ, LedSet, AxisSet, SpindleSet>
+    ) -> RobotBuilder<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>
     where
         SpindleSet: ActuatorSet<Action = SpindleAction>,
     {
         RobotBuilder {
+            timer: self.timer,
             leds: self.leds,
             axes: self.axes,
             spindles,
+            switches: self.switches,
+            extras: self.extras,
+        }
+    }
+}
+
+impl<const TIMER_HZ: u32, LedSet, AxisSet, SpindleSet, ExtraSets>
+    RobotBuilder<TIMER_HZ, LedSet, AxisSet, SpindleSet, EmptySensorSet<SwitchUpdate>, ExtraSets>
+where
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
+    SpindleSet: ActuatorSet<Action = SpindleAction>,
+    ExtraSets: ActuatorSetList,
+{
+    pub fn with_switches<SwitchSet>(
+        self,
+        switches: SwitchSet,
+    ) -> RobotBuilder<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>
+    where
+        SwitchSet: SensorSet<Message = SwitchUpdate>,
+    {
+        RobotBuilder {
+            timer: self.timer,
+            leds: self.leds,
+            axes: self.axes,
+            spindles: self.spindles,
+            switches,
+            extras: self.extras,
+        }
+    }
+}
+
+impl<const TIMER_HZ: u32, LedSet, AxisSet, SpindleSet, SwitchSet>
+    RobotBuilder<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, EmptyActuatorSetList>
+where
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
+    SpindleSet: ActuatorSet<Action = SpindleAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+{
+    pub fn with_extras<ExtraSets>(
+        self,
+        extras: ExtraSets,
+    ) -> RobotBuilder<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>
+    where
+        ExtraSets: ActuatorSetList,
+    {
+        RobotBuilder {
+            timer: self.timer,
+            leds: self.leds,
+            axes: self.axes,
+            spindles: self.spindles,
+            switches: self.switches,
+            extras,
         }
     }
 }
 
-impl<const LED_TIMER_HZ: u32, LedSet, AxisSet, SpindleSet>
-    RobotBuilder<LED_TIMER_HZ, LedSet, AxisSet, SpindleSet>
+impl<const TIMER_HZ: u32, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>
+    RobotBuilder<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>
 where
-    LedSet: ActuatorSet<Action = LedAction<LED_TIMER_HZ>>,
-    AxisSet: ActuatorSet<Action = AxisAction>,
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
     SpindleSet: ActuatorSet<Action = SpindleAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+    ExtraSets: ActuatorSetList,
 {
-    pub fn build(self) -> RobotBuilder2<LED_TIMER_HZ, 0, 0, 0, LedSet, AxisSet, SpindleSet> {
+    pub fn build(
+        self,
+    ) -> CommandsBuilderAlias<
+        TIMER_HZ,
+        0,
+        0,
+        0,
+        0,
+        LedSet,
+        AxisSet,
+        SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        (),
+        0,
+    > {
         RobotBuilder2::new(self)
     }
 }
 
 pub struct RobotBuilder2<
-    const LED_TIMER_HZ: u32,
-    const RUN_COMMANDS_COUNT: usize,
+    const TIMER_HZ: u32,
+    RunCommands,
     const START_COMMANDS_COUNT: usize,
     const STOP_COMMANDS_COUNT: usize,
+    const ERROR_COMMANDS_COUNT: usize,
     LedSet,
     AxisSet,
     SpindleSet,
+    SwitchSet,
+    ExtraSets,
+    Observer,
+    const PROGRAMS_COUNT: usize,
 > where
-    LedSet: ActuatorSet<Action = LedAction<LED_TIMER_HZ>>,
-    AxisSet: ActuatorSet<Action = AxisAction>,
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
     SpindleSet: ActuatorSet<Action = SpindleAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+    ExtraSets: ActuatorSetList,
+    RunCommands:
+        CommandSource<CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>>,
 {
+    timer: SubTimer<TIMER_HZ>,
     leds: LedSet,
     axes: AxisSet,
     spindles: SpindleSet,
-    run_commands:
-        Vec<Command<LED_TIMER_HZ, LedSet::Id, AxisSet::Id, SpindleSet::Id>, RUN_COMMANDS_COUNT>,
-    start_commands:
-        Vec<Command<LED_TIMER_HZ, LedSet::Id, AxisSet::Id, SpindleSet::Id>, START_COMMANDS_COUNT>,
-    stop_commands:
-        Vec<Command<LED_TIMER_HZ, LedSet::Id, AxisSet::Id, SpindleSet::Id>, STOP_COMMANDS_COUNT>,
+    switches: SwitchSet,
+    extras: ExtraSets,
+    run_commands: RunCommands,
+    start_commands: Vec<
+        CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+        START_COMMANDS_COUNT,
+    >,
+    stop_commands: Vec<
+        CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+        STOP_COMMANDS_COUNT,
+    >,
+    error_commands: Vec<
+        CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+        ERROR_COMMANDS_COUNT,
+    >,
+    run_mode: RunMode,
+    error_policy: ErrorPolicy,
+    observer: Observer,
+    programs: Vec<
+        ProgramAlias<
+            TIMER_HZ,
+            RunCommands,
+            START_COMMANDS_COUNT,
+            STOP_COMMANDS_COUNT,
+            LedSet,
+            AxisSet,
+            SpindleSet,
+            SwitchSet,
+            ExtraSets,
+        >,
+        PROGRAMS_COUNT,
+    >,
 }
 
-impl<const LED_TIMER_HZ: u32, LedSet, AxisSet, SpindleSet>
-    RobotBuilder2<LED_TIMER_HZ, 0, 0, 0, LedSet, AxisSet, SpindleSet>
+impl<const TIMER_HZ: u32, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>
+    RobotBuilder2<
+        TIMER_HZ,
+        CommandsAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets, 0>,
+        0,
+        0,
+        0,
+        LedSet,
+        AxisSet,
+        SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        (),
+        0,
+    >
 where
-    LedSet: ActuatorSet<Action = LedAction<LED_TIMER_HZ>>,
-    AxisSet: ActuatorSet<Action = AxisAction>,
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
     SpindleSet: ActuatorSet<Action = SpindleAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+    ExtraSets: ActuatorSetList,
 {
-    pub fn new(builder1: RobotBuilder<LED_TIMER_HZ, LedSet, AxisSet, SpindleSet>) -> Self {
+    pub fn new(
+        builder1: RobotBuilder<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+    ) -> Self {
         Self {
+            timer: builder1.timer,
             leds: builder1.leds,
             axes: builder1.axes,
             spindles: builder1.spindles,
+            switches: builder1.switches,
+            extras: builder1.extras,
             run_commands: Vec::new(),
             start_commands: Vec::new(),
             stop_commands: Vec::new(),
+            error_commands: Vec::new(),
+            run_mode: RunMode::Once,
+            error_policy: ErrorPolicy::Halt,
+            observer: (),
+            programs: Vec::new(),
         }
     }
 }
 
 impl<
-        const LED_TIMER_HZ: u32,
+        const TIMER_HZ: u32,
         const START_COMMANDS_COUNT: usize,
         const STOP_COMMANDS_COUNT: usize,
+        const ERROR_COMMANDS_COUNT: usize,
         LedSet,
         AxisSet,
         SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
     >
     RobotBuilder2<
-        LED_TIMER_HZ,
-        0,
+        TIMER_HZ,
+        CommandsAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets, 0>,
         START_COMMANDS_COUNT,
         STOP_COMMANDS_COUNT,
+        ERROR_COMMANDS_COUNT,
         LedSet,
         AxisSet,
         SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+        0,
     >
 where
-    LedSet: ActuatorSet<Action = LedAction<LED_TIMER_HZ>>,
-    AxisSet: ActuatorSet<Action = AxisAction>,
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
     SpindleSet: ActuatorSet<Action = SpindleAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+    ExtraSets: ActuatorSetList,
 {
     pub fn with_run_commands<const RUN_COMMANDS_COUNT: usize>(
         self,
-        run_commands: &[Command<LED_TIMER_HZ, LedSet::Id, AxisSet::Id, SpindleSet::Id>;
+        run_commands: &[CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>;
              RUN_COMMANDS_COUNT],
-    ) -> RobotBuilder2<
-        LED_TIMER_HZ,
+    ) -> CommandsBuilderAlias<
+        TIMER_HZ,
         RUN_COMMANDS_COUNT,
         START_COMMANDS_COUNT,
         STOP_COMMANDS_COUNT,
+        ERROR_COMMANDS_COUNT,
+        LedSet,
+        AxisSet,
+        SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+        0,
+    > {
+        RobotBuilder2 {
+            run_commands: vec_from_array(run_commands),
+            start_commands: self.start_commands,
+            stop_commands: self.stop_commands,
+            timer: self.timer,
+            leds: self.leds,
+            axes: self.axes,
+            spindles: self.spindles,
+            switches: self.switches,
+            extras: self.extras,
+            error_commands: self.error_commands,
+            run_mode: self.run_mode,
+            error_policy: self.error_policy,
+            observer: self.observer,
+            programs: Vec::new(),
+        }
+    }
+
+    pub fn with_run_stream<'a, const QUEUE_SIZE: usize>(
+        self,
+        run_stream: CommandStreamAlias<
+            'a,
+            TIMER_HZ,
+            LedSet,
+            AxisSet,
+            SpindleSet,
+            SwitchSet,
+            ExtraSets,
+            QUEUE_SIZE,
+        >,
+    ) -> CommandStreamBuilderAlias<
+        'a,
+        TIMER_HZ,
+        QUEUE_SIZE,
+        START_COMMANDS_COUNT,
+        STOP_COMMANDS_COUNT,
+        ERROR_COMMANDS_COUNT,
         LedSet,
         AxisSet,
         SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
     > {
         RobotBuilder2 {
-            run_commands: Vec::from_slice(run_commands).unwrap(),
+            run_commands: run_stream,
             start_commands: self.start_commands,
             stop_commands: self.stop_commands,
+            timer: self.timer,
             leds: self.leds,
             axes: self.axes,
             spindles: self.spindles,
+            switches: self.switches,
+            extras: self.extras,
+            error_commands: self.error_commands,
+            run_mode: self.run_mode,
+            error_policy: self.error_policy,
+            observer: self.observer,
+            programs: Vec::new(),
         }
     }
 }
 
 impl<
-        const LED_TIMER_HZ: u32,
+        const TIMER_HZ: u32,
+        const ERROR_COMMANDS_COUNT: usize,
+        LedSet,
+        AxisSet,
+        SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+    >
+    RobotBuilder2<
+        TIMER_HZ,
+        CommandsAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets, 0>,
+        0,
+        0,
+        ERROR_COMMANDS_COUNT,
+        LedSet,
+        AxisSet,
+        SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+        0,
+    >
+where
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
+    SpindleSet: ActuatorSet<Action = SpindleAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+    ExtraSets: ActuatorSetList,
+{
+    pub fn with_programs<
+        const PROGRAMS_COUNT: usize,
         const RUN_COMMANDS_COUNT: usize,
+        const START_COMMANDS_COUNT: usize,
+        const STOP_COMMANDS_COUNT: usize,
+    >(
+        self,
+        programs: &[ProgramCommandsAlias<
+            TIMER_HZ,
+            RUN_COMMANDS_COUNT,
+            START_COMMANDS_COUNT,
+            STOP_COMMANDS_COUNT,
+            LedSet,
+            AxisSet,
+            SpindleSet,
+            SwitchSet,
+            ExtraSets,
+        >; PROGRAMS_COUNT],
+    ) -> CommandsBuilderAlias<
+        TIMER_HZ,
+        RUN_COMMANDS_COUNT,
+        START_COMMANDS_COUNT,
+        STOP_COMMANDS_COUNT,
+        ERROR_COMMANDS_COUNT,
+        LedSet,
+        AxisSet,
+        SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+        PROGRAMS_COUNT,
+    > {
+        RobotBuilder2 {
+            run_commands: Vec::new(),
+            start_commands: Vec::new(),
+            stop_commands: Vec::new(),
+            timer: self.timer,
+            leds: self.leds,
+            axes: self.axes,
+            spindles: self.spindles,
+            switches: self.switches,
+            extras: self.extras,
+            error_commands: self.error_commands,
+            run_mode: self.run_mode,
+            error_policy: self.error_policy,
+            observer: self.observer,
+            programs: vec_from_array(programs),
+        }
+    }
+}
+
+impl<
+        const TIMER_HZ: u32,
+        RunCommands,
         const STOP_COMMANDS_COUNT: usize,
+        const ERROR_COMMANDS_COUNT: usize,
         LedSet,
         AxisSet,
         SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
     >
     RobotBuilder2<
-        LED_TIMER_HZ,
-        RUN_COMMANDS_COUNT,
+        TIMER_HZ,
+        RunCommands,
         0,
         STOP_COMMANDS_COUNT,
+        ERROR_COMMANDS_COUNT,
         LedSet,
         AxisSet,
         SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+        0,
     >
 where
-    LedSet: ActuatorSet<Action = LedAction<LED_TIMER_HZ>>,
-    AxisSet: ActuatorSet<Action = AxisAction>,
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
     SpindleSet: ActuatorSet<Action = SpindleAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+    ExtraSets: ActuatorSetList,
+    RunCommands:
+        CommandSource<CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>>,
 {
     pub fn with_start_commands<const START_COMMANDS_COUNT: usize>(
         self,
-        start_commands: &[Command<LED_TIMER_HZ, LedSet::Id, AxisSet::Id, SpindleSet::Id>;
+        start_commands: &[CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>;
              START_COMMANDS_COUNT],
     ) -> RobotBuilder2<
-        LED_TIMER_HZ,
-        RUN_COMMANDS_COUNT,
+        TIMER_HZ,
+        RunCommands,
         START_COMMANDS_COUNT,
         STOP_COMMANDS_COUNT,
+        ERROR_COMMANDS_COUNT,
         LedSet,
         AxisSet,
         SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+        0,
     > {
         RobotBuilder2 {
             run_commands: self.run_commands,
-            start_commands: Vec::from_slice(start_commands).unwrap(),
+            start_commands: vec_from_array(start_commands),
             stop_commands: self.stop_commands,
+            timer: self.timer,
             leds: self.leds,
             axes: self.axes,
             spindles: self.spindles,
+            switches: self.switches,
+            extras: self.extras,
+            error_commands: self.error_commands,
+            run_mode: self.run_mode,
+            error_policy: self.error_policy,
+            observer: self.observer,
+            programs: Vec::new(),
         }
     }
 }
 
 impl<
-        const LED_TIMER_HZ: u32,
-        const RUN_COMMANDS_COUNT: usize,
+        const TIMER_HZ: u32,
+        RunCommands,
         const START_COMMANDS_COUNT: usize,
+        const ERROR_COMMANDS_COUNT: usize,
         LedSet,
         AxisSet,
         SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
     >
     RobotBuilder2<
-        LED_TIMER_HZ,
-        RUN_COMMANDS_COUNT,
+        TIMER_HZ,
+        RunCommands,
         START_COMMANDS_COUNT,
         0,
+        ERROR_COMMANDS_COUNT,
         LedSet,
         AxisSet,
         SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+        0,
     >
 where
-    LedSet: ActuatorSet<Action = LedAction<LED_TIMER_HZ>>,
-    AxisSet: ActuatorSet<Action = AxisAction>,
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
     SpindleSet: ActuatorSet<Action = SpindleAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+    ExtraSets: ActuatorSetList,
+    RunCommands:
+        CommandSource<CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>>,
 {
     pub fn with_stop_commands<const STOP_COMMANDS_COUNT: usize>(
         self,
-        stop_commands: &[Command<LED_TIMER_HZ, LedSet::Id, AxisSet::Id, SpindleSet::Id>;
+        stop_commands: &[CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>;
              STOP_COMMANDS_COUNT],
     ) -> RobotBuilder2<
-        LED_TIMER_HZ,
-        RUN_COMMANDS_COUNT,
+        TIMER_HZ,
+        RunCommands,
         START_COMMANDS_COUNT,
         STOP_COMMANDS_COUNT,
+        ERROR_COMMANDS_COUNT,
         LedSet,
         AxisSet,
         SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+        0,
     > {
         RobotBuilder2 {
             run_commands: self.run_commands,
             start_commands: self.start_commands,
-            stop_commands: Vec::from_slice(stop_commands).unwrap(),
+            stop_commands: vec_from_array(stop_commands),
+            timer: self.timer,
             leds: self.leds,
             axes: self.axes,
             spindles: self.spindles,
+            switches: self.switches,
+            extras: self.extras,
+            error_commands: self.error_commands,
+            run_mode: self.run_mode,
+            error_policy: self.error_policy,
+            observer: self.observer,
+            programs: Vec::new(),
         }
     }
 }
 
 impl<
-        const LED_TIMER_HZ: u32,
-        const RUN_COMMANDS_COUNT: usize,
+        const TIMER_HZ: u32,
+        RunCommands,
         const START_COMMANDS_COUNT: usize,
         const STOP_COMMANDS_COUNT: usize,
         LedSet,
         AxisSet,
         SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+        const PROGRAMS_COUNT: usize,
     >
     RobotBuilder2<
-        LED_TIMER_HZ,
-        RUN_COMMANDS_COUNT,
+        TIMER_HZ,
+        RunCommands,
         START_COMMANDS_COUNT,
         STOP_COMMANDS_COUNT,
+        0,
         LedSet,
         AxisSet,
         SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+        PROGRAMS_COUNT,
     >
 where
-    LedSet: ActuatorSet<Action = LedAction<LED_TIMER_HZ>>,
-    AxisSet: ActuatorSet<Action = AxisAction>,
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
     SpindleSet: ActuatorSet<Action = SpindleAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+    ExtraSets: ActuatorSetList,
+    RunCommands:
+        CommandSource<CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>>,
 {
+    pub fn with_error_commands<const ERROR_COMMANDS_COUNT: usize>(
+        self,
+        error_commands: &[CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>;
+             ERROR_COMMANDS_COUNT],
+    ) -> RobotBuilder2<
+        TIMER_HZ,
+        RunCommands,
+        START_COMMANDS_COUNT,
+        STOP_COMMANDS_COUNT,
+        ERROR_COMMANDS_COUNT,
+        LedSet,
+        AxisSet,
+        SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+        PROGRAMS_COUNT,
+    > {
+        RobotBuilder2 {
+            run_commands: self.run_commands,
+            start_commands: self.start_commands,
+            stop_commands: self.stop_commands,
+            error_commands: vec_from_array(error_commands),
+            timer: self.timer,
+            leds: self.leds,
+            axes: self.axes,
+            spindles: self.spindles,
+            switches: self.switches,
+            extras: self.extras,
+            run_mode: self.run_mode,
+            error_policy: self.error_policy,
+            observer: self.observer,
+            programs: self.programs,
+        }
+    }
+}
+
+impl<
+        const TIMER_HZ: u32,
+        RunCommands,
+        const START_COMMANDS_COUNT: usize,
+        const STOP_COMMANDS_COUNT: usize,
+        const ERROR_COMMANDS_COUNT: usize,
+        LedSet,
+        AxisSet,
+        SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        const PROGRAMS_COUNT: usize,
+    >
+    RobotBuilder2<
+        TIMER_HZ,
+        RunCommands,
+        START_COMMANDS_COUNT,
+        STOP_COMMANDS_COUNT,
+        ERROR_COMMANDS_COUNT,
+        LedSet,
+        AxisSet,
+        SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        (),
+        PROGRAMS_COUNT,
+    >
+where
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
+    SpindleSet: ActuatorSet<Action = SpindleAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+    ExtraSets: ActuatorSetList,
+    RunCommands:
+        CommandSource<CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>>,
+{
+    pub fn with_observer<Observer>(
+        self,
+        observer: Observer,
+    ) -> RobotBuilder2<
+        TIMER_HZ,
+        RunCommands,
+        START_COMMANDS_COUNT,
+        STOP_COMMANDS_COUNT,
+        ERROR_COMMANDS_COUNT,
+        LedSet,
+        AxisSet,
+        SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+        PROGRAMS_COUNT,
+    >
+    where
+        Observer: SchedulerObserver<
+            CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+            RunnerErrorAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+        >,
+    {
+        RobotBuilder2 {
+            run_commands: self.run_commands,
+            start_commands: self.start_commands,
+            stop_commands: self.stop_commands,
+            error_commands: self.error_commands,
+            timer: self.timer,
+            leds: self.leds,
+            axes: self.axes,
+            spindles: self.spindles,
+            switches: self.switches,
+            extras: self.extras,
+            run_mode: self.run_mode,
+            error_policy: self.error_policy,
+            observer,
+            programs: self.programs,
+        }
+    }
+}
+
+impl<
+        const TIMER_HZ: u32,
+        RunCommands,
+        const START_COMMANDS_COUNT: usize,
+        const STOP_COMMANDS_COUNT: usize,
+        const ERROR_COMMANDS_COUNT: usize,
+        LedSet,
+        AxisSet,
+        SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+        const PROGRAMS_COUNT: usize,
+    >
+    RobotBuilder2<
+        TIMER_HZ,
+        RunCommands,
+        START_COMMANDS_COUNT,
+        STOP_COMMANDS_COUNT,
+        ERROR_COMMANDS_COUNT,
+        LedSet,
+        AxisSet,
+        SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+        PROGRAMS_COUNT,
+    >
+where
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
+    SpindleSet: ActuatorSet<Action = SpindleAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+    ExtraSets: ActuatorSetList,
+    RunCommands:
+        CommandSource<CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>>,
+    Observer: SchedulerObserver<
+        CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+        RunnerErrorAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+    >,
+{
+    pub fn with_run_mode(mut self, run_mode: RunMode) -> Self {
+        self.run_mode = run_mode;
+        self
+    }
+
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+
     pub fn build<const ACTIVE_COMMANDS_COUNT: usize>(
         self,
     ) -> Robot<
-        LED_TIMER_HZ,
-        RUN_COMMANDS_COUNT,
+        TIMER_HZ,
+        RunCommands,
         START_COMMANDS_COUNT,
         STOP_COMMANDS_COUNT,
+        ERROR_COMMANDS_COUNT,
         ACTIVE_COMMANDS_COUNT,
         LedSet,
         AxisSet,
         SpindleSet,
+        SwitchSet,
+        ExtraSets,
+        Observer,
+        PROGRAMS_COUNT,
     > {
+        let runner = Runner::new(
+            self.timer,
+            self.leds,
+            self.axes,
+            self.spindles,
+            self.switches,
+            self.extras,
+        );
+
         Robot::new(
+            runner,
             self.run_commands,
             self.start_commands,
             self.stop_commands,
-            self.leds,
-            self.axes,
-            self.spindles,
+            self.error_commands,
+            self.run_mode,
+            self.error_policy,
         )
+        .with_observer(self.observer)
+        .with_programs(self.programs)
     }
 }
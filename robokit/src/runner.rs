@@ -1,57 +1,481 @@
 use core::fmt::Debug;
 use core::task::Poll;
 use defmt::Format;
+use fugit::{TimerDurationU32 as TimerDuration, TimerInstantU32 as TimerInstant};
+use fugit_timer::Timer;
 use heapless::Deque;
 
 use crate::actuators::{
-    axis::AxisAction, led::LedAction, spindle::SpindleAction, Actuator, ActuatorSet,
+    axis::{AxisAction, AxisStatus},
+    led::LedAction,
+    spindle::SpindleAction,
+    Actuator, ActuatorSet, ActuatorSetList, ActuatorSetStatus,
 };
+use crate::motion::{MotionAction, MotionError, MotionSegments};
+use crate::scheduler::{CommandFlow, CommandFlowError, ProgramCommand};
+use crate::sensors::{
+    switch::{SwitchStatus, SwitchUpdate},
+    SensorSet,
+};
+use crate::timer::SubTimer;
+
+#[derive(Clone, Copy, Debug, Format)]
+pub enum Command<const TIMER_HZ: u32, LedId, AxisId, SpindleId, SwitchId, ExtraCommand = ()>
+where
+    LedId: Debug + Format,
+    AxisId: Debug + Format,
+    SpindleId: Debug + Format,
+    SwitchId: Debug + Format,
+    ExtraCommand: Debug + Format,
+{
+    Led(LedId, LedAction<TIMER_HZ>),
+    Axis(AxisId, AxisAction),
+    Motion(MotionAction<AxisId>),
+    Spindle(SpindleId, SpindleAction),
+    Extra(ExtraCommand),
+    Wait {
+        duration: TimerDuration<TIMER_HZ>,
+    },
+    WaitSwitch {
+        switch: SwitchId,
+        status: SwitchStatus,
+        timeout: Option<TimerDuration<TIMER_HZ>>,
+    },
+    GroupStart,
+    GroupEnd,
+    Timeout {
+        duration: TimerDuration<TIMER_HZ>,
+        command: TimedCommand<TIMER_HZ, LedId, AxisId, SpindleId, SwitchId, ExtraCommand>,
+    },
+    Label {
+        label: &'static str,
+    },
+    Call {
+        label: &'static str,
+    },
+    Return,
+    Jump {
+        label: &'static str,
+    },
+    RepeatStart {
+        count: usize,
+    },
+    RepeatEnd,
+}
 
 #[derive(Clone, Copy, Debug, Format)]
-pub enum Command<const LED_TIMER_HZ: u32, LedId, AxisId, SpindleId>
+pub enum TimedCommand<const TIMER_HZ: u32, LedId, AxisId, SpindleId, SwitchId, ExtraCommand = ()>
 where
     LedId: Debug + Format,
     AxisId: Debug + Format,
     SpindleId: Debug + Format,
+    SwitchId: Debug + Format,
+    ExtraCommand: Debug + Format,
 {
-    Led(LedId, LedAction<LED_TIMER_HZ>),
+    Led(LedId, LedAction<TIMER_HZ>),
     Axis(AxisId, AxisAction),
+    Motion(MotionAction<AxisId>),
     Spindle(SpindleId, SpindleAction),
+    Extra(ExtraCommand),
+    Wait {
+        duration: TimerDuration<TIMER_HZ>,
+    },
+    WaitSwitch {
+        switch: SwitchId,
+        status: SwitchStatus,
+        timeout: Option<TimerDuration<TIMER_HZ>>,
+    },
+}
+
+impl<const TIMER_HZ: u32, LedId, AxisId, SpindleId, SwitchId, ExtraCommand>
+    From<TimedCommand<TIMER_HZ, LedId, AxisId, SpindleId, SwitchId, ExtraCommand>>
+    for Command<TIMER_HZ, LedId, AxisId, SpindleId, SwitchId, ExtraCommand>
+where
+    LedId: Debug + Format,
+    AxisId: Debug + Format,
+    SpindleId: Debug + Format,
+    SwitchId: Debug + Format,
+    ExtraCommand: Debug + Format,
+{
+    fn from(
+        command: TimedCommand<TIMER_HZ, LedId, AxisId, SpindleId, SwitchId, ExtraCommand>,
+    ) -> Self {
+        match command {
+            TimedCommand::Led(id, action) => Command::Led(id, action),
+            TimedCommand::Axis(id, action) => Command::Axis(id, action),
+            TimedCommand::Motion(action) => Command::Motion(action),
+            TimedCommand::Spindle(id, action) => Command::Spindle(id, action),
+            TimedCommand::Extra(command) => Command::Extra(command),
+            TimedCommand::Wait { duration } => Command::Wait { duration },
+            TimedCommand::WaitSwitch {
+                switch,
+                status,
+                timeout,
+            } => Command::WaitSwitch {
+                switch,
+                status,
+                timeout,
+            },
+        }
+    }
+}
+
+impl<const TIMER_HZ: u32, LedId, AxisId, SpindleId, SwitchId, ExtraCommand> ProgramCommand
+    for Command<TIMER_HZ, LedId, AxisId, SpindleId, SwitchId, ExtraCommand>
+where
+    LedId: Debug + Format,
+    AxisId: Debug + Format,
+    SpindleId: Debug + Format,
+    SwitchId: Debug + Format,
+    ExtraCommand: Debug + Format,
+{
+    fn flow(&self) -> CommandFlow {
+        match *self {
+            Command::GroupStart => CommandFlow::GroupStart,
+            Command::GroupEnd => CommandFlow::GroupEnd,
+            Command::Label { label } => CommandFlow::Label { label },
+            Command::Call { label } => CommandFlow::Call { label },
+            Command::Return => CommandFlow::Return,
+            Command::Jump { label } => CommandFlow::Jump { label },
+            Command::RepeatStart { count } => CommandFlow::RepeatStart { count },
+            Command::RepeatEnd => CommandFlow::RepeatEnd,
+            _ => CommandFlow::Run,
+        }
+    }
+}
+
+pub type CommandAlias<const TIMER_HZ: u32, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets> =
+    Command<
+        TIMER_HZ,
+        <LedSet as ActuatorSet>::Id,
+        <AxisSet as ActuatorSet>::Id,
+        <SpindleSet as ActuatorSet>::Id,
+        <SwitchSet as SensorSet>::Id,
+        <ExtraSets as ActuatorSetList>::Command,
+    >;
+
+#[derive(Clone, Copy, Debug, Format)]
+struct ActiveCommand<Command, AxisId, const TIMER_HZ: u32> {
+    command: Command,
+    started_at: TimerInstant<TIMER_HZ>,
+    timeout: Option<TimerDuration<TIMER_HZ>>,
+    is_failed: bool,
+    is_retrying: bool,
+    motion: Option<Result<MotionSegments<AxisId>, MotionError>>,
+    axis_action: Option<AxisAction>,
 }
 
+type ActiveCommandAlias<const TIMER_HZ: u32, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets> =
+    ActiveCommand<
+        CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+        <AxisSet as ActuatorSet>::Id,
+        TIMER_HZ,
+    >;
+
+type RunnerPollAlias<const TIMER_HZ: u32, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets> =
+    Poll<Result<(), RunnerErrorAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>>>;
+
 pub struct Runner<
-    const LED_TIMER_HZ: u32,
+    const TIMER_HZ: u32,
     const ACTIVE_COMMMANDS_COUNT: usize,
     LedSet,
     AxisSet,
     SpindleSet,
+    SwitchSet,
+    ExtraSets,
 > where
-    LedSet: ActuatorSet<Action = LedAction<LED_TIMER_HZ>>,
-    AxisSet: ActuatorSet<Action = AxisAction>,
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
     SpindleSet: ActuatorSet<Action = SpindleAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+    ExtraSets: ActuatorSetList,
 {
     active_commands: Deque<
-        Command<LED_TIMER_HZ, LedSet::Id, AxisSet::Id, SpindleSet::Id>,
+        ActiveCommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
         ACTIVE_COMMMANDS_COUNT,
     >,
+    timer: SubTimer<TIMER_HZ>,
+    paused_at: Option<TimerInstant<TIMER_HZ>>,
     leds: LedSet,
     axes: AxisSet,
     spindles: SpindleSet,
+    switches: SwitchSet,
+    extras: ExtraSets,
+    overflowed_command:
+        Option<ActiveCommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>>,
+    is_emergency_stopping: bool,
 }
 
-impl<const LED_TIMER_HZ: u32, const ACTIVE_COMMMANDS_COUNT: usize, LedSet, AxisSet, SpindleSet>
-    Runner<LED_TIMER_HZ, ACTIVE_COMMMANDS_COUNT, LedSet, AxisSet, SpindleSet>
+impl<
+        const TIMER_HZ: u32,
+        const ACTIVE_COMMMANDS_COUNT: usize,
+        LedSet,
+        AxisSet,
+        SpindleSet,
+        SwitchSet,
+        ExtraSets,
+    > Runner<TIMER_HZ, ACTIVE_COMMMANDS_COUNT, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>
 where
-    LedSet: ActuatorSet<Action = LedAction<LED_TIMER_HZ>>,
-    AxisSet: ActuatorSet<Action = AxisAction>,
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
     SpindleSet: ActuatorSet<Action = SpindleAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+    ExtraSets: ActuatorSetList,
 {
-    pub fn new(leds: LedSet, axes: AxisSet, spindles: SpindleSet) -> Self {
+    pub fn new(
+        timer: SubTimer<TIMER_HZ>,
+        leds: LedSet,
+        axes: AxisSet,
+        spindles: SpindleSet,
+        switches: SwitchSet,
+        extras: ExtraSets,
+    ) -> Self {
         Self {
             active_commands: Deque::new(),
+            timer,
+            paused_at: None,
             leds,
             axes,
             spindles,
+            switches,
+            extras,
+            overflowed_command: None,
+            is_emergency_stopping: false,
+        }
+    }
+
+    pub fn active_command(
+        &self,
+    ) -> Option<CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>> {
+        self.active_commands
+            .front()
+            .map(|active_command| active_command.command)
+    }
+
+    pub fn axes(&self) -> &AxisSet {
+        &self.axes
+    }
+
+    fn elapsed_since(&mut self, instant: TimerInstant<TIMER_HZ>) -> TimerDuration<TIMER_HZ> {
+        let now = self.timer.now();
+
+        // https://playground.arduino.cc/Code/TimingRollover/
+        TimerDuration::<TIMER_HZ>::from_ticks(now.ticks().wrapping_sub(instant.ticks()))
+    }
+
+    fn sense_all(
+        &mut self,
+    ) -> Result<(), RunnerErrorAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>>
+    {
+        for id in SwitchSet::IDS {
+            self.switches
+                .sense(id)
+                .map_err(|err| RunnerError::Switch(*id, err))?;
+        }
+
+        Ok(())
+    }
+
+    fn retry(&mut self) {
+        let started_at = self.timer.now();
+
+        for active_command in self.active_commands.iter_mut() {
+            if !active_command.is_failed {
+                continue;
+            }
+
+            match active_command.command {
+                Command::Led(id, action) => self.leds.run(&id, &action),
+                Command::Axis(id, _) => {
+                    self.axes.cancel(&id);
+                    active_command.is_retrying = true;
+                }
+                Command::Motion(action) => {
+                    for id in action.axes() {
+                        self.axes.cancel(&id);
+                    }
+                    active_command.is_retrying = true;
+                }
+                Command::Spindle(id, action) => self.spindles.run(&id, &action),
+                Command::Extra(command) => self.extras.run(&command),
+                Command::Wait { .. }
+                | Command::WaitSwitch { .. }
+                | Command::GroupStart
+                | Command::GroupEnd
+                | Command::Label { .. }
+                | Command::Call { .. }
+                | Command::Return
+                | Command::Jump { .. }
+                | Command::RepeatStart { .. }
+                | Command::RepeatEnd
+                | Command::Timeout { .. } => {}
+            }
+
+            active_command.started_at = started_at;
+            active_command.is_failed = false;
+        }
+
+        // the overflowed command never ran, so its motion is planned from where the axes are now
+        if let Some(mut active_command) = self.overflowed_command.take() {
+            if let Command::Motion(action) = active_command.command {
+                active_command.motion = Some(action.segments(|id| self.axes.status(id)));
+            }
+            active_command.started_at = started_at;
+            active_command.is_failed = false;
+            self.start(active_command);
+        }
+    }
+
+    fn start(
+        &mut self,
+        active_command: ActiveCommandAlias<
+            TIMER_HZ,
+            LedSet,
+            AxisSet,
+            SpindleSet,
+            SwitchSet,
+            ExtraSets,
+        >,
+    ) {
+        let mut active_command = active_command;
+        if let Command::Axis(id, action) = active_command.command {
+            active_command.axis_action =
+                Some(Self::absolute_axis_action(self.axes.status(&id), action));
+        }
+
+        let command = active_command.command;
+        let motion = active_command.motion;
+        if let Err(active_command) = self.active_commands.push_back(active_command) {
+            self.overflowed_command = Some(active_command);
+            return;
+        }
+
+        match command {
+            Command::Led(id, action) => self.leds.run(&id, &action),
+            Command::Axis(id, action) => self.axes.run(&id, &action),
+            Command::Motion(_) => {
+                if let Some(Ok(motion)) = motion {
+                    Self::run_motion(&mut self.axes, &motion.current());
+                }
+            }
+            Command::Spindle(id, action) => self.spindles.run(&id, &action),
+            Command::Extra(command) => self.extras.run(&command),
+            Command::Wait { .. }
+            | Command::WaitSwitch { .. }
+            | Command::GroupStart
+            | Command::GroupEnd
+            | Command::Label { .. }
+            | Command::Call { .. }
+            | Command::Return
+            | Command::Jump { .. }
+            | Command::RepeatStart { .. }
+            | Command::RepeatEnd
+            | Command::Timeout { .. } => {}
+        }
+    }
+
+    fn requeue(
+        &mut self,
+        active_command: ActiveCommandAlias<
+            TIMER_HZ,
+            LedSet,
+            AxisSet,
+            SpindleSet,
+            SwitchSet,
+            ExtraSets,
+        >,
+    ) {
+        if let Err(active_command) = self.active_commands.push_back(active_command) {
+            self.overflowed_command = Some(active_command);
+        }
+    }
+
+    // a relative move from where the axis is now, so running it again doesn't go twice as far
+    fn absolute_axis_action(status: AxisStatus, action: AxisAction) -> AxisAction {
+        match action {
+            AxisAction::MoveRelative {
+                max_velocity_in_millimeters_per_sec,
+                distance_in_millimeters,
+            } => AxisAction::MoveCoordinated {
+                max_velocity_in_millimeters_per_sec,
+                max_acceleration_in_millimeters_per_sec_per_sec: status
+                    .max_acceleration_in_millimeters_per_sec_per_sec,
+                max_jerk_in_millimeters_per_sec_per_sec_per_sec: None,
+                position_in_millimeters: status.logical_position_in_millimeters
+                    + distance_in_millimeters,
+                is_relative: true,
+            },
+            action => action,
+        }
+    }
+
+    fn run_motion(axes: &mut AxisSet, action: &MotionAction<AxisSet::Id>) {
+        let axis_actions = action.plan(|id| axes.status(id));
+        for (id, axis_action) in axis_actions.iter().flatten() {
+            axes.run(id, axis_action);
+        }
+    }
+
+    fn poll_motion(
+        &mut self,
+        action: &MotionAction<AxisSet::Id>,
+    ) -> RunnerPollAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets> {
+        let mut is_ready = true;
+
+        for id in action.axes() {
+            match self.axes.poll(&id) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(RunnerError::Axis(id, err))),
+                Poll::Pending => is_ready = false,
+            }
+        }
+
+        if is_ready {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+
+    fn poll_all(
+        &mut self,
+    ) -> RunnerPollAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets> {
+        let mut is_ready = true;
+
+        for id in LedSet::IDS {
+            match self.leds.poll(id) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(RunnerError::Led(*id, err))),
+                Poll::Pending => is_ready = false,
+            }
+        }
+
+        for id in AxisSet::IDS {
+            match self.axes.poll(id) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(RunnerError::Axis(*id, err))),
+                Poll::Pending => is_ready = false,
+            }
+        }
+
+        for id in SpindleSet::IDS {
+            match self.spindles.poll(id) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(RunnerError::Spindle(*id, err))),
+                Poll::Pending => is_ready = false,
+            }
+        }
+
+        match self.extras.poll_all() {
+            Poll::Ready(Ok(())) => {}
+            Poll::Ready(Err(err)) => return Poll::Ready(Err(RunnerError::Extra(err))),
+            Poll::Pending => is_ready = false,
+        }
+
+        if is_ready {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
         }
     }
 }
@@ -60,91 +484,455 @@ where
 pub enum RunnerAction<Command> {
     Run(Command),
     Reset,
+    Retry,
 }
 
 #[derive(Clone, Copy, Debug)]
-pub enum RunnerError<LedId, LedSetError, AxisId, AxisSetError, SpindleId, SpindleSetError>
-where
+pub enum RunnerError<
+    const TIMER_HZ: u32,
+    LedId,
+    LedSetError,
+    AxisId,
+    AxisSetError,
+    SpindleId,
+    SpindleSetError,
+    SwitchId,
+    SwitchSetError,
+    ExtraCommand = (),
+    ExtraSetsError = (),
+> where
     LedId: Debug + Format,
     LedSetError: Debug,
     AxisId: Debug + Format,
     AxisSetError: Debug,
     SpindleId: Debug + Format,
     SpindleSetError: Debug,
+    SwitchId: Debug + Format,
+    SwitchSetError: Debug,
+    ExtraCommand: Debug + Format,
+    ExtraSetsError: Debug,
 {
     Led(LedId, LedSetError),
     Axis(AxisId, AxisSetError),
     Spindle(SpindleId, SpindleSetError),
+    Switch(SwitchId, SwitchSetError),
+    Extra(ExtraSetsError),
+    Motion(MotionError),
+    Timeout(Command<TIMER_HZ, LedId, AxisId, SpindleId, SwitchId, ExtraCommand>),
+    TooManyActiveCommands(Command<TIMER_HZ, LedId, AxisId, SpindleId, SwitchId, ExtraCommand>),
+    Flow(CommandFlowError),
 }
 
-impl<const LED_TIMER_HZ: u32, const ACTIVE_COMMMANDS_COUNT: usize, LedSet, AxisSet, SpindleSet>
-    Actuator for Runner<LED_TIMER_HZ, ACTIVE_COMMMANDS_COUNT, LedSet, AxisSet, SpindleSet>
+impl<
+        const TIMER_HZ: u32,
+        LedId,
+        LedSetError,
+        AxisId,
+        AxisSetError,
+        SpindleId,
+        SpindleSetError,
+        SwitchId,
+        SwitchSetError,
+        ExtraCommand,
+        ExtraSetsError,
+    > From<CommandFlowError>
+    for RunnerError<
+        TIMER_HZ,
+        LedId,
+        LedSetError,
+        AxisId,
+        AxisSetError,
+        SpindleId,
+        SpindleSetError,
+        SwitchId,
+        SwitchSetError,
+        ExtraCommand,
+        ExtraSetsError,
+    >
 where
-    LedSet: ActuatorSet<Action = LedAction<LED_TIMER_HZ>>,
-    AxisSet: ActuatorSet<Action = AxisAction>,
-    SpindleSet: ActuatorSet<Action = SpindleAction>,
+    LedId: Debug + Format,
+    LedSetError: Debug,
+    AxisId: Debug + Format,
+    AxisSetError: Debug,
+    SpindleId: Debug + Format,
+    SpindleSetError: Debug,
+    SwitchId: Debug + Format,
+    SwitchSetError: Debug,
+    ExtraCommand: Debug + Format,
+    ExtraSetsError: Debug,
 {
-    type Action = RunnerAction<Command<LED_TIMER_HZ, LedSet::Id, AxisSet::Id, SpindleSet::Id>>;
-    type Error = RunnerError<
-        LedSet::Id,
-        LedSet::Error,
-        AxisSet::Id,
-        AxisSet::Error,
-        SpindleSet::Id,
-        SpindleSet::Error,
+    fn from(err: CommandFlowError) -> Self {
+        RunnerError::Flow(err)
+    }
+}
+
+pub type RunnerErrorAlias<const TIMER_HZ: u32, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets> =
+    RunnerError<
+        TIMER_HZ,
+        <LedSet as ActuatorSet>::Id,
+        <LedSet as ActuatorSet>::Error,
+        <AxisSet as ActuatorSet>::Id,
+        <AxisSet as ActuatorSet>::Error,
+        <SpindleSet as ActuatorSet>::Id,
+        <SpindleSet as ActuatorSet>::Error,
+        <SwitchSet as SensorSet>::Id,
+        <SwitchSet as SensorSet>::Error,
+        <ExtraSets as ActuatorSetList>::Command,
+        <ExtraSets as ActuatorSetList>::Error,
     >;
 
+impl<
+        const TIMER_HZ: u32,
+        const ACTIVE_COMMMANDS_COUNT: usize,
+        LedSet,
+        AxisSet,
+        SpindleSet,
+        SwitchSet,
+        ExtraSets,
+    > Actuator
+    for Runner<TIMER_HZ, ACTIVE_COMMMANDS_COUNT, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>
+where
+    LedSet: ActuatorSet<Action = LedAction<TIMER_HZ>>,
+    AxisSet: ActuatorSetStatus<AxisStatus, Action = AxisAction>,
+    SpindleSet: ActuatorSet<Action = SpindleAction>,
+    SwitchSet: SensorSet<Message = SwitchUpdate>,
+    ExtraSets: ActuatorSetList,
+{
+    type Action =
+        RunnerAction<CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>>;
+    type Error = RunnerErrorAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>;
+
     fn run(
         &mut self,
-        action: &RunnerAction<Command<LED_TIMER_HZ, LedSet::Id, AxisSet::Id, SpindleSet::Id>>,
+        action: &RunnerAction<
+            CommandAlias<TIMER_HZ, LedSet, AxisSet, SpindleSet, SwitchSet, ExtraSets>,
+        >,
     ) {
         match action {
             RunnerAction::Run(command) => {
-                match command {
-                    Command::Led(id, action) => self.leds.run(id, action),
-                    Command::Axis(id, action) => self.axes.run(id, action),
-                    Command::Spindle(id, action) => self.spindles.run(id, action),
+                if command.flow() != CommandFlow::Run {
+                    return;
                 }
 
-                self.active_commands.push_back(*command).unwrap();
+                // the timed command is run in place of its timeout, the shortest timeout applies
+                let (command, mut timeout) = match *command {
+                    Command::Timeout { duration, command } => (command.into(), Some(duration)),
+                    command => (command, None),
+                };
+                if let Command::WaitSwitch {
+                    timeout: Some(switch_timeout),
+                    ..
+                } = command
+                {
+                    timeout =
+                        Some(timeout.map_or(switch_timeout, |timeout| switch_timeout.min(timeout)));
+                }
+
+                let started_at = self.timer.now();
+                let motion = match command {
+                    Command::Motion(action) => Some(action.segments(|id| self.axes.status(id))),
+                    _ => None,
+                };
+
+                self.start(ActiveCommand {
+                    command,
+                    started_at,
+                    timeout,
+                    is_failed: false,
+                    is_retrying: false,
+                    motion,
+                    axis_action: None,
+                });
             }
-            RunnerAction::Reset => self.active_commands.clear(),
+            RunnerAction::Reset => self.cancel(),
+            RunnerAction::Retry => self.retry(),
         }
     }
 
     fn poll(&mut self) -> Poll<Result<(), Self::Error>> {
+        if let Err(err) = self.sense_all() {
+            return Poll::Ready(Err(err));
+        }
+
+        if self.is_emergency_stopping {
+            match self.poll_all() {
+                Poll::Ready(Ok(())) => self.is_emergency_stopping = false,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        if let Some(active_command) = self.overflowed_command.as_mut() {
+            if !active_command.is_failed {
+                active_command.is_failed = true;
+
+                return Poll::Ready(Err(RunnerError::TooManyActiveCommands(
+                    active_command.command,
+                )));
+            }
+        }
+
         let num_commands = self.active_commands.len();
         for _command_index in 0..num_commands {
-            let command = self.active_commands.pop_front().unwrap();
-            let result = match command {
+            let Some(mut active_command) = self.active_commands.pop_front() else {
+                break;
+            };
+
+            if active_command.is_retrying {
+                let result = match active_command.command {
+                    Command::Axis(id, _) => self
+                        .axes
+                        .poll(&id)
+                        .map_err(|err| RunnerError::Axis(id, err)),
+                    Command::Motion(action) => self.poll_motion(&action),
+                    _ => Poll::Ready(Ok(())),
+                };
+                match result {
+                    Poll::Ready(Ok(())) => {
+                        active_command.is_retrying = false;
+                        active_command.started_at = self.timer.now();
+                        match active_command.command {
+                            Command::Axis(id, action) => self
+                                .axes
+                                .run(&id, &active_command.axis_action.unwrap_or(action)),
+                            // a motion carries on from the segment it failed on
+                            Command::Motion(_) => {
+                                if let Some(Ok(motion)) = active_command.motion {
+                                    Self::run_motion(&mut self.axes, &motion.current());
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    Poll::Ready(Err(err)) => {
+                        active_command.is_retrying = false;
+                        active_command.is_failed = true;
+                        self.requeue(active_command);
+
+                        return Poll::Ready(Err(err));
+                    }
+                    Poll::Pending => {}
+                }
+                self.requeue(active_command);
+                continue;
+            }
+
+            let result = match active_command.command {
                 Command::Led(id, _) => self.leds.poll(&id).map_err(|err| RunnerError::Led(id, err)),
                 Command::Axis(id, _) => self
                     .axes
                     .poll(&id)
                     .map_err(|err| RunnerError::Axis(id, err)),
+                Command::Motion(action) => match active_command.motion {
+                    // nothing was started, so it fails once and waits to be cancelled or retried
+                    Some(Err(_)) if active_command.is_failed => Poll::Pending,
+                    Some(Err(err)) => Poll::Ready(Err(RunnerError::Motion(err))),
+                    _ => match self.poll_motion(&action) {
+                        Poll::Ready(Ok(())) if self.paused_at.is_some() => Poll::Pending,
+                        // the next segment starts from rest, where the last one stopped
+                        Poll::Ready(Ok(())) => match active_command
+                            .motion
+                            .as_mut()
+                            .and_then(|motion| motion.as_mut().ok())
+                            .and_then(MotionSegments::advance)
+                        {
+                            Some(segment) => {
+                                Self::run_motion(&mut self.axes, &segment);
+                                Poll::Pending
+                            }
+                            None => Poll::Ready(Ok(())),
+                        },
+                        result => result,
+                    },
+                },
                 Command::Spindle(id, _) => self
                     .spindles
                     .poll(&id)
                     .map_err(|err| RunnerError::Spindle(id, err)),
+                Command::Extra(command) => self.extras.poll(&command).map_err(RunnerError::Extra),
+                Command::Wait { duration } => {
+                    if self.paused_at.is_none()
+                        && self.elapsed_since(active_command.started_at) >= duration
+                    {
+                        Poll::Ready(Ok(()))
+                    } else {
+                        Poll::Pending
+                    }
+                }
+                Command::WaitSwitch { switch, status, .. } => {
+                    let latest_status = self.switches.latest(&switch).map(|update| update.status);
+
+                    if self.paused_at.is_none() && latest_status == Some(status) {
+                        Poll::Ready(Ok(()))
+                    } else {
+                        Poll::Pending
+                    }
+                }
+                Command::GroupStart
+                | Command::GroupEnd
+                | Command::Label { .. }
+                | Command::Call { .. }
+                | Command::Return
+                | Command::Jump { .. }
+                | Command::RepeatStart { .. }
+                | Command::RepeatEnd
+                | Command::Timeout { .. } => Poll::Ready(Ok(())),
+            };
+
+            // time out only once, the command stays active until it's cancelled or retried
+            let result = match (result, active_command.timeout) {
+                (Poll::Pending, Some(timeout))
+                    if !active_command.is_failed
+                        && self.paused_at.is_none()
+                        && self.elapsed_since(active_command.started_at) >= timeout =>
+                {
+                    Poll::Ready(Err(RunnerError::Timeout(active_command.command)))
+                }
+                (result, _) => result,
             };
 
             match result {
                 Poll::Ready(Ok(())) => {}
                 Poll::Ready(Err(err)) => {
-                    self.active_commands.push_back(command).unwrap();
+                    active_command.is_failed = true;
+                    self.requeue(active_command);
 
                     return Poll::Ready(Err(err.into()));
                 }
-                Poll::Pending => {
-                    self.active_commands.push_back(command).unwrap();
-                }
+                Poll::Pending => self.requeue(active_command),
             }
         }
 
-        if self.active_commands.len() == 0 {
+        if self.active_commands.is_empty() && self.overflowed_command.is_none() {
             Poll::Ready(Ok(()))
         } else {
             Poll::Pending
         }
     }
+
+    fn pause(&mut self) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(self.timer.now());
+        }
+
+        for active_command in self.active_commands.iter() {
+            match active_command.command {
+                Command::Led(id, _) => self.leds.pause(&id),
+                Command::Axis(id, _) => self.axes.pause(&id),
+                Command::Motion(action) => {
+                    for id in action.axes() {
+                        self.axes.pause(&id);
+                    }
+                }
+                Command::Spindle(id, _) => self.spindles.pause(&id),
+                Command::Extra(command) => self.extras.pause(&command),
+                Command::Wait { .. }
+                | Command::WaitSwitch { .. }
+                | Command::GroupStart
+                | Command::GroupEnd
+                | Command::Label { .. }
+                | Command::Call { .. }
+                | Command::Return
+                | Command::Jump { .. }
+                | Command::RepeatStart { .. }
+                | Command::RepeatEnd
+                | Command::Timeout { .. } => {}
+            }
+        }
+    }
+
+    fn resume(&mut self) {
+        if let Some(paused_at) = self.paused_at.take() {
+            // don't count the time spent paused
+            let paused_ticks = self.elapsed_since(paused_at).ticks();
+            for active_command in self.active_commands.iter_mut() {
+                active_command.started_at = TimerInstant::<TIMER_HZ>::from_ticks(
+                    active_command.started_at.ticks().wrapping_add(paused_ticks),
+                );
+            }
+        }
+
+        for active_command in self.active_commands.iter() {
+            match active_command.command {
+                Command::Led(id, _) => self.leds.resume(&id),
+                Command::Axis(id, _) => self.axes.resume(&id),
+                Command::Motion(action) => {
+                    for id in action.axes() {
+                        self.axes.resume(&id);
+                    }
+                }
+                Command::Spindle(id, _) => self.spindles.resume(&id),
+                Command::Extra(command) => self.extras.resume(&command),
+                Command::Wait { .. }
+                | Command::WaitSwitch { .. }
+                | Command::GroupStart
+                | Command::GroupEnd
+                | Command::Label { .. }
+                | Command::Call { .. }
+                | Command::Return
+                | Command::Jump { .. }
+                | Command::RepeatStart { .. }
+                | Command::RepeatEnd
+                | Command::Timeout { .. } => {}
+            }
+        }
+    }
+
+    fn cancel(&mut self) {
+        self.paused_at = None;
+        self.overflowed_command = None;
+
+        // cancelled commands stay active until their actuators have stopped
+        let num_commands = self.active_commands.len();
+        for _command_index in 0..num_commands {
+            let Some(mut active_command) = self.active_commands.pop_front() else {
+                break;
+            };
+            active_command.timeout = None;
+            active_command.is_retrying = false;
+            match active_command.command {
+                Command::Led(id, _) => self.leds.cancel(&id),
+                Command::Axis(id, _) => self.axes.cancel(&id),
+                Command::Motion(action) => {
+                    active_command.motion = None;
+                    for id in action.axes() {
+                        self.axes.cancel(&id);
+                    }
+                }
+                Command::Spindle(id, _) => self.spindles.cancel(&id),
+                Command::Extra(command) => self.extras.cancel(&command),
+                Command::Wait { .. }
+                | Command::WaitSwitch { .. }
+                | Command::GroupStart
+                | Command::GroupEnd
+                | Command::Label { .. }
+                | Command::Call { .. }
+                | Command::Return
+                | Command::Jump { .. }
+                | Command::RepeatStart { .. }
+                | Command::RepeatEnd
+                | Command::Timeout { .. } => continue,
+            }
+            self.requeue(active_command);
+        }
+    }
+
+    fn emergency_stop(&mut self) {
+        self.active_commands.clear();
+        self.overflowed_command = None;
+        self.paused_at = None;
+
+        for id in LedSet::IDS {
+            self.leds.emergency_stop(id);
+        }
+        for id in AxisSet::IDS {
+            self.axes.emergency_stop(id);
+        }
+        for id in SpindleSet::IDS {
+            self.spindles.emergency_stop(id);
+        }
+        self.extras.emergency_stop_all();
+
+        self.is_emergency_stopping = true;
+    }
 }
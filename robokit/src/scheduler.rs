@@ -9,149 +9,1090 @@ use crate::{actuators::Actuator, runner::RunnerAction};
 pub enum SchedulerState {
     Idle,
     Start,
+    StartReset,
     StartLoop,
     Run { command_index: usize },
+    RunGroup { command_index: usize },
     RunLoop { command_index: usize },
+    Step { command_index: usize },
+    Paused { command_index: usize },
     Stop,
+    StopReset,
     StopLoop,
+    ErrorReset,
+    ErrorLoop,
+    Faulted,
+    Estop,
+}
+
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum SchedulerPhase {
+    Idle,
+    Start,
+    Run,
+    Step,
+    Paused,
+    Stop,
+    Error,
+    Faulted,
+    Estop,
+}
+
+impl From<SchedulerState> for SchedulerPhase {
+    fn from(state: SchedulerState) -> Self {
+        match state {
+            SchedulerState::Idle => SchedulerPhase::Idle,
+            SchedulerState::Start | SchedulerState::StartReset | SchedulerState::StartLoop => {
+                SchedulerPhase::Start
+            }
+            SchedulerState::Run { .. }
+            | SchedulerState::RunGroup { .. }
+            | SchedulerState::RunLoop { .. } => SchedulerPhase::Run,
+            SchedulerState::Step { .. } => SchedulerPhase::Step,
+            SchedulerState::Paused { .. } => SchedulerPhase::Paused,
+            SchedulerState::Stop | SchedulerState::StopReset | SchedulerState::StopLoop => {
+                SchedulerPhase::Stop
+            }
+            SchedulerState::ErrorReset | SchedulerState::ErrorLoop => SchedulerPhase::Error,
+            SchedulerState::Faulted => SchedulerPhase::Faulted,
+            SchedulerState::Estop => SchedulerPhase::Estop,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Format)]
+pub enum SchedulerEvent<'a, Command, Error> {
+    Phase(SchedulerPhase),
+    Started {
+        command_index: usize,
+        command: Command,
+    },
+    Finished {
+        command_index: usize,
+    },
+    Cycle {
+        cycle_count: usize,
+    },
+    Retry {
+        retry_count: usize,
+    },
+    Error(&'a Error),
+}
+
+pub trait SchedulerObserver<Command, Error> {
+    fn notify(&mut self, event: SchedulerEvent<'_, Command, Error>);
+}
+
+impl<Command, Error> SchedulerObserver<Command, Error> for () {
+    fn notify(&mut self, _event: SchedulerEvent<'_, Command, Error>) {}
+}
+
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum RunMode {
+    Once,
+    Repeat { count: usize },
+    Continuous,
+}
+
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    Halt,
+    RunErrorCommands,
+    // run the failed commands again up to `count` times, then as `RunErrorCommands`
+    Retry { count: usize },
+}
+
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum CommandFlow {
+    Run,
+    GroupStart,
+    GroupEnd,
+    Label { label: &'static str },
+    Call { label: &'static str },
+    // a return outside of a call ends the run commands, so subroutines can follow the program
+    Return,
+    Jump { label: &'static str },
+    RepeatStart { count: usize },
+    RepeatEnd,
+}
+
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum CommandFlowError {
+    UnknownLabel { label: &'static str },
+    CallStackOverflow,
+    UnexpectedRepeatEnd,
+    UnexpectedGroupEnd,
+    NotSeekable,
+    // groups don't nest, and can't hold labels, calls, returns, jumps or repeats
+    FlowInGroup,
+}
+
+pub const CALL_STACK_SIZE: usize = 8;
+
+const PROGRAM_STEPS_MAX: usize = 100_000;
+
+#[derive(Clone, Copy, Debug, Format)]
+enum CallFrame {
+    Call {
+        return_index: usize,
+    },
+    Repeat {
+        start_index: usize,
+        remaining_count: usize,
+    },
+}
+
+struct CallStack {
+    frames: Vec<CallFrame, CALL_STACK_SIZE>,
+}
+
+impl CallStack {
+    fn new() -> Self {
+        Self { frames: Vec::new() }
+    }
+
+    fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    fn next<Command, RunCommands>(
+        &mut self,
+        run_commands: &mut RunCommands,
+        command_index: usize,
+        flow: CommandFlow,
+    ) -> Result<Option<usize>, CommandFlowError>
+    where
+        Command: ProgramCommand,
+        RunCommands: CommandSource<Command>,
+    {
+        match flow {
+            CommandFlow::Run
+            | CommandFlow::GroupStart
+            | CommandFlow::GroupEnd
+            | CommandFlow::Label { .. } => Ok(Some(command_index + 1)),
+            CommandFlow::Call { label } => {
+                let label_index = find_label(run_commands, label)?;
+                self.push(CallFrame::Call {
+                    return_index: command_index + 1,
+                })?;
+
+                Ok(Some(label_index))
+            }
+            CommandFlow::Return => {
+                while let Some(frame) = self.frames.pop() {
+                    // returning from within a repeat leaves the repeat
+                    if let CallFrame::Call { return_index } = frame {
+                        return Ok(Some(return_index));
+                    }
+                }
+
+                Ok(None)
+            }
+            CommandFlow::Jump { label } => Ok(Some(find_label(run_commands, label)?)),
+            CommandFlow::RepeatStart { count } => {
+                if run_commands.total().is_none() {
+                    return Err(CommandFlowError::NotSeekable);
+                }
+
+                if count == 0 {
+                    return Ok(find_repeat_end(run_commands, command_index)
+                        .map(|repeat_end_index| repeat_end_index + 1));
+                }
+
+                self.push(CallFrame::Repeat {
+                    start_index: command_index + 1,
+                    remaining_count: count - 1,
+                })?;
+
+                Ok(Some(command_index + 1))
+            }
+            CommandFlow::RepeatEnd => match self.frames.last_mut() {
+                Some(CallFrame::Repeat {
+                    start_index,
+                    remaining_count,
+                }) => {
+                    if *remaining_count > 0 {
+                        *remaining_count -= 1;
+
+                        Ok(Some(*start_index))
+                    } else {
+                        self.frames.pop();
+
+                        Ok(Some(command_index + 1))
+                    }
+                }
+                _ => Err(CommandFlowError::UnexpectedRepeatEnd),
+            },
+        }
+    }
+
+    fn push(&mut self, frame: CallFrame) -> Result<(), CommandFlowError> {
+        self.frames
+            .push(frame)
+            .map_err(|_| CommandFlowError::CallStackOverflow)
+    }
+}
+
+fn find_label<Command, RunCommands>(
+    run_commands: &mut RunCommands,
+    label: &'static str,
+) -> Result<usize, CommandFlowError>
+where
+    Command: ProgramCommand,
+    RunCommands: CommandSource<Command>,
+{
+    let total = run_commands.total().ok_or(CommandFlowError::NotSeekable)?;
+
+    (0..total)
+        .find(|command_index| match run_commands.get(*command_index) {
+            Poll::Ready(Some(command)) => command.flow() == CommandFlow::Label { label },
+            _ => false,
+        })
+        .ok_or(CommandFlowError::UnknownLabel { label })
+}
+
+fn find_repeat_end<Command, RunCommands>(
+    run_commands: &mut RunCommands,
+    repeat_start_index: usize,
+) -> Option<usize>
+where
+    Command: ProgramCommand,
+    RunCommands: CommandSource<Command>,
+{
+    let mut depth = 0;
+    let mut command_index = repeat_start_index + 1;
+
+    while let Poll::Ready(Some(command)) = run_commands.get(command_index) {
+        match command.flow() {
+            CommandFlow::RepeatStart { .. } => depth += 1,
+            CommandFlow::RepeatEnd if depth == 0 => return Some(command_index),
+            CommandFlow::RepeatEnd => depth -= 1,
+            _ => {}
+        }
+
+        command_index += 1;
+    }
+
+    None
+}
+
+pub trait ProgramCommand {
+    fn flow(&self) -> CommandFlow;
+}
+
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum ProgramPart {
+    Start,
+    Run,
+    Stop,
+}
+
+#[derive(Clone, Debug)]
+pub struct Program<
+    Command,
+    RunCommands,
+    const START_COMMANDS_COUNT: usize,
+    const STOP_COMMANDS_COUNT: usize,
+> {
+    pub id: &'static str,
+    pub run_commands: RunCommands,
+    pub start_commands: Vec<Command, START_COMMANDS_COUNT>,
+    pub stop_commands: Vec<Command, STOP_COMMANDS_COUNT>,
+}
+
+impl<
+        Command,
+        const RUN_COMMANDS_COUNT: usize,
+        const START_COMMANDS_COUNT: usize,
+        const STOP_COMMANDS_COUNT: usize,
+    > Program<Command, Vec<Command, RUN_COMMANDS_COUNT>, START_COMMANDS_COUNT, STOP_COMMANDS_COUNT>
+where
+    Command: Copy,
+{
+    pub fn new(
+        id: &'static str,
+        start_commands: &[Command],
+        run_commands: &[Command],
+        stop_commands: &[Command],
+    ) -> Result<Self, ProgramBuildError> {
+        Ok(Self {
+            id,
+            run_commands: Vec::from_slice(run_commands)
+                .map_err(|()| ProgramBuildError::TooManyCommands(ProgramPart::Run))?,
+            start_commands: Vec::from_slice(start_commands)
+                .map_err(|()| ProgramBuildError::TooManyCommands(ProgramPart::Start))?,
+            stop_commands: Vec::from_slice(stop_commands)
+                .map_err(|()| ProgramBuildError::TooManyCommands(ProgramPart::Stop))?,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum ProgramBuildError {
+    TooManyCommands(ProgramPart),
+}
+
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum ProgramSelectError {
+    UnknownProgram,
+    NotIdle,
+}
+
+pub trait CommandSource<Command> {
+    fn get(&mut self, index: usize) -> Poll<Option<Command>>;
+    fn total(&self) -> Option<usize>;
+}
+
+impl<Command, const COMMANDS_COUNT: usize> CommandSource<Command> for Vec<Command, COMMANDS_COUNT>
+where
+    Command: Copy,
+{
+    fn get(&mut self, index: usize) -> Poll<Option<Command>> {
+        Poll::Ready(self.as_slice().get(index).copied())
+    }
+
+    fn total(&self) -> Option<usize> {
+        Some(self.len())
+    }
 }
 
 pub struct Scheduler<
     Command,
     Runner,
-    const RUN_COMMANDS_COUNT: usize,
+    RunCommands,
     const START_COMMANDS_COUNT: usize,
     const STOP_COMMANDS_COUNT: usize,
+    const ERROR_COMMANDS_COUNT: usize,
+    Observer,
 > where
     Runner: Actuator<Action = RunnerAction<Command>>,
+    RunCommands: CommandSource<Command>,
 {
     runner: Runner,
+    observer: Observer,
     state: SchedulerState,
-    run_commands: Vec<Command, RUN_COMMANDS_COUNT>,
+    phase: SchedulerPhase,
+    resume_state: SchedulerState,
+    run_commands: RunCommands,
+    call_stack: CallStack,
     start_commands: Vec<Command, START_COMMANDS_COUNT>,
     stop_commands: Vec<Command, STOP_COMMANDS_COUNT>,
+    error_commands: Vec<Command, ERROR_COMMANDS_COUNT>,
+    run_mode: RunMode,
+    error_policy: ErrorPolicy,
+    is_step_mode: bool,
+    cycle_count: usize,
+    retry_count: usize,
+    error: Option<Runner::Error>,
 }
 
 impl<
         Command,
         Runner,
-        const RUN_COMMANDS_COUNT: usize,
+        RunCommands,
         const START_COMMANDS_COUNT: usize,
         const STOP_COMMANDS_COUNT: usize,
-    > Scheduler<Command, Runner, RUN_COMMANDS_COUNT, START_COMMANDS_COUNT, STOP_COMMANDS_COUNT>
+        const ERROR_COMMANDS_COUNT: usize,
+    >
+    Scheduler<
+        Command,
+        Runner,
+        RunCommands,
+        START_COMMANDS_COUNT,
+        STOP_COMMANDS_COUNT,
+        ERROR_COMMANDS_COUNT,
+        (),
+    >
 where
-    Command: Copy + Debug + Format,
+    Command: Copy + Debug + Format + ProgramCommand,
     Runner: Actuator<Action = RunnerAction<Command>>,
+    RunCommands: CommandSource<Command>,
 {
     pub fn new(
         runner: Runner,
-        run_commands: Vec<Command, RUN_COMMANDS_COUNT>,
+        run_commands: RunCommands,
         start_commands: Vec<Command, START_COMMANDS_COUNT>,
         stop_commands: Vec<Command, STOP_COMMANDS_COUNT>,
+        error_commands: Vec<Command, ERROR_COMMANDS_COUNT>,
+        run_mode: RunMode,
+        error_policy: ErrorPolicy,
     ) -> Self {
         Self {
             runner,
+            observer: (),
             state: SchedulerState::Idle,
+            phase: SchedulerPhase::Idle,
+            resume_state: SchedulerState::Idle,
             run_commands,
+            call_stack: CallStack::new(),
             start_commands,
             stop_commands,
+            error_commands,
+            run_mode,
+            error_policy,
+            is_step_mode: false,
+            cycle_count: 0,
+            retry_count: 0,
+            error: None,
+        }
+    }
+
+    pub fn with_observer<Observer>(
+        self,
+        observer: Observer,
+    ) -> Scheduler<
+        Command,
+        Runner,
+        RunCommands,
+        START_COMMANDS_COUNT,
+        STOP_COMMANDS_COUNT,
+        ERROR_COMMANDS_COUNT,
+        Observer,
+    >
+    where
+        Observer: SchedulerObserver<Command, Runner::Error>,
+    {
+        Scheduler {
+            runner: self.runner,
+            observer,
+            state: self.state,
+            phase: self.phase,
+            resume_state: self.resume_state,
+            run_commands: self.run_commands,
+            call_stack: self.call_stack,
+            start_commands: self.start_commands,
+            stop_commands: self.stop_commands,
+            error_commands: self.error_commands,
+            run_mode: self.run_mode,
+            error_policy: self.error_policy,
+            is_step_mode: self.is_step_mode,
+            cycle_count: self.cycle_count,
+            retry_count: self.retry_count,
+            error: self.error,
+        }
+    }
+}
+
+impl<
+        Command,
+        Runner,
+        RunCommands,
+        const START_COMMANDS_COUNT: usize,
+        const STOP_COMMANDS_COUNT: usize,
+        const ERROR_COMMANDS_COUNT: usize,
+        Observer,
+    >
+    Scheduler<
+        Command,
+        Runner,
+        RunCommands,
+        START_COMMANDS_COUNT,
+        STOP_COMMANDS_COUNT,
+        ERROR_COMMANDS_COUNT,
+        Observer,
+    >
+where
+    Command: Copy + Debug + Format + ProgramCommand,
+    Runner: Actuator<Action = RunnerAction<Command>>,
+    Runner::Error: From<CommandFlowError>,
+    RunCommands: CommandSource<Command>,
+    Observer: SchedulerObserver<Command, Runner::Error>,
+{
+    pub fn cycle_count(&self) -> usize {
+        self.cycle_count
+    }
+
+    pub fn phase(&self) -> SchedulerPhase {
+        self.state.into()
+    }
+
+    pub fn program(&mut self) -> impl Iterator<Item = (ProgramPart, usize, Command)> + '_ {
+        let cycles_count = match self.run_commands.total() {
+            None => 0,
+            Some(_) => match self.run_mode {
+                RunMode::Once => 1,
+                RunMode::Repeat { count } => count,
+                // a second cycle shows where relative moves drift to
+                RunMode::Continuous => 2,
+            },
+        };
+        let run_commands = &mut self.run_commands;
+        let mut call_stack = CallStack::new();
+        let mut cycle_index = 0;
+        let mut command_index = 0;
+        let mut steps_count = 0;
+
+        let start = self
+            .start_commands
+            .iter()
+            .copied()
+            .enumerate()
+            .map(|(command_index, command)| (ProgramPart::Start, command_index, command));
+        let run = core::iter::from_fn(move || loop {
+            // a jump back never ends, so only follow the program so far
+            if cycle_index >= cycles_count || steps_count >= PROGRAM_STEPS_MAX {
+                return None;
+            }
+            steps_count += 1;
+
+            let command = match run_commands.get(command_index) {
+                Poll::Ready(Some(command)) => command,
+                _ => {
+                    cycle_index += 1;
+                    command_index = 0;
+                    call_stack.clear();
+                    continue;
+                }
+            };
+
+            match command.flow() {
+                CommandFlow::Run => {
+                    command_index += 1;
+
+                    return Some((ProgramPart::Run, command_index - 1, command));
+                }
+                flow => match call_stack.next(run_commands, command_index, flow) {
+                    Ok(Some(next_command_index)) => command_index = next_command_index,
+                    Ok(None) => {
+                        cycle_index += 1;
+                        command_index = 0;
+                        call_stack.clear();
+                    }
+                    // the scheduler finds the error when it gets there
+                    Err(_) => return None,
+                },
+            }
+        });
+        let stop = self
+            .stop_commands
+            .iter()
+            .copied()
+            .enumerate()
+            .map(|(command_index, command)| (ProgramPart::Stop, command_index, command));
+
+        start.chain(run).chain(stop)
+    }
+
+    pub fn flow_error(&mut self) -> Option<(usize, CommandFlowError)> {
+        self.run_commands.total()?;
+
+        let mut call_stack = CallStack::new();
+        let mut command_index = 0;
+        let mut is_in_group = false;
+
+        for _ in 0..PROGRAM_STEPS_MAX {
+            let Poll::Ready(Some(command)) = self.run_commands.get(command_index) else {
+                return None;
+            };
+
+            let next = match (command.flow(), is_in_group) {
+                (CommandFlow::Run, _) => Ok(Some(command_index + 1)),
+                (CommandFlow::GroupStart, false) => {
+                    is_in_group = true;
+                    Ok(Some(command_index + 1))
+                }
+                (CommandFlow::GroupEnd, true) => {
+                    is_in_group = false;
+                    Ok(Some(command_index + 1))
+                }
+                (CommandFlow::GroupEnd, false) => Err(CommandFlowError::UnexpectedGroupEnd),
+                (_, true) => Err(CommandFlowError::FlowInGroup),
+                (flow, false) => call_stack.next(&mut self.run_commands, command_index, flow),
+            };
+            match next {
+                Ok(Some(next_command_index)) => command_index = next_command_index,
+                Ok(None) => return None,
+                Err(err) => return Some((command_index, err)),
+            }
+        }
+
+        None
+    }
+
+    pub fn load_program(
+        &mut self,
+        run_commands: RunCommands,
+        start_commands: Vec<Command, START_COMMANDS_COUNT>,
+        stop_commands: Vec<Command, STOP_COMMANDS_COUNT>,
+    ) -> Result<(), ProgramSelectError> {
+        if !matches!(self.state, SchedulerState::Idle) {
+            return Err(ProgramSelectError::NotIdle);
+        }
+
+        self.run_commands = run_commands;
+        self.start_commands = start_commands;
+        self.stop_commands = stop_commands;
+        self.call_stack.clear();
+
+        Ok(())
+    }
+
+    pub fn command_index(&self) -> Option<usize> {
+        match self.state {
+            SchedulerState::Run { command_index }
+            | SchedulerState::RunGroup { command_index }
+            | SchedulerState::RunLoop { command_index }
+            | SchedulerState::Step { command_index }
+            | SchedulerState::Paused { command_index } => Some(command_index),
+            _ => None,
+        }
+    }
+
+    pub fn commands_count(&self) -> Option<usize> {
+        self.run_commands.total()
+    }
+
+    pub fn runner(&self) -> &Runner {
+        &self.runner
+    }
+
+    pub fn observer(&self) -> &Observer {
+        &self.observer
+    }
+
+    pub fn observer_mut(&mut self) -> &mut Observer {
+        &mut self.observer
+    }
+
+    pub fn error(&self) -> Option<&Runner::Error> {
+        self.error.as_ref()
+    }
+
+    fn is_latched(&self) -> bool {
+        matches!(
+            self.state,
+            SchedulerState::ErrorReset
+                | SchedulerState::ErrorLoop
+                | SchedulerState::Faulted
+                | SchedulerState::Estop
+        )
+    }
+
+    fn fail(&mut self, err: Runner::Error) -> Poll<Result<(), &Runner::Error>> {
+        let is_paused = matches!(self.state, SchedulerState::Paused { .. });
+
+        if let ErrorPolicy::Retry { count } = self.error_policy {
+            if !is_paused && self.retry_count < count {
+                self.retry_count += 1;
+
+                defmt::println!("Retry: {}", self.retry_count);
+
+                self.observer.notify(SchedulerEvent::Retry {
+                    retry_count: self.retry_count,
+                });
+
+                self.runner.run(&RunnerAction::Retry);
+
+                return Poll::Pending;
+            }
+        }
+
+        self.fail_flow(err)
+    }
+
+    // a flow error is in the program itself, so it isn't retried
+    fn fail_flow(&mut self, err: Runner::Error) -> Poll<Result<(), &Runner::Error>> {
+        match self.error_policy {
+            ErrorPolicy::Halt => self.fault(err, SchedulerState::Faulted),
+            ErrorPolicy::RunErrorCommands | ErrorPolicy::Retry { .. } => {
+                self.fault(err, SchedulerState::ErrorReset)
+            }
+        }
+    }
+
+    fn fault(
+        &mut self,
+        err: Runner::Error,
+        state: SchedulerState,
+    ) -> Poll<Result<(), &Runner::Error>> {
+        defmt::println!("Fault!");
+
+        self.runner.run(&RunnerAction::Reset);
+
+        self.state = state;
+
+        let err = self.error.insert(err);
+        self.observer.notify(SchedulerEvent::Error(err));
+
+        Poll::Ready(Err(err))
+    }
+
+    fn end_cycle(&mut self) {
+        self.cycle_count += 1;
+
+        self.observer.notify(SchedulerEvent::Cycle {
+            cycle_count: self.cycle_count,
+        });
+
+        let is_done = match self.run_mode {
+            RunMode::Once => true,
+            RunMode::Repeat { count } => self.cycle_count >= count,
+            RunMode::Continuous => false,
+        };
+
+        if is_done {
+            self.state = SchedulerState::Stop;
+        } else {
+            defmt::println!("Cycle: {}", self.cycle_count);
+
+            self.call_stack.clear();
+            self.state = SchedulerState::Run { command_index: 0 };
         }
     }
 
     pub fn start(&mut self) {
+        if self.is_latched() {
+            return;
+        }
+
         self.state = SchedulerState::Start;
     }
 
     pub fn stop(&mut self) {
+        if self.is_latched() {
+            return;
+        }
+
         self.state = SchedulerState::Stop;
     }
 
+    pub fn emergency_stop(&mut self) {
+        defmt::println!("Emergency stop!");
+
+        self.runner.emergency_stop();
+
+        self.state = SchedulerState::Estop;
+    }
+
+    pub fn reset(&mut self) {
+        if let SchedulerState::Faulted | SchedulerState::Estop = self.state {
+            self.error = None;
+
+            self.state = SchedulerState::Idle;
+        }
+    }
+
+    pub fn set_step_mode(&mut self, is_step_mode: bool) {
+        self.is_step_mode = is_step_mode;
+
+        if !is_step_mode {
+            self.step();
+        }
+    }
+
+    pub fn is_step_mode(&self) -> bool {
+        self.is_step_mode
+    }
+
+    pub fn step(&mut self) {
+        if let SchedulerState::Step { command_index } = self.state {
+            self.state = SchedulerState::Run { command_index };
+        }
+    }
+
+    pub fn pause(&mut self) {
+        let command_index = match self.state {
+            SchedulerState::Run { command_index } => command_index,
+            SchedulerState::RunGroup { command_index } => command_index,
+            SchedulerState::RunLoop { command_index } => command_index,
+            _ => return,
+        };
+
+        self.runner.pause();
+
+        self.resume_state = self.state;
+        self.state = SchedulerState::Paused { command_index };
+    }
+
+    pub fn resume(&mut self) {
+        if let SchedulerState::Paused { .. } = self.state {
+            self.runner.resume();
+
+            self.state = self.resume_state;
+        }
+    }
+
     pub fn toggle(&mut self) {
         self.state = match self.state {
             SchedulerState::Idle => SchedulerState::Start,
             SchedulerState::Start => SchedulerState::Stop,
+            SchedulerState::StartReset => SchedulerState::Stop,
             SchedulerState::StartLoop => SchedulerState::Stop,
             SchedulerState::Run { .. } => SchedulerState::Stop,
+            SchedulerState::RunGroup { .. } => SchedulerState::Stop,
             SchedulerState::RunLoop { .. } => SchedulerState::Stop,
+            SchedulerState::Step { .. } => SchedulerState::Stop,
+            SchedulerState::Paused { .. } => SchedulerState::Stop,
             SchedulerState::Stop => SchedulerState::Start,
+            SchedulerState::StopReset => SchedulerState::Start,
             SchedulerState::StopLoop => SchedulerState::Start,
+            SchedulerState::ErrorReset => SchedulerState::ErrorReset,
+            SchedulerState::ErrorLoop => SchedulerState::ErrorLoop,
+            SchedulerState::Faulted => SchedulerState::Faulted,
+            SchedulerState::Estop => SchedulerState::Estop,
         };
     }
 
-    pub fn poll(&mut self) -> Poll<Result<(), Runner::Error>> {
+    // an error is returned once, when it faults the scheduler
+    pub fn poll(&mut self) -> Poll<Result<(), &Runner::Error>> {
+        let phase = self.phase();
+        if phase != self.phase {
+            self.phase = phase;
+            self.observer.notify(SchedulerEvent::Phase(phase));
+        }
+
         match self.state {
             SchedulerState::Idle => Poll::Ready(Ok(())),
             SchedulerState::Start => {
                 self.runner.run(&RunnerAction::Reset);
 
-                for command in self.start_commands.iter() {
-                    defmt::println!("Start: {}", command);
-
-                    self.runner.run(&RunnerAction::Run(*command))
-                }
+                self.cycle_count = 0;
+                self.retry_count = 0;
+                self.call_stack.clear();
 
-                self.state = SchedulerState::StartLoop;
+                self.state = SchedulerState::StartReset;
 
                 Poll::Pending
             }
-            SchedulerState::StartLoop => {
-                if let Poll::Ready(Ok(())) = self.runner.poll() {
-                    self.state = SchedulerState::Run { command_index: 0 };
+            SchedulerState::StartReset => match self.runner.poll() {
+                Poll::Ready(Ok(())) => {
+                    for command in self.start_commands.iter() {
+                        defmt::println!("Start: {}", command);
+
+                        self.runner.run(&RunnerAction::Run(*command))
+                    }
+
+                    self.state = SchedulerState::StartLoop;
+
+                    Poll::Pending
                 }
+                Poll::Ready(Err(err)) => self.fail(err),
+                Poll::Pending => Poll::Pending,
+            },
+            SchedulerState::StartLoop => match self.runner.poll() {
+                Poll::Ready(Ok(())) => {
+                    self.retry_count = 0;
 
-                Poll::Pending
-            }
+                    self.state = match self.run_mode {
+                        RunMode::Repeat { count: 0 } => SchedulerState::Stop,
+                        _ => SchedulerState::Run { command_index: 0 },
+                    };
+
+                    Poll::Pending
+                }
+                Poll::Ready(Err(err)) => self.fail(err),
+                Poll::Pending => Poll::Pending,
+            },
             SchedulerState::Run { command_index } => {
-                let command = self
-                    .run_commands
-                    .get(command_index)
-                    .expect("Unexpected run command index");
+                match self.run_commands.get(command_index) {
+                    Poll::Pending => {
+                        if let Poll::Ready(Err(err)) = self.runner.poll() {
+                            return self.fail(err);
+                        }
+                    }
+                    Poll::Ready(None) => self.end_cycle(),
+                    Poll::Ready(Some(command)) => match command.flow() {
+                        CommandFlow::GroupStart => {
+                            self.state = SchedulerState::RunGroup {
+                                command_index: command_index + 1,
+                            };
+                        }
+                        CommandFlow::GroupEnd => {
+                            return self.fail_flow(CommandFlowError::UnexpectedGroupEnd.into());
+                        }
+                        CommandFlow::Run => {
+                            defmt::println!("Run: {}", command);
 
-                defmt::println!("Run: {}", command);
+                            self.runner.run(&RunnerAction::Run(command));
 
-                self.runner.run(&RunnerAction::Run(*command));
+                            self.observer.notify(SchedulerEvent::Started {
+                                command_index,
+                                command,
+                            });
 
-                self.state = SchedulerState::RunLoop { command_index };
+                            self.state = SchedulerState::RunLoop { command_index };
+                        }
+                        flow => {
+                            match self
+                                .call_stack
+                                .next(&mut self.run_commands, command_index, flow)
+                            {
+                                Ok(Some(next_command_index)) => {
+                                    self.state = SchedulerState::Run {
+                                        command_index: next_command_index,
+                                    };
+                                }
+                                Ok(None) => self.end_cycle(),
+                                Err(err) => return self.fail_flow(err.into()),
+                            }
+                        }
+                    },
+                }
 
                 Poll::Pending
             }
+            SchedulerState::RunGroup { mut command_index } => loop {
+                match self.run_commands.get(command_index) {
+                    // keep the started commands going while the rest of the group arrives
+                    Poll::Pending => {
+                        self.state = SchedulerState::RunGroup { command_index };
+
+                        return match self.runner.poll() {
+                            Poll::Ready(Err(err)) => self.fail(err),
+                            _ => Poll::Pending,
+                        };
+                    }
+                    // the program ended without closing the group
+                    Poll::Ready(None) => {
+                        self.state = SchedulerState::RunLoop {
+                            command_index: command_index - 1,
+                        };
+
+                        return Poll::Pending;
+                    }
+                    Poll::Ready(Some(command)) => match command.flow() {
+                        CommandFlow::GroupEnd => {
+                            self.state = SchedulerState::RunLoop { command_index };
+
+                            return Poll::Pending;
+                        }
+                        CommandFlow::GroupStart
+                        | CommandFlow::Label { .. }
+                        | CommandFlow::Call { .. }
+                        | CommandFlow::Return
+                        | CommandFlow::Jump { .. }
+                        | CommandFlow::RepeatStart { .. }
+                        | CommandFlow::RepeatEnd => {
+                            return self.fail_flow(CommandFlowError::FlowInGroup.into());
+                        }
+                        CommandFlow::Run => {
+                            defmt::println!("Run: {}", command);
+
+                            self.runner.run(&RunnerAction::Run(command));
+
+                            self.observer.notify(SchedulerEvent::Started {
+                                command_index,
+                                command,
+                            });
+                        }
+                    },
+                }
+
+                command_index += 1;
+            },
             SchedulerState::RunLoop { command_index } => match self.runner.poll() {
                 Poll::Ready(Ok(())) => {
-                    let next_command_index = command_index + 1;
+                    self.retry_count = 0;
 
-                    if next_command_index >= self.run_commands.len() {
-                        self.state = SchedulerState::Stop;
+                    self.observer
+                        .notify(SchedulerEvent::Finished { command_index });
+
+                    self.state = if self.is_step_mode {
+                        SchedulerState::Step {
+                            command_index: command_index + 1,
+                        }
                     } else {
-                        self.state = SchedulerState::Run {
-                            command_index: next_command_index,
-                        };
-                    }
+                        SchedulerState::Run {
+                            command_index: command_index + 1,
+                        }
+                    };
 
                     Poll::Pending
                 }
-                Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+                Poll::Ready(Err(err)) => self.fail(err),
                 Poll::Pending => Poll::Pending,
             },
+            SchedulerState::Step { .. } => match self.runner.poll() {
+                Poll::Ready(Err(err)) => self.fail(err),
+                _ => Poll::Pending,
+            },
+            SchedulerState::Paused { .. } => match self.runner.poll() {
+                Poll::Ready(Err(err)) => self.fail(err),
+                _ => Poll::Pending,
+            },
             SchedulerState::Stop => {
                 self.runner.run(&RunnerAction::Reset);
 
-                for command in self.stop_commands.iter() {
-                    defmt::println!("Stop: {}", command);
+                self.state = SchedulerState::StopReset;
 
-                    self.runner.run(&RunnerAction::Run(*command));
+                Poll::Pending
+            }
+            SchedulerState::StopReset => match self.runner.poll() {
+                Poll::Ready(Ok(())) => {
+                    for command in self.stop_commands.iter() {
+                        defmt::println!("Stop: {}", command);
+
+                        self.runner.run(&RunnerAction::Run(*command));
+                    }
+
+                    self.state = SchedulerState::StopLoop;
+
+                    Poll::Pending
+                }
+                // don't run the error commands for a failed stop
+                Poll::Ready(Err(err)) => self.fault(err, SchedulerState::Faulted),
+                Poll::Pending => Poll::Pending,
+            },
+            SchedulerState::StopLoop => match self.runner.poll() {
+                Poll::Ready(Ok(())) => {
+                    self.state = SchedulerState::Idle;
+
+                    Poll::Pending
                 }
+                Poll::Ready(Err(err)) => self.fault(err, SchedulerState::Faulted),
+                Poll::Pending => Poll::Pending,
+            },
+            SchedulerState::ErrorReset => match self.runner.poll() {
+                Poll::Ready(Ok(())) => {
+                    for command in self.error_commands.iter() {
+                        defmt::println!("Error: {}", command);
 
-                self.state = SchedulerState::StopLoop;
+                        self.runner.run(&RunnerAction::Run(*command));
+                    }
 
-                Poll::Pending
-            }
-            SchedulerState::StopLoop => {
-                if let Poll::Ready(Ok(())) = self.runner.poll() {
-                    self.state = SchedulerState::Idle
+                    self.state = SchedulerState::ErrorLoop;
+
+                    Poll::Pending
                 }
+                // the runner can't recover, so skip the error commands
+                Poll::Ready(Err(_)) => {
+                    self.state = SchedulerState::Faulted;
+
+                    Poll::Pending
+                }
+                Poll::Pending => Poll::Pending,
+            },
+            SchedulerState::ErrorLoop => match self.runner.poll() {
+                Poll::Ready(Ok(())) => {
+                    self.state = SchedulerState::Faulted;
+
+                    Poll::Pending
+                }
+                // keep the first error, halt the error commands
+                Poll::Ready(Err(_)) => {
+                    self.runner.run(&RunnerAction::Reset);
+
+                    self.state = SchedulerState::Faulted;
+
+                    Poll::Pending
+                }
+                Poll::Pending => Poll::Pending,
+            },
+            SchedulerState::Faulted => {
+                let _ = self.runner.poll();
 
                 Poll::Pending
             }
+            SchedulerState::Estop => match self.runner.poll() {
+                Poll::Ready(Ok(())) => Poll::Ready(Ok(())),
+                Poll::Ready(Err(err)) => {
+                    let err = self.error.insert(err);
+                    self.observer.notify(SchedulerEvent::Error(err));
+
+                    Poll::Ready(Err(err))
+                }
+                Poll::Pending => Poll::Pending,
+            },
         }
     }
 }
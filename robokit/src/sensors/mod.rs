@@ -1,5 +1,9 @@
 pub mod switch;
 
+use core::fmt::Debug;
+use core::marker::PhantomData;
+use defmt::Format;
+
 use crate::error::Error;
 
 pub trait Sensor {
@@ -8,3 +12,191 @@ pub trait Sensor {
 
     fn sense(&mut self) -> Result<Option<Self::Message>, Self::Error>;
 }
+
+pub trait SensorSet {
+    type Message: Copy + Debug + Format;
+    type Id: Copy + Debug + Format + 'static;
+    type Error: Error;
+
+    const IDS: &'static [Self::Id];
+
+    // sensors only send updates, so remember the latest message of each
+    fn sense(&mut self, id: &Self::Id) -> Result<(), Self::Error>;
+    fn latest(&self, id: &Self::Id) -> Option<Self::Message>;
+}
+
+pub struct EmptySensorSet<Message> {
+    message: PhantomData<Message>,
+}
+
+impl<Message> EmptySensorSet<Message>
+where
+    Message: Copy + Debug + Format,
+{
+    pub fn new() -> Self {
+        Self {
+            message: PhantomData::<Message>,
+        }
+    }
+}
+
+impl<Message> Default for EmptySensorSet<Message>
+where
+    Message: Copy + Debug + Format,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Message> SensorSet for EmptySensorSet<Message>
+where
+    Message: Copy + Debug + Format,
+{
+    type Message = Message;
+    type Id = ();
+    type Error = ();
+
+    const IDS: &'static [Self::Id] = &[];
+
+    fn sense(&mut self, _id: &Self::Id) -> Result<(), Self::Error> {
+        // there's no such sensor
+        Err(())
+    }
+    fn latest(&self, _id: &Self::Id) -> Option<Self::Message> {
+        None
+    }
+}
+
+#[macro_export]
+macro_rules! sensor_set {
+    (
+        $type:ident { $($sensor:ident),* },
+        $message:ty,
+        $id:ident,
+        $set:ident,
+        $error:ident
+    ) => {
+        $crate::paste! {
+            #[derive(Copy, Clone, Debug, defmt::Format)]
+            pub enum $id {
+                $(
+                    [<$sensor:camel>],
+                )*
+            }
+
+            #[derive(Copy, Clone, Debug)]
+            pub enum $error<
+                $(
+                    [<$sensor:camel $type:camel>]: core::fmt::Debug,
+                )*
+            >{
+                $(
+                    [<$sensor:camel $type:camel>]([<$sensor:camel $type:camel>]),
+                )*
+            }
+
+            pub struct $set<
+                $(
+                    [<$sensor:camel $type:camel>],
+                )*
+            >
+            where
+                $(
+                    [<$sensor:camel $type:camel>]: $crate::sensors::Sensor<Message = $message>,
+                )*
+            {
+
+                $(
+                    [<$sensor:snake $type:snake>]: [<$sensor:camel $type:camel>],
+                    [<$sensor:snake $type:snake _message>]: Option<$message>,
+                )*
+            }
+
+            impl<
+                $(
+                    [<$sensor:camel $type:camel>],
+                )*
+            > $set<
+                $(
+                    [<$sensor:camel $type:camel>],
+                )*
+            >
+            where
+                $(
+                    [<$sensor:camel $type:camel>]: $crate::sensors::Sensor<Message = $message>,
+                )*
+            {
+                pub fn new(
+                    $(
+                        [<$sensor:snake $type:snake>]: [<$sensor:camel $type:camel>],
+                    )*
+                ) -> Self {
+                    Self {
+                        $(
+                            [<$sensor:snake $type:snake>],
+                            [<$sensor:snake $type:snake _message>]: None,
+                        )*
+                    }
+                }
+            }
+
+            impl<
+                $(
+                    [<$sensor:camel $type:camel>],
+                )*
+            > $crate::sensors::SensorSet for $set<
+                $(
+                    [<$sensor:camel $type:camel>],
+                )*
+            >
+            where
+                $(
+                    [<$sensor:camel $type:camel>]: $crate::sensors::Sensor<Message = $message>,
+                    [<$sensor:camel $type:camel>]::Error: core::fmt::Debug,
+                )*
+            {
+                type Message = $message;
+                type Id = $id;
+                type Error = $error<
+                    $(
+                        [<$sensor:camel $type:camel>]::Error,
+                    )*
+                >;
+
+                const IDS: &'static [Self::Id] = &[
+                    $(
+                        $id::[<$sensor:camel>],
+                    )*
+                ];
+
+                fn sense(&mut self, id: &Self::Id) -> Result<(), Self::Error> {
+                    match id {
+                        $(
+                            $id::[<$sensor:camel>] => {
+                                let message = self
+                                    .[<$sensor:snake $type:snake>]
+                                    .sense()
+                                    .map_err($error::[<$sensor:camel $type:camel>])?;
+                                if message.is_some() {
+                                    self.[<$sensor:snake $type:snake _message>] = message;
+                                }
+                                Ok(())
+                            },
+                        )*
+                    }
+                }
+
+                fn latest(&self, id: &Self::Id) -> Option<Self::Message> {
+                    match id {
+                        $(
+                            $id::[<$sensor:camel>] => {
+                                self.[<$sensor:snake $type:snake _message>]
+                            },
+                        )*
+                    }
+                }
+            }
+        }
+    };
+}
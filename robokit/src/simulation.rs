@@ -0,0 +1,458 @@
+// run a program without touching any hardware, to check a job before running it on real stock
+
+use alloc::{rc::Rc, vec::Vec};
+use core::convert::Infallible;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::task::Poll;
+use defmt::Format;
+use embedded_hal::digital::v2::OutputPin;
+use fugit::{TimerDurationU32 as TimerDuration, TimerDurationU64};
+use fugit_timer::Timer;
+use num::abs;
+use stepper::ramp_maker::MotionProfile;
+
+use crate::actuators::{
+    axis::{AxisAction, AxisError, AxisLimitSide, AxisStatus},
+    profile::{AxisProfile, AxisRamp},
+    spindle::{SpindleDriver, SpindleStatus},
+    Actuator, ActuatorStatus,
+};
+use crate::timer::SubTimer;
+
+// a clock that only moves when the simulation ticks it
+pub struct Simulation<const TIMER_HZ: u32> {
+    now: Rc<AtomicU32>,
+    tick_duration: TimerDuration<TIMER_HZ>,
+    max_duration: TimerDurationU64<TIMER_HZ>,
+}
+
+impl<const TIMER_HZ: u32> Simulation<TIMER_HZ> {
+    // a shorter tick is more accurate but slower, the simulation gives up after max_duration
+    pub fn new(
+        tick_duration: TimerDuration<TIMER_HZ>,
+        max_duration: TimerDurationU64<TIMER_HZ>,
+    ) -> Self {
+        Self {
+            now: Rc::new(AtomicU32::new(0)),
+            tick_duration,
+            max_duration,
+        }
+    }
+
+    pub fn sub(&self) -> SubTimer<TIMER_HZ> {
+        SubTimer::new(self.now.clone())
+    }
+
+    pub fn tick_duration(&self) -> TimerDuration<TIMER_HZ> {
+        self.tick_duration
+    }
+
+    pub fn max_duration(&self) -> TimerDurationU64<TIMER_HZ> {
+        self.max_duration
+    }
+
+    pub fn tick(&self) {
+        self.now
+            .fetch_add(self.tick_duration.ticks(), Ordering::SeqCst);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SimulationReport<const TIMER_HZ: u32, AxisId> {
+    pub is_complete: bool,
+    pub duration: TimerDurationU64<TIMER_HZ>,
+    pub axes: Vec<(AxisId, SimulatedAxisStatus)>,
+}
+
+impl<const TIMER_HZ: u32, AxisId> SimulationReport<TIMER_HZ, AxisId> {
+    pub fn limit_violations_count(&self) -> usize {
+        self.axes
+            .iter()
+            .map(|(_, status)| status.limit_violations_count)
+            .sum()
+    }
+}
+
+// an output pin that goes nowhere, e.g. for a simulated led
+pub struct SimulatedPin;
+
+impl OutputPin for SimulatedPin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Format)]
+pub struct SimulatedAxisStatus {
+    pub logical_position_in_millimeters: f64,
+    pub real_position_in_millimeters: f64,
+    pub is_homed: bool,
+    pub max_acceleration_in_millimeters_per_sec_per_sec: f64,
+    pub max_jerk_in_millimeters_per_sec_per_sec_per_sec: Option<f64>,
+    pub min_position_in_millimeters: f64,
+    pub max_position_in_millimeters: f64,
+    pub travel_in_millimeters: f64,
+    // moves past the limits, where a real axis would hit a limit switch
+    pub limit_violations_count: usize,
+}
+
+impl From<SimulatedAxisStatus> for AxisStatus {
+    fn from(status: SimulatedAxisStatus) -> Self {
+        AxisStatus {
+            logical_position_in_millimeters: status.logical_position_in_millimeters,
+            real_position_in_millimeters: status.real_position_in_millimeters,
+            is_homed: status.is_homed,
+            max_acceleration_in_millimeters_per_sec_per_sec: status
+                .max_acceleration_in_millimeters_per_sec_per_sec,
+            max_jerk_in_millimeters_per_sec_per_sec_per_sec: status
+                .max_jerk_in_millimeters_per_sec_per_sec_per_sec,
+        }
+    }
+}
+
+// the errors of a real axis that don't depend on its hardware
+pub type SimulatedAxisError = AxisError<Infallible, Infallible, Infallible>;
+
+// moves take as long as the motion profile of a real axis, without stepping
+pub struct SimulatedAxis<const TIMER_HZ: u32> {
+    timer: SubTimer<TIMER_HZ>,
+    max_acceleration_in_steps_per_sec_per_sec: f64,
+    max_jerk_in_steps_per_sec_per_sec_per_sec: Option<f64>,
+    steps_per_millimeter: f64,
+    limit_min_in_millimeters: f64,
+    limit_max_in_millimeters: f64,
+    home_side: AxisLimitSide,
+    is_moving: bool,
+    logical_position: f64,
+    real_position: f64,
+    is_homed: bool,
+    allow_unhomed_absolute_moves: bool,
+    soft_limits_in_millimeters: Option<(f64, f64)>,
+    error: Option<SimulatedAxisError>,
+    min_position: f64,
+    max_position: f64,
+    travel: f64,
+    limit_violations_count: usize,
+}
+
+impl<const TIMER_HZ: u32> SimulatedAxis<TIMER_HZ> {
+    // the limits are where the limit switches would be, relative to home
+    pub fn new(
+        timer: SubTimer<TIMER_HZ>,
+        max_acceleration_in_millimeters_per_sec_per_sec: f64,
+        steps_per_millimeter: f64,
+        limit_min_in_millimeters: f64,
+        limit_max_in_millimeters: f64,
+        home_side: AxisLimitSide,
+    ) -> Self {
+        Self {
+            timer,
+            max_acceleration_in_steps_per_sec_per_sec:
+                max_acceleration_in_millimeters_per_sec_per_sec * steps_per_millimeter,
+            max_jerk_in_steps_per_sec_per_sec_per_sec: None,
+            steps_per_millimeter,
+            limit_min_in_millimeters,
+            limit_max_in_millimeters,
+            home_side,
+            is_moving: false,
+            logical_position: 0_f64,
+            real_position: 0_f64,
+            is_homed: false,
+            allow_unhomed_absolute_moves: false,
+            soft_limits_in_millimeters: None,
+            error: None,
+            min_position: 0_f64,
+            max_position: 0_f64,
+            travel: 0_f64,
+            limit_violations_count: 0,
+        }
+    }
+
+    pub fn with_max_jerk(
+        mut self,
+        max_jerk_in_millimeters_per_sec_per_sec_per_sec: f64,
+    ) -> Result<Self, SimulatedAxisError> {
+        if !max_jerk_in_millimeters_per_sec_per_sec_per_sec.is_finite()
+            || max_jerk_in_millimeters_per_sec_per_sec_per_sec <= 0_f64
+        {
+            return Err(AxisError::MaxJerkOutOfRange(
+                max_jerk_in_millimeters_per_sec_per_sec_per_sec,
+            ));
+        }
+        self.max_jerk_in_steps_per_sec_per_sec_per_sec =
+            Some(max_jerk_in_millimeters_per_sec_per_sec_per_sec * self.steps_per_millimeter);
+        Ok(self)
+    }
+
+    pub fn with_soft_limits(mut self, min_in_millimeters: f64, max_in_millimeters: f64) -> Self {
+        self.soft_limits_in_millimeters = Some((min_in_millimeters, max_in_millimeters));
+        self
+    }
+
+    pub fn with_unhomed_absolute_moves(mut self) -> Self {
+        self.allow_unhomed_absolute_moves = true;
+        self
+    }
+
+    // the sum of the delays between each step, from rest to rest
+    fn move_duration(
+        &self,
+        distance_in_millimeters: f64,
+        max_velocity_in_millimeters_per_sec: f64,
+        max_acceleration_in_steps_per_sec_per_sec: f64,
+        max_jerk_in_steps_per_sec_per_sec_per_sec: Option<f64>,
+    ) -> f64 {
+        let steps = abs(distance_in_millimeters * self.steps_per_millimeter) as u32;
+        let max_velocity_in_steps_per_sec =
+            max_velocity_in_millimeters_per_sec * self.steps_per_millimeter;
+
+        profile_duration(
+            AxisRamp::from_limits(
+                max_acceleration_in_steps_per_sec_per_sec,
+                max_jerk_in_steps_per_sec_per_sec_per_sec,
+            ),
+            max_velocity_in_steps_per_sec,
+            steps,
+        )
+    }
+
+    fn start_motion(&mut self, duration_in_secs: f64) {
+        let duration =
+            TimerDuration::<TIMER_HZ>::from_ticks((duration_in_secs * (TIMER_HZ as f64)) as u32);
+        // a sub timer can't fail to start
+        let _ = self.timer.start(duration);
+        self.is_moving = true;
+    }
+
+    // there's nothing to slow down, so the axis ends up where it was going
+    fn stop_motion(&mut self) {
+        self.is_moving = false;
+        self.real_position = self.logical_position;
+    }
+}
+
+impl<const TIMER_HZ: u32> Actuator for SimulatedAxis<TIMER_HZ> {
+    type Action = AxisAction;
+    type Error = SimulatedAxisError;
+
+    fn run(&mut self, action: &Self::Action) {
+        self.error = None;
+
+        match action {
+            AxisAction::MoveRelative {
+                max_velocity_in_millimeters_per_sec,
+                distance_in_millimeters,
+            } => {
+                let max_acceleration_in_millimeters_per_sec_per_sec =
+                    self.max_acceleration_in_steps_per_sec_per_sec / self.steps_per_millimeter;
+
+                self.run(&AxisAction::MoveCoordinated {
+                    max_velocity_in_millimeters_per_sec: *max_velocity_in_millimeters_per_sec,
+                    max_acceleration_in_millimeters_per_sec_per_sec,
+                    max_jerk_in_millimeters_per_sec_per_sec_per_sec: None,
+                    position_in_millimeters: self.logical_position + distance_in_millimeters,
+                    is_relative: true,
+                })
+            }
+            AxisAction::MoveAbsolute {
+                max_velocity_in_millimeters_per_sec,
+                position_in_millimeters,
+            } => {
+                let max_acceleration_in_millimeters_per_sec_per_sec =
+                    self.max_acceleration_in_steps_per_sec_per_sec / self.steps_per_millimeter;
+
+                self.run(&AxisAction::MoveCoordinated {
+                    max_velocity_in_millimeters_per_sec: *max_velocity_in_millimeters_per_sec,
+                    max_acceleration_in_millimeters_per_sec_per_sec,
+                    max_jerk_in_millimeters_per_sec_per_sec_per_sec: None,
+                    position_in_millimeters: *position_in_millimeters,
+                    is_relative: false,
+                })
+            }
+            AxisAction::MoveCoordinated {
+                max_velocity_in_millimeters_per_sec,
+                max_acceleration_in_millimeters_per_sec_per_sec,
+                max_jerk_in_millimeters_per_sec_per_sec_per_sec,
+                position_in_millimeters,
+                is_relative,
+            } => {
+                let position = *position_in_millimeters;
+                let distance = position - self.real_position;
+
+                // rejected like on a real axis, see `AxisDevice`
+                if !is_relative && !self.is_homed && !self.allow_unhomed_absolute_moves {
+                    self.error = Some(AxisError::NotHomed);
+                    return;
+                }
+                if let (true, Some((min, max))) = (self.is_homed, self.soft_limits_in_millimeters) {
+                    if position < min || position > max {
+                        self.error = Some(AxisError::SoftLimit {
+                            requested: position,
+                            min,
+                            max,
+                        });
+                        return;
+                    }
+                }
+
+                if position < self.limit_min_in_millimeters
+                    || position > self.limit_max_in_millimeters
+                {
+                    self.limit_violations_count += 1;
+                }
+                if position < self.min_position {
+                    self.min_position = position;
+                }
+                if position > self.max_position {
+                    self.max_position = position;
+                }
+                self.travel += abs(distance);
+                self.logical_position = position;
+
+                let duration_in_secs = self.move_duration(
+                    distance,
+                    *max_velocity_in_millimeters_per_sec,
+                    max_acceleration_in_millimeters_per_sec_per_sec * self.steps_per_millimeter,
+                    match max_jerk_in_millimeters_per_sec_per_sec_per_sec {
+                        Some(max_jerk) => Some(max_jerk * self.steps_per_millimeter),
+                        None => self.max_jerk_in_steps_per_sec_per_sec_per_sec,
+                    },
+                );
+                self.start_motion(duration_in_secs);
+            }
+            AxisAction::Home {
+                max_velocity_in_millimeters_per_sec,
+                back_off_distance_in_millimeters,
+            } => {
+                // the limit switch is the back off distance past home
+                let limit_position = match self.home_side {
+                    AxisLimitSide::Min => -back_off_distance_in_millimeters,
+                    AxisLimitSide::Max => *back_off_distance_in_millimeters,
+                };
+                let towards_home_distance = limit_position - self.real_position;
+
+                self.travel += abs(towards_home_distance) + abs(*back_off_distance_in_millimeters);
+                self.logical_position = 0_f64;
+                self.is_homed = true;
+
+                let duration_in_secs = self.move_duration(
+                    towards_home_distance,
+                    *max_velocity_in_millimeters_per_sec,
+                    self.max_acceleration_in_steps_per_sec_per_sec,
+                    self.max_jerk_in_steps_per_sec_per_sec_per_sec,
+                ) + self.move_duration(
+                    *back_off_distance_in_millimeters,
+                    *max_velocity_in_millimeters_per_sec,
+                    self.max_acceleration_in_steps_per_sec_per_sec,
+                    self.max_jerk_in_steps_per_sec_per_sec_per_sec,
+                );
+                self.start_motion(duration_in_secs);
+            }
+        }
+    }
+
+    fn poll(&mut self) -> Poll<Result<(), Self::Error>> {
+        if let Some(err) = self.error.take() {
+            return Poll::Ready(Err(err));
+        }
+        if !self.is_moving {
+            return Poll::Ready(Ok(()));
+        }
+
+        match self.timer.wait() {
+            Err(nb::Error::WouldBlock) => Poll::Pending,
+            _ => {
+                self.stop_motion();
+
+                Poll::Ready(Ok(()))
+            }
+        }
+    }
+
+    fn cancel(&mut self) {
+        self.stop_motion();
+    }
+
+    fn emergency_stop(&mut self) {
+        self.stop_motion();
+        self.is_homed = false;
+    }
+}
+
+impl<const TIMER_HZ: u32> ActuatorStatus for SimulatedAxis<TIMER_HZ> {
+    type Status = SimulatedAxisStatus;
+
+    fn status(&self) -> Self::Status {
+        SimulatedAxisStatus {
+            logical_position_in_millimeters: self.logical_position,
+            real_position_in_millimeters: self.real_position,
+            is_homed: self.is_homed,
+            max_acceleration_in_millimeters_per_sec_per_sec: self
+                .max_acceleration_in_steps_per_sec_per_sec
+                / self.steps_per_millimeter,
+            max_jerk_in_millimeters_per_sec_per_sec_per_sec: self
+                .max_jerk_in_steps_per_sec_per_sec_per_sec
+                .map(|max_jerk| max_jerk / self.steps_per_millimeter),
+            min_position_in_millimeters: self.min_position,
+            max_position_in_millimeters: self.max_position,
+            travel_in_millimeters: self.travel,
+            limit_violations_count: self.limit_violations_count,
+        }
+    }
+}
+
+// the sum of the delays between each step of a motion from rest
+fn profile_duration<Profile>(mut profile: Profile, max_velocity: f64, steps: u32) -> f64
+where
+    Profile: MotionProfile<Velocity = f64, Delay = f64>,
+{
+    profile.enter_position_mode(max_velocity, steps);
+
+    let mut duration_in_secs = 0_f64;
+    while let Some(delay_in_secs) = profile.next_delay() {
+        duration_in_secs += delay_in_secs;
+    }
+
+    duration_in_secs
+}
+
+// reaches any speed instantly
+pub struct SimulatedSpindleDriver {
+    status: SpindleStatus,
+}
+
+impl SimulatedSpindleDriver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn status(&self) -> SpindleStatus {
+        self.status
+    }
+}
+
+impl Default for SimulatedSpindleDriver {
+    fn default() -> Self {
+        Self {
+            status: SpindleStatus::Off,
+        }
+    }
+}
+
+impl SpindleDriver for SimulatedSpindleDriver {
+    type Error = Infallible;
+
+    fn set(&mut self, status: SpindleStatus) {
+        self.status = status;
+    }
+
+    fn poll(&mut self) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,64 @@
+use core::task::Poll;
+use defmt::Format;
+use heapless::spsc::{Consumer, Producer, Queue};
+
+use crate::scheduler::CommandSource;
+
+#[derive(Clone, Copy, Debug, Format)]
+pub enum StreamItem<Command> {
+    Command(Command),
+    End,
+}
+
+// a lock-free single producer single consumer queue, holds one less item than its size
+pub type CommandQueue<Command, const QUEUE_SIZE: usize> = Queue<StreamItem<Command>, QUEUE_SIZE>;
+
+// when the queue is full `enqueue` gives the item back, so hold off until `ready`
+pub type CommandProducer<'a, Command, const QUEUE_SIZE: usize> =
+    Producer<'a, StreamItem<Command>, QUEUE_SIZE>;
+
+pub struct CommandStream<'a, Command, const QUEUE_SIZE: usize> {
+    consumer: Consumer<'a, StreamItem<Command>, QUEUE_SIZE>,
+    current: Option<(usize, Command)>,
+}
+
+impl<'a, Command, const QUEUE_SIZE: usize> CommandStream<'a, Command, QUEUE_SIZE> {
+    pub fn new(consumer: Consumer<'a, StreamItem<Command>, QUEUE_SIZE>) -> Self {
+        Self {
+            consumer,
+            current: None,
+        }
+    }
+}
+
+impl<'a, Command, const QUEUE_SIZE: usize> CommandSource<Command>
+    for CommandStream<'a, Command, QUEUE_SIZE>
+where
+    Command: Copy,
+{
+    fn get(&mut self, index: usize) -> Poll<Option<Command>> {
+        if let Some((current_index, command)) = self.current {
+            if current_index == index {
+                return Poll::Ready(Some(command));
+            }
+        }
+
+        match self.consumer.dequeue() {
+            Some(StreamItem::Command(command)) => {
+                self.current = Some((index, command));
+
+                Poll::Ready(Some(command))
+            }
+            Some(StreamItem::End) => {
+                self.current = None;
+
+                Poll::Ready(None)
+            }
+            None => Poll::Pending,
+        }
+    }
+
+    fn total(&self) -> Option<usize> {
+        None
+    }
+}
@@ -1,3 +1,5 @@
+use heapless::Vec;
+
 // two's complement
 pub fn i16_to_u16(num: i16) -> u16 {
     if num == 0 {
@@ -19,3 +21,17 @@ pub fn u16_to_i16(num: u16) -> i16 {
         -((u16::MAX - num) as i16) - 1
     }
 }
+
+// the array fills the vec exactly, so no command is ever left out
+pub fn vec_from_array<T, const N: usize>(array: &[T; N]) -> Vec<T, N>
+where
+    T: Clone,
+{
+    let mut vec = Vec::new();
+    for item in array {
+        if vec.push(item.clone()).is_err() {
+            break;
+        }
+    }
+    vec
+}
@@ -0,0 +1,301 @@
+// check a program before it runs, rather than halfway through a cut
+
+use core::fmt::Debug;
+use defmt::Format;
+use num::abs;
+
+use crate::actuators::{
+    axis::{AxisAction, AxisStatus},
+    spindle::SpindleAction,
+    spindle::SpindleStatus,
+};
+use crate::motion::MotionAction;
+use crate::runner::Command;
+use crate::scheduler::{CommandFlowError, ProgramPart};
+
+#[derive(Clone, Copy, Debug, Format)]
+pub struct AxisLimits {
+    pub min_position_in_millimeters: f64,
+    pub max_position_in_millimeters: f64,
+    pub max_velocity_in_millimeters_per_sec: f64,
+}
+
+#[derive(Clone, Copy, Debug, Format)]
+pub struct SpindleLimits {
+    pub max_rpm: i16,
+}
+
+#[derive(Clone, Copy, Debug, Format)]
+pub struct ValidationSettings<'a, AxisId, SpindleId> {
+    // axes without limits are only checked for homing
+    pub axes: &'a [(AxisId, AxisLimits)],
+    pub spindles: &'a [(SpindleId, SpindleLimits)],
+}
+
+#[derive(Clone, Copy, Debug, Format, PartialEq)]
+pub enum ValidationError<AxisId, SpindleId> {
+    PositionOutOfRange {
+        axis: AxisId,
+        position_in_millimeters: f64,
+        min_position_in_millimeters: f64,
+        max_position_in_millimeters: f64,
+    },
+    VelocityOutOfRange {
+        axis: AxisId,
+        velocity_in_millimeters_per_sec: f64,
+        max_velocity_in_millimeters_per_sec: f64,
+    },
+    NotHomed {
+        axis: AxisId,
+    },
+    RpmOutOfRange {
+        spindle: SpindleId,
+        rpm: i16,
+        max_rpm: i16,
+    },
+    Flow(CommandFlowError),
+}
+
+#[derive(Clone, Copy, Debug, Format, PartialEq)]
+pub struct ProgramError<AxisId, SpindleId> {
+    pub part: ProgramPart,
+    pub command_index: usize,
+    pub error: ValidationError<AxisId, SpindleId>,
+}
+
+pub struct AxisValidator<AxisId> {
+    axis: AxisId,
+    limits: Option<AxisLimits>,
+    position: Option<f64>,
+}
+
+impl<AxisId> AxisValidator<AxisId>
+where
+    AxisId: Copy + Debug + Format + PartialEq,
+{
+    pub fn new(axis: AxisId, limits: Option<AxisLimits>) -> Self {
+        Self {
+            axis,
+            limits,
+            position: None,
+        }
+    }
+
+    pub fn position(&self) -> Option<f64> {
+        self.position
+    }
+
+    // the positions of the other axes are needed for where an arc goes between its ends
+    pub fn check<const TIMER_HZ: u32, LedId, SpindleId, SwitchId, ExtraCommand, Positions>(
+        &mut self,
+        command: &Command<TIMER_HZ, LedId, AxisId, SpindleId, SwitchId, ExtraCommand>,
+        positions: Positions,
+    ) -> Result<(), ValidationError<AxisId, SpindleId>>
+    where
+        LedId: Copy + Debug + Format,
+        SpindleId: Copy + Debug + Format,
+        SwitchId: Copy + Debug + Format,
+        ExtraCommand: Copy + Debug + Format,
+        Positions: Fn(&AxisId) -> Option<f64>,
+    {
+        let arc_bounds = match command {
+            Command::Motion(motion) => self.arc_bounds(motion, &positions),
+            _ => None,
+        };
+
+        let action = match command {
+            Command::Axis(axis, action) if *axis == self.axis => *action,
+            Command::Timeout { command, .. } => return self.check(&(*command).into(), positions),
+            // no axis of a motion moves faster than the motion, so check it at that velocity
+            Command::Motion(motion) => match motion.axis_action(self.axis) {
+                Some(action) => action,
+                // an arc can move an axis without an end
+                None => return self.check_bounds(arc_bounds),
+            },
+            _ => return Ok(()),
+        };
+
+        match action {
+            AxisAction::MoveRelative {
+                max_velocity_in_millimeters_per_sec,
+                distance_in_millimeters,
+            } => {
+                self.check_velocity(max_velocity_in_millimeters_per_sec)?;
+
+                // before homing there's no knowing where a relative move ends up
+                if let Some(position) = self.position {
+                    let position = position + distance_in_millimeters;
+                    self.check_position(position)?;
+                    self.position = Some(position);
+                }
+            }
+            // the position is from where the axis was, so it's only known once homed
+            AxisAction::MoveCoordinated {
+                max_velocity_in_millimeters_per_sec,
+                position_in_millimeters,
+                is_relative: true,
+                ..
+            } => {
+                self.check_velocity(max_velocity_in_millimeters_per_sec)?;
+
+                if self.position.is_some() {
+                    self.check_position(position_in_millimeters)?;
+                    self.position = Some(position_in_millimeters);
+                }
+            }
+            AxisAction::MoveAbsolute {
+                max_velocity_in_millimeters_per_sec,
+                position_in_millimeters,
+            }
+            | AxisAction::MoveCoordinated {
+                max_velocity_in_millimeters_per_sec,
+                position_in_millimeters,
+                is_relative: false,
+                ..
+            } => {
+                self.check_velocity(max_velocity_in_millimeters_per_sec)?;
+
+                if self.position.is_none() {
+                    return Err(ValidationError::NotHomed { axis: self.axis });
+                }
+                self.check_position(position_in_millimeters)?;
+                self.position = Some(position_in_millimeters);
+            }
+            AxisAction::Home {
+                max_velocity_in_millimeters_per_sec,
+                ..
+            } => {
+                self.check_velocity(max_velocity_in_millimeters_per_sec)?;
+
+                self.position = Some(0_f64);
+            }
+        }
+
+        self.check_bounds(arc_bounds)
+    }
+
+    fn check_bounds<SpindleId>(
+        &self,
+        bounds: Option<(f64, f64)>,
+    ) -> Result<(), ValidationError<AxisId, SpindleId>> {
+        if let Some((min, max)) = bounds {
+            self.check_position(min)?;
+            self.check_position(max)?;
+        }
+
+        Ok(())
+    }
+
+    fn arc_bounds<Positions>(
+        &self,
+        motion: &MotionAction<AxisId>,
+        positions: &Positions,
+    ) -> Option<(f64, f64)>
+    where
+        Positions: Fn(&AxisId) -> Option<f64>,
+    {
+        let position = self.position?;
+
+        // an absolute arc is only placed once all of its axes are homed,
+        // while a relative arc keeps its shape wherever the other axes are
+        let is_relative = match motion {
+            MotionAction::ArcMoveAbsolute { .. } => false,
+            MotionAction::ArcMoveRelative { .. } => true,
+            MotionAction::LinearMoveAbsolute { .. } | MotionAction::LinearMoveRelative { .. } => {
+                return None
+            }
+        };
+        if !is_relative
+            && motion
+                .axes()
+                .any(|axis| axis != self.axis && positions(&axis).is_none())
+        {
+            return None;
+        }
+
+        let status = |axis: &AxisId| AxisStatus {
+            logical_position_in_millimeters: if *axis == self.axis {
+                position
+            } else {
+                positions(axis).unwrap_or(0_f64)
+            },
+            ..AxisStatus::default()
+        };
+        motion.segments(status).ok()?.arc_bounds(self.axis)
+    }
+
+    fn check_velocity<SpindleId>(
+        &self,
+        velocity_in_millimeters_per_sec: f64,
+    ) -> Result<(), ValidationError<AxisId, SpindleId>> {
+        if let Some(limits) = self.limits {
+            if velocity_in_millimeters_per_sec > limits.max_velocity_in_millimeters_per_sec {
+                return Err(ValidationError::VelocityOutOfRange {
+                    axis: self.axis,
+                    velocity_in_millimeters_per_sec,
+                    max_velocity_in_millimeters_per_sec: limits.max_velocity_in_millimeters_per_sec,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_position<SpindleId>(
+        &self,
+        position_in_millimeters: f64,
+    ) -> Result<(), ValidationError<AxisId, SpindleId>> {
+        if let Some(limits) = self.limits {
+            let range = limits.min_position_in_millimeters..=limits.max_position_in_millimeters;
+            if !range.contains(&position_in_millimeters) {
+                return Err(ValidationError::PositionOutOfRange {
+                    axis: self.axis,
+                    position_in_millimeters,
+                    min_position_in_millimeters: limits.min_position_in_millimeters,
+                    max_position_in_millimeters: limits.max_position_in_millimeters,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub fn check_spindle<const TIMER_HZ: u32, LedId, AxisId, SpindleId, SwitchId, ExtraCommand>(
+    spindles: &[(SpindleId, SpindleLimits)],
+    command: &Command<TIMER_HZ, LedId, AxisId, SpindleId, SwitchId, ExtraCommand>,
+) -> Result<(), ValidationError<AxisId, SpindleId>>
+where
+    LedId: Copy + Debug + Format,
+    AxisId: Copy + Debug + Format,
+    SpindleId: Copy + Debug + Format + PartialEq,
+    SwitchId: Copy + Debug + Format,
+    ExtraCommand: Copy + Debug + Format,
+{
+    let (spindle, rpm) = match command {
+        Command::Timeout { command, .. } => return check_spindle(spindles, &(*command).into()),
+        Command::Spindle(
+            spindle,
+            SpindleAction::Set {
+                status: SpindleStatus::On { rpm },
+            },
+        ) => (*spindle, *rpm),
+        _ => return Ok(()),
+    };
+    let limits = spindles
+        .iter()
+        .find(|(id, _)| *id == spindle)
+        .map(|(_, limits)| *limits);
+
+    if let Some(limits) = limits {
+        if abs(rpm as i32) > limits.max_rpm as i32 {
+            return Err(ValidationError::RpmOutOfRange {
+                spindle,
+                rpm,
+                max_rpm: limits.max_rpm,
+            });
+        }
+    }
+
+    Ok(())
+}